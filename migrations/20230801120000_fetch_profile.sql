@@ -0,0 +1,31 @@
+--
+-- Per-source and per-feed fetch credentials, cookies and headers.
+--
+-- The profile itself is stored encrypted in `credentials`, see `fetcher::CredentialsKey`.
+--
+
+CREATE TABLE public.fetch_profile (
+                                      id uuid DEFAULT gen_random_uuid() NOT NULL,
+                                      source_id uuid,
+                                      feed_id uuid,
+                                      nonce bytea NOT NULL,
+                                      credentials bytea NOT NULL,
+                                      create_timestamp timestamp with time zone DEFAULT now() NOT NULL,
+                                      update_timestamp timestamp with time zone DEFAULT now() NOT NULL,
+                                      CONSTRAINT fetch_profile_owner CHECK ((source_id IS NULL) <> (feed_id IS NULL))
+);
+
+ALTER TABLE ONLY public.fetch_profile
+    ADD CONSTRAINT fetch_profile_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.fetch_profile
+    ADD CONSTRAINT unique_fetch_profile_source UNIQUE (source_id);
+
+ALTER TABLE ONLY public.fetch_profile
+    ADD CONSTRAINT unique_fetch_profile_feed UNIQUE (feed_id);
+
+ALTER TABLE ONLY public.fetch_profile
+    ADD CONSTRAINT fetch_profile_source_id_fk FOREIGN KEY (source_id) REFERENCES public.source(id) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.fetch_profile
+    ADD CONSTRAINT fetch_profile_feed_id_fk FOREIGN KEY (feed_id) REFERENCES public.feed(id) ON UPDATE CASCADE ON DELETE CASCADE;
@@ -0,0 +1,10 @@
+--
+-- Full-text mode for feeds that only carry a teaser of each item.
+--
+
+ALTER TABLE public.feed
+    ADD COLUMN full_text boolean DEFAULT false NOT NULL;
+
+ALTER TABLE public.news
+    ADD COLUMN content_html text,
+    ADD COLUMN content_text text;
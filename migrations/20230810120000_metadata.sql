@@ -0,0 +1,11 @@
+--
+-- Fields filled from the metadata pages declare about themselves.
+--
+
+ALTER TABLE public.news
+    ADD COLUMN author text,
+    ADD COLUMN description text;
+
+ALTER TABLE public.company
+    ADD COLUMN description text,
+    ADD COLUMN logo_url text;
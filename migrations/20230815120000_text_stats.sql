@@ -0,0 +1,21 @@
+--
+-- Language and readability statistics of extracted text.
+--
+
+ALTER TABLE public.news
+    ADD COLUMN language character varying(3),
+    ADD COLUMN word_count integer,
+    ADD COLUMN reading_time integer,
+    ADD COLUMN flesch_reading_ease double precision,
+    ADD COLUMN flesch_kincaid_grade double precision;
+
+ALTER TABLE public.page
+    ADD COLUMN language character varying(3),
+    ADD COLUMN word_count integer,
+    ADD COLUMN reading_time integer,
+    ADD COLUMN flesch_reading_ease double precision,
+    ADD COLUMN flesch_kincaid_grade double precision;
+
+CREATE INDEX news_language_idx ON public.news USING btree (language);
+
+CREATE INDEX page_language_idx ON public.page USING btree (language);
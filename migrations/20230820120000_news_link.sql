@@ -0,0 +1,36 @@
+--
+-- Outbound links of news articles, resolved to the news items and sources they point at.
+--
+-- `url` is the canonical URL of the target, see `extractor::canonical_url`. Links to pages
+-- that aren't known yet are kept with both targets unset, and resolved once they are saved.
+--
+
+CREATE TABLE public.news_link (
+                                  id uuid DEFAULT gen_random_uuid() NOT NULL,
+                                  news_id uuid NOT NULL,
+                                  url text NOT NULL,
+                                  target_news_id uuid,
+                                  target_source_id uuid,
+                                  create_timestamp timestamp with time zone DEFAULT now() NOT NULL
+);
+
+ALTER TABLE ONLY public.news_link
+    ADD CONSTRAINT news_link_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.news_link
+    ADD CONSTRAINT unique_news_link UNIQUE (news_id, url);
+
+ALTER TABLE ONLY public.news_link
+    ADD CONSTRAINT news_link_news_id_fk FOREIGN KEY (news_id) REFERENCES public.news(id) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.news_link
+    ADD CONSTRAINT news_link_target_news_id_fk FOREIGN KEY (target_news_id) REFERENCES public.news(id) ON UPDATE CASCADE ON DELETE SET NULL;
+
+ALTER TABLE ONLY public.news_link
+    ADD CONSTRAINT news_link_target_source_id_fk FOREIGN KEY (target_source_id) REFERENCES public.source(id) ON UPDATE CASCADE ON DELETE SET NULL;
+
+CREATE INDEX news_link_url_idx ON public.news_link USING btree (url);
+
+CREATE INDEX news_link_target_news_id_idx ON public.news_link USING btree (target_news_id);
+
+CREATE INDEX news_link_target_source_id_idx ON public.news_link USING btree (target_source_id);
@@ -0,0 +1,22 @@
+--
+-- Per-domain CSS selectors for sites the generic article extraction gets wrong.
+--
+-- A rule for `example.com` also applies to its subdomains, the longest matching domain wins.
+--
+
+CREATE TABLE public.extraction_rule (
+                                        id uuid DEFAULT gen_random_uuid() NOT NULL,
+                                        domain character varying(255) NOT NULL,
+                                        content_selector text,
+                                        remove_selectors text[] DEFAULT '{}'::text[] NOT NULL,
+                                        date_selector text,
+                                        author_selector text,
+                                        create_timestamp timestamp with time zone DEFAULT now() NOT NULL,
+                                        update_timestamp timestamp with time zone DEFAULT now() NOT NULL
+);
+
+ALTER TABLE ONLY public.extraction_rule
+    ADD CONSTRAINT extraction_rule_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.extraction_rule
+    ADD CONSTRAINT unique_extraction_rule_domain UNIQUE (domain);
@@ -0,0 +1,35 @@
+--
+-- Overlapping passages of news articles and pages, for summaries and search hits to cite.
+--
+-- Offsets are in characters into `news.content_text`, or the extracted text file of a news item
+-- without it, and into `page.content`.
+--
+
+CREATE TABLE public.chunk (
+                              id uuid DEFAULT gen_random_uuid() NOT NULL,
+                              news_id uuid,
+                              page_id uuid,
+                              chunk_index integer NOT NULL,
+                              start_offset integer NOT NULL,
+                              end_offset integer NOT NULL,
+                              token_count integer NOT NULL,
+                              heading text,
+                              content text NOT NULL,
+                              create_timestamp timestamp with time zone DEFAULT now() NOT NULL,
+                              CONSTRAINT chunk_owner CHECK ((news_id IS NULL) <> (page_id IS NULL))
+);
+
+ALTER TABLE ONLY public.chunk
+    ADD CONSTRAINT chunk_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.chunk
+    ADD CONSTRAINT unique_chunk_news_index UNIQUE (news_id, chunk_index);
+
+ALTER TABLE ONLY public.chunk
+    ADD CONSTRAINT unique_chunk_page_index UNIQUE (page_id, chunk_index);
+
+ALTER TABLE ONLY public.chunk
+    ADD CONSTRAINT chunk_news_id_fk FOREIGN KEY (news_id) REFERENCES public.news(id) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.chunk
+    ADD CONSTRAINT chunk_page_id_fk FOREIGN KEY (page_id) REFERENCES public.page(id) ON UPDATE CASCADE ON DELETE CASCADE;
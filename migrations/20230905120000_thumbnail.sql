@@ -0,0 +1,13 @@
+--
+-- Thumbnails of the lead image of news items, the preview image of sources and the first image
+-- of pages, as URLs into the artifact store.
+--
+
+ALTER TABLE public.news
+    ADD COLUMN thumbnail_url text;
+
+ALTER TABLE public.source
+    ADD COLUMN thumbnail_url text;
+
+ALTER TABLE public.page
+    ADD COLUMN thumbnail_url text;
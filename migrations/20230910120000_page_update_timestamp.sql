@@ -0,0 +1,18 @@
+--
+-- Keep update_timestamp of pages current on every update. The page's ETag is derived from it,
+-- so clock_timestamp() is used rather than now() to tell apart updates in the same transaction.
+--
+
+CREATE FUNCTION public.set_update_timestamp() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    NEW.update_timestamp = clock_timestamp();
+    RETURN NEW;
+END;
+$$;
+
+CREATE TRIGGER page_update_timestamp
+    BEFORE UPDATE ON public.page
+    FOR EACH ROW
+    EXECUTE FUNCTION public.set_update_timestamp();
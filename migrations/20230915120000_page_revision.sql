@@ -0,0 +1,28 @@
+--
+-- Every version of a page's title and content, numbered from 1 per page. Rows are never updated,
+-- reverting to an old revision adds a new one pointing back at it.
+--
+
+CREATE TABLE public.page_revision (
+                                      id uuid DEFAULT gen_random_uuid() NOT NULL,
+                                      page_id uuid NOT NULL,
+                                      revision_number integer NOT NULL,
+                                      title text NOT NULL,
+                                      content text NOT NULL,
+                                      author text,
+                                      reverted_from integer,
+                                      create_timestamp timestamp with time zone DEFAULT now() NOT NULL
+);
+
+ALTER TABLE ONLY public.page_revision
+    ADD CONSTRAINT page_revision_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.page_revision
+    ADD CONSTRAINT unique_page_revision_number UNIQUE (page_id, revision_number);
+
+ALTER TABLE ONLY public.page_revision
+    ADD CONSTRAINT page_revision_page_id_fk FOREIGN KEY (page_id) REFERENCES public.page(id) ON UPDATE CASCADE ON DELETE CASCADE;
+
+-- the current version of existing pages is their first revision
+INSERT INTO public.page_revision (page_id, revision_number, title, content, create_timestamp)
+SELECT id, 1, title, content, update_timestamp FROM public.page;
@@ -0,0 +1,26 @@
+--
+-- Wiki-links between pages. `target` is the link target as written and `target_slug` its slug,
+-- which is what links are matched on. Links to pages that don't exist yet keep a NULL
+-- `target_page_id` until a matching page is saved.
+--
+
+DELETE FROM public.link;
+
+ALTER TABLE public.link
+    ALTER COLUMN target_page_id DROP NOT NULL,
+    ADD COLUMN target text NOT NULL,
+    ADD COLUMN target_slug text NOT NULL,
+    ADD COLUMN create_timestamp timestamp with time zone DEFAULT now() NOT NULL;
+
+ALTER TABLE ONLY public.link
+    ADD CONSTRAINT unique_link_source_target UNIQUE (source_page_id, target_slug);
+
+ALTER TABLE ONLY public.link
+    DROP CONSTRAINT source_page_fk,
+    ADD CONSTRAINT source_page_fk FOREIGN KEY (source_page_id) REFERENCES public.page(id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.link
+    DROP CONSTRAINT target_page_fk,
+    ADD CONSTRAINT target_page_fk FOREIGN KEY (target_page_id) REFERENCES public.page(id) ON DELETE SET NULL;
+
+CREATE INDEX link_target_page_id_idx ON public.link (target_page_id);
@@ -0,0 +1,22 @@
+--
+-- Tags of pages, added by hand or from `#hashtags` in the content. Hashtag tags are
+-- `from_content` and replaced whenever the content is saved, tags added by hand are kept.
+-- Tag names are lower case.
+--
+
+ALTER TABLE public.page_tag
+    ADD COLUMN from_content boolean DEFAULT false NOT NULL,
+    ADD COLUMN create_timestamp timestamp with time zone DEFAULT now() NOT NULL;
+
+ALTER TABLE ONLY public.page_tag
+    ADD CONSTRAINT unique_page_tag UNIQUE (page_id, tag_id);
+
+ALTER TABLE ONLY public.page_tag
+    DROP CONSTRAINT page_fk,
+    ADD CONSTRAINT page_fk FOREIGN KEY (page_id) REFERENCES public.page(id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.page_tag
+    DROP CONSTRAINT tag_fk,
+    ADD CONSTRAINT tag_fk FOREIGN KEY (tag_id) REFERENCES public.tag(id) ON DELETE CASCADE;
+
+CREATE INDEX page_tag_tag_id_idx ON public.page_tag (tag_id);
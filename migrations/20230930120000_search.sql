@@ -0,0 +1,35 @@
+--
+-- Full-text search over pages, news items and sources. The tsvectors are computed by these
+-- functions and indexed as expressions rather than stored as columns, which would otherwise
+-- show up in every `SELECT *` mapped onto the models. Queries must call the same functions for
+-- the indexes to be used.
+--
+-- Titles and names weigh more than content. Stemming is English, as is most of what's stored.
+--
+
+CREATE FUNCTION public.page_search_vector(title text, content text) RETURNS tsvector
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE
+    AS $$
+SELECT setweight(to_tsvector('english'::regconfig, coalesce(title, '')), 'A')
+    || setweight(to_tsvector('english'::regconfig, coalesce(content, '')), 'B')
+$$;
+
+CREATE FUNCTION public.news_search_vector(title text, content_text text) RETURNS tsvector
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE
+    AS $$
+SELECT setweight(to_tsvector('english'::regconfig, coalesce(title, '')), 'A')
+    || setweight(to_tsvector('english'::regconfig, coalesce(content_text, '')), 'B')
+$$;
+
+CREATE FUNCTION public.source_search_vector(name text, description text) RETURNS tsvector
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE
+    AS $$
+SELECT setweight(to_tsvector('english'::regconfig, coalesce(name, '')), 'A')
+    || setweight(to_tsvector('english'::regconfig, coalesce(description, '')), 'B')
+$$;
+
+CREATE INDEX page_search_idx ON public.page USING gin (public.page_search_vector(title, content));
+
+CREATE INDEX news_search_idx ON public.news USING gin (public.news_search_vector(title, content_text));
+
+CREATE INDEX source_search_idx ON public.source USING gin (public.source_search_vector(name, description));
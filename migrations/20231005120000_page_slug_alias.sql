@@ -0,0 +1,26 @@
+--
+-- Former slugs of pages, so that links to a page keep working after its slug changes. An alias
+-- is dropped when another page of the garden takes its slug.
+--
+
+CREATE TABLE public.page_slug_alias (
+                                        id uuid DEFAULT gen_random_uuid() NOT NULL,
+                                        garden_id uuid NOT NULL,
+                                        slug character varying NOT NULL,
+                                        page_id uuid NOT NULL,
+                                        create_timestamp timestamp with time zone DEFAULT now() NOT NULL
+);
+
+ALTER TABLE ONLY public.page_slug_alias
+    ADD CONSTRAINT page_slug_alias_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.page_slug_alias
+    ADD CONSTRAINT unique_page_slug_alias UNIQUE (garden_id, slug);
+
+ALTER TABLE ONLY public.page_slug_alias
+    ADD CONSTRAINT page_slug_alias_garden_id_fk FOREIGN KEY (garden_id) REFERENCES public.garden(id) ON DELETE CASCADE;
+
+ALTER TABLE ONLY public.page_slug_alias
+    ADD CONSTRAINT page_slug_alias_page_id_fk FOREIGN KEY (page_id) REFERENCES public.page(id) ON DELETE CASCADE;
+
+CREATE INDEX page_slug_alias_page_id_idx ON public.page_slug_alias (page_id);
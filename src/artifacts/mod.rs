@@ -0,0 +1,78 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use axum::Router;
+use tower_http::services::ServeDir;
+
+mod thumbnail;
+
+pub(crate) use thumbnail::save_thumbnails;
+
+/// Where generated files like thumbnails are stored.
+const ARTIFACT_DIR: &str = "downloads/artifacts";
+
+/// The path artifacts are served under.
+const ARTIFACT_PATH: &str = "/artifacts";
+
+/// Store an artifact under `key`, a relative path like `thumbnails/<id>/320.webp`, returning
+/// the URL it's served at.
+pub(crate) fn save_artifact(key: &str, data: &[u8]) -> io::Result<String> {
+    let path = Path::new(ARTIFACT_DIR).join(key);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, data)?;
+    Ok(artifact_url(key))
+}
+
+/// Where to store an artifact that's written by something else, like a whole directory, and the
+/// URL it's served at.
+pub(crate) fn artifact_location(key: &str) -> (PathBuf, String) {
+    (Path::new(ARTIFACT_DIR).join(key), artifact_url(key))
+}
+
+fn artifact_url(key: &str) -> String {
+    format!("{}/{}", ARTIFACT_PATH, key)
+}
+
+/// The stored file an artifact URL like `/artifacts/thumbnails/<id>/320.webp` is served from.
+pub(crate) fn artifact_file(url: &str) -> Option<PathBuf> {
+    let key = url.strip_prefix(ARTIFACT_PATH)?.strip_prefix('/')?;
+    let key = Path::new(key);
+    if key.components().any(|component| !matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    Some(Path::new(ARTIFACT_DIR).join(key))
+}
+
+/// Serve stored artifacts as static files.
+pub(crate) fn router<S>() -> Router<S> where S: Clone + Send + Sync + 'static {
+    Router::new().nest_service(ARTIFACT_PATH, ServeDir::new(ARTIFACT_DIR))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, RgbaImage};
+
+    use super::thumbnail::*;
+
+    #[test]
+    fn test_make_thumbnails() {
+        let mut png = Cursor::new(Vec::new());
+        RgbaImage::from_pixel(800, 400, image::Rgba([200, 120, 40, 255]))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+
+        let thumbnails = make_thumbnails(png.get_ref()).unwrap();
+        assert_eq!(thumbnails.len(), THUMBNAIL_WIDTHS.len() * 2);
+        let sizes: Vec<(u32, u32, ThumbnailFormat)> = thumbnails.iter().map(|t| (t.width, t.height, t.format)).collect();
+        assert!(sizes.contains(&(320, 160, ThumbnailFormat::Webp)));
+        assert!(sizes.contains(&(640, 320, ThumbnailFormat::Jpeg)));
+        assert!(thumbnails.iter().all(|t| !t.data.is_empty()));
+
+        assert!(make_thumbnails(b"not an image").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+
+use crate::artifacts::save_artifact;
+
+/// Widths thumbnails are generated at, the largest is the one exposed as `thumbnail_url`.
+pub(crate) const THUMBNAIL_WIDTHS: [u32; 2] = [320, 640];
+
+/// Encoder quality, out of 100.
+const THUMBNAIL_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ThumbnailFormat {
+    Webp,
+    Jpeg,
+}
+
+impl ThumbnailFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Webp => "webp",
+            ThumbnailFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// An encoded thumbnail.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Thumbnail {
+    /// The entry of `THUMBNAIL_WIDTHS` the thumbnail was made for.
+    pub target_width: u32,
+    pub width: u32,
+    pub height: u32,
+    pub format: ThumbnailFormat,
+    pub data: Vec<u8>,
+}
+
+/// Decode an image and resize it to each of `THUMBNAIL_WIDTHS`, as WebP and as JPEG for
+/// clients without WebP support.
+///
+/// Images are never scaled up, an image narrower than a width is encoded at its own size.
+pub(crate) fn make_thumbnails(image: &[u8]) -> anyhow::Result<Vec<Thumbnail>> {
+    let image = image::load_from_memory(image)?;
+    let mut thumbnails = Vec::new();
+    for width in THUMBNAIL_WIDTHS {
+        let resized = if image.width() > width {
+            image.thumbnail(width, u32::MAX)
+        } else {
+            image.clone()
+        };
+        let rgba = resized.to_rgba8();
+        let webp = webp::Encoder::from_rgba(&rgba, resized.width(), resized.height())
+            .encode(THUMBNAIL_QUALITY as f32);
+        thumbnails.push(Thumbnail {
+            target_width: width,
+            width: resized.width(),
+            height: resized.height(),
+            format: ThumbnailFormat::Webp,
+            data: webp.to_vec(),
+        });
+
+        // JPEG has no alpha channel
+        let mut jpeg = Cursor::new(Vec::new());
+        JpegEncoder::new_with_quality(&mut jpeg, THUMBNAIL_QUALITY)
+            .encode_image(&DynamicImage::ImageRgb8(resized.to_rgb8()))?;
+        thumbnails.push(Thumbnail {
+            target_width: width,
+            width: resized.width(),
+            height: resized.height(),
+            format: ThumbnailFormat::Jpeg,
+            data: jpeg.into_inner(),
+        });
+    }
+    Ok(thumbnails)
+}
+
+/// Generate and store the thumbnails of an image as `thumbnails/<id>/<width>.<extension>`,
+/// returning the URL of the largest WebP thumbnail.
+///
+/// `id` should identify the original image, so that the same image isn't stored twice.
+pub(crate) fn save_thumbnails(id: &str, image: &[u8]) -> anyhow::Result<String> {
+    let mut largest = None;
+    for thumbnail in make_thumbnails(image)? {
+        // name by the target width, so that URLs don't depend on the original's size
+        let key = format!("thumbnails/{}/{}.{}", id, thumbnail.target_width, thumbnail.format.extension());
+        let url = save_artifact(&key, &thumbnail.data)?;
+        if thumbnail.format == ThumbnailFormat::Webp {
+            largest = Some(url);
+        }
+    }
+    largest.ok_or_else(|| anyhow::anyhow!("no thumbnails generated"))
+}
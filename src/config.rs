@@ -1,3 +1,6 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
 /// The configuration parameters for the application.
 ///
 /// These can either be passed on the command line, or pulled from environment variables.
@@ -17,7 +20,88 @@ pub struct Config {
     #[clap(long, env)]
     pub port: Option<u16>,
 
+    /// The URL the server is reached at, e.g. `https://garden.example.com`, for the absolute
+    /// links of the garden feeds. Without it they link relative to the server's root.
+    #[clap(long, env)]
+    pub public_url: Option<String>,
+
     /// The connection URL for the NATS server.
     #[clap(long, env, default_value = "nats://localhost:4222")]
     pub nats_url: String,
+
+    /// Seconds to wait for a connection when fetching webpages and feeds.
+    #[clap(long, env, default_value = "10")]
+    pub fetch_connect_timeout: u64,
+
+    /// Seconds to wait for a response or for the next chunk of a response body.
+    #[clap(long, env, default_value = "30")]
+    pub fetch_read_timeout: u64,
+
+    /// The maximum size in bytes of a fetched response body.
+    #[clap(long, env, default_value = "10485760")]
+    pub fetch_max_body_size: usize,
+
+    /// The maximum number of redirects to follow when fetching.
+    #[clap(long, env, default_value = "10")]
+    pub fetch_max_redirects: u32,
+
+    /// An optional proxy URL that all fetches are routed through.
+    #[clap(long, env)]
+    pub fetch_proxy: Option<String>,
+
+    /// Accept invalid TLS certificates when fetching, e.g. for self-signed intranet hosts.
+    ///
+    /// This disables certificate verification for every fetch, so leave it off unless needed.
+    #[clap(long, env)]
+    pub fetch_allow_insecure: bool,
+
+    /// Base64 encoded 32 byte key used to encrypt per-source fetch credentials.
+    #[clap(long, env)]
+    pub fetch_credentials_key: Option<String>,
+
+    /// The maximum number of concurrent fetches during bulk ingestion.
+    #[clap(long, env, default_value = "8")]
+    pub ingest_concurrency: usize,
+
+    /// The maximum number of concurrent fetches to any one host during bulk ingestion.
+    #[clap(long, env, default_value = "2")]
+    pub ingest_per_host_concurrency: usize,
+
+    /// The address to accept newsletter emails on over SMTP, e.g. `127.0.0.1:2525`.
+    ///
+    /// The SMTP listener is only started when this is set.
+    #[clap(long, env)]
+    pub smtp_addr: Option<SocketAddr>,
+
+    /// A task to run instead of starting the server.
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Export the published pages of a garden as a static site.
+    Export {
+        /// The slug of the garden.
+        garden: String,
+
+        /// The directory to write the site to, or the zip file with `--zip`.
+        output: PathBuf,
+
+        /// Write a zip file instead of a directory.
+        #[clap(long)]
+        zip: bool,
+
+        /// The URL the site will be published at, for the absolute URLs of the sitemap and feed.
+        #[clap(long)]
+        base_url: Option<String>,
+    },
+    /// Import an Obsidian vault or a folder of Markdown notes into a garden.
+    Import {
+        /// The slug of the garden.
+        garden: String,
+
+        /// The vault's directory, or a zip file of it.
+        vault: PathBuf,
+    },
 }
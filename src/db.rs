@@ -1,7 +1,10 @@
+use std::collections::HashSet;
 use std::env;
-use sqlx::{Error, Pool, Postgres, query_as};
+use chrono::{DateTime, Utc};
+use sqlx::{Error, PgConnection, Pool, Postgres, query_as};
 use sqlx::postgres::{PgPoolOptions, PgQueryResult};
-use crate::models::{Company, Feed, Garden, NewsItem, Page, Source, SourceType, Tool};
+use crate::extractor::{Chunk, Organization, SiteRules, TextStats, WikiLink};
+use crate::models::{free_slug, ChunkRecord, Citation, Company, ExtractionRule, Feed, FetchProfileRecord, Garden, NewsItem, Page, PageLink, PageRevision, SearchFilter, SearchHit, Source, SourceType, Tag, TagCount, TextFilter, Tool, SEARCH_MARK_END, SEARCH_MARK_START};
 
 #[allow(dead_code)]
 pub(crate) async fn companies(pool: &Pool<Postgres>) -> Result<Vec<Company>, Error> {
@@ -43,20 +46,32 @@ pub(crate) async fn garden_by_id(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Resu
         .await
 }
 
-pub(crate) async fn pages_by_garden_slug(pool: &Pool<Postgres>, slug: &String) -> Result<Vec<Page>, Error> {
+pub(crate) async fn pages_by_garden_slug(pool: &Pool<Postgres>, slug: &String, filter: &TextFilter, tag: Option<&str>) -> Result<Vec<Page>, Error> {
     query_as!(Page, r#"SELECT page.*
 FROM garden
 inner join page on page.garden_id = garden.id
-WHERE garden.slug = $1"#, slug)
+WHERE garden.slug = $1
+  AND ($2::varchar IS NULL OR page.language = $2)
+  AND ($3::integer IS NULL OR page.reading_time >= $3)
+  AND ($4::integer IS NULL OR page.reading_time <= $4)
+  AND ($5::varchar IS NULL OR EXISTS (
+      SELECT 1 FROM page_tag JOIN tag ON tag.id = page_tag.tag_id
+      WHERE page_tag.page_id = page.id AND tag.name = lower($5)))"#, slug, filter.language.as_deref(), filter.min_reading_time, filter.max_reading_time, tag)
         .fetch_all(&*pool)
         .await
 }
 
-pub(crate) async fn pages_by_garden_slug_and_type(pool: &Pool<Postgres>, slug: &String, page_type: &String) -> Result<Vec<Page>, Error> {
+pub(crate) async fn pages_by_garden_slug_and_type(pool: &Pool<Postgres>, slug: &String, page_type: &String, filter: &TextFilter, tag: Option<&str>) -> Result<Vec<Page>, Error> {
     query_as!(Page, r#"SELECT page.*
 FROM garden
 inner join page on page.garden_id = garden.id
-WHERE garden.slug = $1 and page.page_type = $2"#, slug, page_type)
+WHERE garden.slug = $1 and page.page_type = $2
+  AND ($3::varchar IS NULL OR page.language = $3)
+  AND ($4::integer IS NULL OR page.reading_time >= $4)
+  AND ($5::integer IS NULL OR page.reading_time <= $5)
+  AND ($6::varchar IS NULL OR EXISTS (
+      SELECT 1 FROM page_tag JOIN tag ON tag.id = page_tag.tag_id
+      WHERE page_tag.page_id = page.id AND tag.name = lower($6)))"#, slug, page_type, filter.language.as_deref(), filter.min_reading_time, filter.max_reading_time, tag)
         .fetch_all(&*pool)
         .await
 }
@@ -67,9 +82,51 @@ pub(crate) async fn page_by_id(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result
         .await
 }
 
-pub(crate) async fn page_by_slug(pool: &Pool<Postgres>, slug: &String) -> Result<Page, Error> {
-    query_as!(Page, r#"SELECT * FROM page WHERE slug = $1"#, slug)
-        .fetch_one(&*pool)
+/// Pages with a slug, in any garden. Slugs are only unique within a garden.
+pub(crate) async fn pages_by_slug(pool: &Pool<Postgres>, slug: &String) -> Result<Vec<Page>, Error> {
+    query_as!(Page, r#"SELECT * FROM page WHERE slug = $1 LIMIT 2"#, slug)
+        .fetch_all(&*pool)
+        .await
+}
+
+pub(crate) async fn garden_page(pool: &Pool<Postgres>, garden_slug: &String, slug: &String) -> Result<Option<Page>, Error> {
+    query_as!(Page, r#"SELECT page.*
+FROM garden
+inner join page on page.garden_id = garden.id
+WHERE garden.slug = $1 AND page.slug = $2"#, garden_slug, slug)
+        .fetch_optional(&*pool)
+        .await
+}
+
+/// The current slug of the page of a garden that had `slug` before it was changed.
+pub(crate) async fn page_slug_alias(pool: &Pool<Postgres>, garden_slug: &String, slug: &String) -> Result<Option<String>, Error> {
+    sqlx::query_scalar!(r#"
+SELECT page.slug
+FROM page_slug_alias alias
+JOIN garden ON garden.id = alias.garden_id
+JOIN page ON page.id = alias.page_id
+WHERE garden.slug = $1 AND alias.slug = $2
+"#, garden_slug, slug)
+        .fetch_optional(&*pool)
+        .await
+}
+
+/// `slug`, or the first of `slug-2`, `slug-3`, ... that no other page of the garden has.
+pub(crate) async fn unique_page_slug(pool: &Pool<Postgres>, garden_id: &uuid::Uuid, slug: &str, page_id: Option<&uuid::Uuid>) -> Result<String, Error> {
+    let taken: HashSet<String> = sqlx::query_scalar!(r#"
+SELECT slug FROM page
+WHERE garden_id = $1 AND (slug = $2 OR slug LIKE $2 || '-%') AND ($3::uuid IS NULL OR id <> $3)
+"#, garden_id, slug, page_id)
+        .fetch_all(&*pool)
+        .await?
+        .into_iter()
+        .collect();
+    Ok(free_slug(slug, &taken))
+}
+
+pub(crate) async fn delete_page(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<PgQueryResult, Error> {
+    sqlx::query!("DELETE FROM page WHERE id = $1", id)
+        .execute(&*pool)
         .await
 }
 
@@ -97,8 +154,11 @@ pub(crate) async fn gardens(pool: &Pool<Postgres>) -> Result<Vec<Garden>, Error>
         .await
 }
 
-pub(crate) async fn pages(pool: &Pool<Postgres>) -> Result<Vec<Page>, Error> {
-    query_as!(Page, r#"SELECT * FROM page"#)
+pub(crate) async fn pages(pool: &Pool<Postgres>, filter: &TextFilter) -> Result<Vec<Page>, Error> {
+    query_as!(Page, r#"SELECT * FROM page
+WHERE ($1::varchar IS NULL OR page.language = $1)
+  AND ($2::integer IS NULL OR page.reading_time >= $2)
+  AND ($3::integer IS NULL OR page.reading_time <= $3)"#, filter.language.as_deref(), filter.min_reading_time, filter.max_reading_time)
         .fetch_all(&*pool)
         .await
 }
@@ -111,22 +171,600 @@ pub(crate) async fn feeds(pool: &Pool<Postgres>) -> Result<Vec<Feed>, Error> {
 }
 
 #[allow(dead_code)]
-pub(crate) async fn news(pool: &Pool<Postgres>) -> Result<Vec<NewsItem>, Error> {
-    query_as!(NewsItem, r#"SELECT * FROM news"#)
+pub(crate) async fn news(pool: &Pool<Postgres>, filter: &TextFilter) -> Result<Vec<NewsItem>, Error> {
+    query_as!(NewsItem, r#"SELECT * FROM news
+WHERE ($1::varchar IS NULL OR news.language = $1)
+  AND ($2::integer IS NULL OR news.reading_time >= $2)
+  AND ($3::integer IS NULL OR news.reading_time <= $3)"#, filter.language.as_deref(), filter.min_reading_time, filter.max_reading_time)
+        .fetch_all(&*pool)
+        .await
+}
+
+pub(crate) async fn news_by_id(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<Option<NewsItem>, Error> {
+    query_as!(NewsItem, r#"SELECT * FROM news WHERE id = $1"#, id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub(crate) async fn feed_by_id(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<Feed, Error> {
+    query_as!(Feed, r#"SELECT * FROM feed WHERE id = $1"#, id)
+        .fetch_one(&*pool)
+        .await
+}
+
+pub(crate) async fn update_feed_full_text(pool: &Pool<Postgres>, id: &uuid::Uuid, full_text: &bool) -> Result<Feed, Error> {
+    query_as!(Feed, r#"UPDATE feed SET full_text = $1 WHERE id = $2 RETURNING *"#, full_text, id)
+        .fetch_one(&*pool)
+        .await
+}
+
+pub(crate) async fn update_news_content(pool: &Pool<Postgres>, id: &uuid::Uuid, content_html: Option<&str>, content_text: &str) -> Result<PgQueryResult, Error> {
+    sqlx::query!("UPDATE news SET content_html = $1, content_text = $2 WHERE id = $3", content_html, content_text, id)
+        .execute(&*pool)
+        .await
+}
+
+/// Fill in the author and description of a news item, keeping existing values.
+pub(crate) async fn update_news_metadata(pool: &Pool<Postgres>, id: &uuid::Uuid, author: &Option<String>, description: &Option<String>) -> Result<PgQueryResult, Error> {
+    sqlx::query!("UPDATE news SET author = COALESCE(author, $1), description = COALESCE(description, $2) WHERE id = $3", author.as_deref(), description.as_deref(), id)
+        .execute(&*pool)
+        .await
+}
+
+pub(crate) async fn update_news_thumbnail(pool: &Pool<Postgres>, id: &uuid::Uuid, thumbnail_url: &str) -> Result<PgQueryResult, Error> {
+    sqlx::query!("UPDATE news SET thumbnail_url = $1 WHERE id = $2", thumbnail_url, id)
+        .execute(pool)
+        .await
+}
+
+pub(crate) async fn update_source_thumbnail(pool: &Pool<Postgres>, id: &uuid::Uuid, thumbnail_url: &str) -> Result<PgQueryResult, Error> {
+    sqlx::query!("UPDATE source SET thumbnail_url = $1 WHERE id = $2", thumbnail_url, id)
+        .execute(pool)
+        .await
+}
+
+pub(crate) async fn update_page_thumbnail(pool: &Pool<Postgres>, id: &uuid::Uuid, thumbnail_url: &str) -> Result<PgQueryResult, Error> {
+    sqlx::query!("UPDATE page SET thumbnail_url = $1 WHERE id = $2", thumbnail_url, id)
+        .execute(pool)
+        .await
+}
+
+pub(crate) async fn update_news_text_stats(pool: &Pool<Postgres>, id: &uuid::Uuid, stats: &TextStats) -> Result<PgQueryResult, Error> {
+    sqlx::query!(r#"
+UPDATE news SET language = $1, word_count = $2, reading_time = $3, flesch_reading_ease = $4, flesch_kincaid_grade = $5
+WHERE id = $6
+"#,
+        stats.language.as_deref(), stats.word_count, stats.reading_time, stats.flesch_reading_ease, stats.flesch_kincaid_grade, id)
+        .execute(&*pool)
+        .await
+}
+
+/// Save the outbound links of a news item, resolving them to known news and sources.
+///
+/// `links` are pairs of canonical URL and host, sources are matched on their host.
+pub(crate) async fn save_news_links(pool: &Pool<Postgres>, id: &uuid::Uuid, links: &[(String, String)]) -> Result<u64, Error> {
+    let mut saved = 0;
+    for (link, host) in links {
+        let result = sqlx::query!(r#"
+INSERT INTO news_link (news_id, url, target_news_id, target_source_id)
+SELECT news.id,
+       $2,
+       (SELECT target.id FROM news target WHERE target.url = $2 AND target.id <> news.id LIMIT 1),
+       (SELECT source.id FROM source
+        WHERE lower(substring(source.url from '^[a-zA-Z]+://(?:www\.)?([^/:?#]+)')) = $3
+        ORDER BY length(source.url)
+        LIMIT 1)
+FROM news
+WHERE news.id = $1
+ON CONFLICT (news_id, url) DO NOTHING
+"#, id, link, host)
+            .execute(&*pool)
+            .await?;
+        saved += result.rows_affected();
+    }
+    Ok(saved)
+}
+
+/// Point links to `canonical_url` that were saved before the news item was known at it.
+pub(crate) async fn resolve_news_links(pool: &Pool<Postgres>, id: &uuid::Uuid, canonical_url: &str) -> Result<PgQueryResult, Error> {
+    sqlx::query!(r#"
+UPDATE news_link SET target_news_id = $1
+WHERE url = $2 AND target_news_id IS NULL AND news_id <> $1
+"#, id, canonical_url)
+        .execute(&*pool)
+        .await
+}
+
+/// The pages a news item links to.
+pub(crate) async fn news_cites(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<Vec<Citation>, Error> {
+    query_as!(Citation, r#"
+SELECT news_link.url, target.id AS "news_id?", target.title AS "title?", source.id AS "source_id?", source.name AS "source_name?", news_link.create_timestamp
+FROM news_link
+LEFT JOIN news target ON target.id = news_link.target_news_id
+LEFT JOIN source ON source.id = news_link.target_source_id
+WHERE news_link.news_id = $1
+ORDER BY news_link.url
+"#, id)
         .fetch_all(&*pool)
         .await
 }
 
+/// The news items linking to a news item.
+pub(crate) async fn news_cited_by(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<Vec<Citation>, Error> {
+    query_as!(Citation, r#"
+SELECT news.url, news.id AS "news_id?", news.title AS "title?", source.id AS "source_id?", source.name AS "source_name?", news_link.create_timestamp
+FROM news_link
+JOIN news ON news.id = news_link.news_id
+JOIN feed ON feed.id = news.feed_id
+JOIN source ON source.id = feed.source_id
+WHERE news_link.target_news_id = $1
+ORDER BY news.published_timestamp DESC
+"#, id)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// The pages the news items of a source link to, other than the source's own pages.
+pub(crate) async fn source_cites(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<Vec<Citation>, Error> {
+    query_as!(Citation, r#"
+SELECT DISTINCT ON (news_link.url) news_link.url, target.id AS "news_id?", target.title AS "title?", source.id AS "source_id?", source.name AS "source_name?", news_link.create_timestamp
+FROM news_link
+JOIN news ON news.id = news_link.news_id
+JOIN feed ON feed.id = news.feed_id
+LEFT JOIN news target ON target.id = news_link.target_news_id
+LEFT JOIN source ON source.id = news_link.target_source_id
+WHERE feed.source_id = $1 AND news_link.target_source_id IS DISTINCT FROM $1
+ORDER BY news_link.url, news_link.create_timestamp
+"#, id)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// The news items of other sources linking to a source or any of its news items.
+pub(crate) async fn source_cited_by(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<Vec<Citation>, Error> {
+    query_as!(Citation, r#"
+SELECT DISTINCT ON (news.id) news.url, news.id AS "news_id?", news.title AS "title?", source.id AS "source_id?", source.name AS "source_name?", news_link.create_timestamp
+FROM news_link
+JOIN news ON news.id = news_link.news_id
+JOIN feed ON feed.id = news.feed_id
+JOIN source ON source.id = feed.source_id
+LEFT JOIN news target ON target.id = news_link.target_news_id
+LEFT JOIN feed target_feed ON target_feed.id = target.feed_id
+WHERE (news_link.target_source_id = $1 OR target_feed.source_id = $1) AND source.id <> $1
+ORDER BY news.id, news_link.create_timestamp
+"#, id)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// Fill in the fields of a company from its organization metadata, keeping existing values.
+pub(crate) async fn update_company_metadata(pool: &Pool<Postgres>, id: &uuid::Uuid, organization: &Organization) -> Result<Company, Error> {
+    sqlx::query_as!(Company, r#"
+UPDATE company SET
+    description = COALESCE(description, $1),
+    logo_url = COALESCE(logo_url, $2),
+    address = COALESCE(address, $3),
+    ticker = COALESCE(ticker, $4),
+    num_employees_min = COALESCE(num_employees_min, $5),
+    num_employees_max = COALESCE(num_employees_max, $6),
+    update_timestamp = now()
+WHERE id = $7
+RETURNING *
+"#,
+        organization.description.as_deref(), organization.logo.as_deref(), organization.address.as_deref(),
+        organization.ticker.as_deref().filter(|ticker| ticker.len() <= 10),
+        organization.num_employees_min, organization.num_employees_max, id)
+        .fetch_one(&*pool)
+        .await
+}
+
+/// Save a new page along with its first revision.
 pub(crate) async fn save_page(pool: &Pool<Postgres>,
                               title: &String,
                               content: &String,
                               garden_id: &uuid::Uuid,
                               published: &bool,
                               slug: &String,
-                              page_type: &String) -> Result<Page, Error> {
-    return sqlx::query_as!(Page, "INSERT INTO page (title, content, garden_id, published, slug, page_type) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
-            title, content, garden_id, published, slug, page_type)
-        .fetch_one(pool)
+                              page_type: &String,
+                              stats: &TextStats,
+                              author: Option<&str>) -> Result<Page, Error> {
+    let mut tx = pool.begin().await?;
+    let page = sqlx::query_as!(Page, r#"
+INSERT INTO page (title, content, garden_id, published, slug, page_type, language, word_count, reading_time, flesch_reading_ease, flesch_kincaid_grade)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+RETURNING *
+"#,
+            title, content, garden_id, published, slug, page_type,
+            stats.language.as_deref(), stats.word_count, stats.reading_time, stats.flesch_reading_ease, stats.flesch_kincaid_grade)
+        .fetch_one(&mut *tx)
+        .await?;
+    save_page_revision(&mut tx, &page, author, None).await?;
+    drop_page_slug_alias(&mut tx, &page).await?;
+    tx.commit().await?;
+    Ok(page)
+}
+
+/// Update a page and save the result as a new revision, unless the page changed since
+/// `update_timestamp`. Returns `None` if it did, or if the page doesn't exist.
+///
+/// A changed slug is kept as an alias of the page.
+///
+/// `reverted_from` is the number of the revision whose title and content are being restored.
+pub(crate) async fn update_page(pool: &Pool<Postgres>,
+                                id: &uuid::Uuid,
+                                update_timestamp: &DateTime<Utc>,
+                                title: &String,
+                                content: &String,
+                                published: &bool,
+                                slug: &String,
+                                page_type: &String,
+                                stats: &TextStats,
+                                author: Option<&str>,
+                                reverted_from: Option<i32>) -> Result<Option<Page>, Error> {
+    let mut tx = pool.begin().await?;
+    let previous_slug = sqlx::query_scalar!("SELECT slug FROM page WHERE id = $1 FOR UPDATE", id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let page = sqlx::query_as!(Page, r#"
+UPDATE page
+SET title = $3, content = $4, published = $5, slug = $6, page_type = $7,
+    language = $8, word_count = $9, reading_time = $10, flesch_reading_ease = $11, flesch_kincaid_grade = $12
+WHERE id = $1 AND update_timestamp = $2
+RETURNING *
+"#,
+            id, update_timestamp, title, content, published, slug, page_type,
+            stats.language.as_deref(), stats.word_count, stats.reading_time, stats.flesch_reading_ease, stats.flesch_kincaid_grade)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if let Some(page) = &page {
+        save_page_revision(&mut tx, page, author, reverted_from).await?;
+        if let Some(previous_slug) = previous_slug.filter(|previous_slug| *previous_slug != page.slug) {
+            sqlx::query!(r#"
+INSERT INTO page_slug_alias (garden_id, slug, page_id)
+VALUES ($1, $2, $3)
+ON CONFLICT (garden_id, slug) DO UPDATE SET page_id = EXCLUDED.page_id
+"#, page.garden_id, previous_slug, page.id)
+                .execute(&mut *tx)
+                .await?;
+            drop_page_slug_alias(&mut tx, page).await?;
+        }
+    }
+    tx.commit().await?;
+    Ok(page)
+}
+
+/// Drop the alias a page has taken the slug of, so the slug leads to the page itself.
+async fn drop_page_slug_alias(conn: &mut PgConnection, page: &Page) -> Result<PgQueryResult, Error> {
+    sqlx::query!("DELETE FROM page_slug_alias WHERE garden_id = $1 AND slug = $2", page.garden_id, page.slug)
+        .execute(conn)
+        .await
+}
+
+/// Record the current title and content of a page as its next revision.
+///
+/// Called in the transaction that wrote the page, whose row lock keeps revision numbers of
+/// concurrent edits apart.
+async fn save_page_revision(conn: &mut PgConnection, page: &Page, author: Option<&str>, reverted_from: Option<i32>) -> Result<PageRevision, Error> {
+    query_as!(PageRevision, r#"
+INSERT INTO page_revision (page_id, revision_number, title, content, author, reverted_from)
+SELECT $1, COALESCE(MAX(revision_number), 0) + 1, $2, $3, $4, $5
+FROM page_revision
+WHERE page_id = $1
+RETURNING *
+"#, page.id, page.title, page.content, author, reverted_from)
+        .fetch_one(conn)
+        .await
+}
+
+/// Replace the wiki-links of a page, resolving each to a page of the same garden by slug or title.
+pub(crate) async fn save_page_links(pool: &Pool<Postgres>, page: &Page, links: &[WikiLink]) -> Result<(), Error> {
+    let targets: Vec<String> = links.iter().map(|link| link.target.clone()).collect();
+    let slugs: Vec<String> = links.iter().map(|link| link.slug()).collect();
+    let labels: Vec<Option<String>> = links.iter().map(|link| link.label.clone()).collect();
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM link WHERE source_page_id = $1", page.id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(r#"
+INSERT INTO link (source_page_id, target_page_id, target, target_slug, label)
+SELECT $1,
+       (SELECT target.id FROM page target
+        WHERE target.garden_id = $2 AND (target.slug = l.target_slug OR lower(target.title) = lower(l.target))
+        ORDER BY target.slug = l.target_slug DESC
+        LIMIT 1),
+       l.target, l.target_slug, l.label
+FROM UNNEST($3::text[], $4::text[], $5::text[]) AS l(target, target_slug, label)
+"#, page.id, page.garden_id, &targets, &slugs, &labels as &[Option<String>])
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Point dangling links of the page's garden that match its slug or title at it.
+pub(crate) async fn resolve_page_links(pool: &Pool<Postgres>, page: &Page) -> Result<PgQueryResult, Error> {
+    sqlx::query!(r#"
+UPDATE link SET target_page_id = $1
+FROM page source
+WHERE source.id = link.source_page_id AND source.garden_id = $2
+  AND link.target_page_id IS NULL
+  AND (link.target_slug = $3 OR lower(link.target) = lower($4))
+"#, page.id, page.garden_id, page.slug, page.title)
+        .execute(&*pool)
+        .await
+}
+
+/// The pages linking to a page.
+pub(crate) async fn page_backlinks(pool: &Pool<Postgres>, page_id: &uuid::Uuid) -> Result<Vec<PageLink>, Error> {
+    query_as!(PageLink, r#"
+SELECT link.target, link.label, source.id AS "page_id?", source.slug AS "slug?", source.title AS "title?", link.create_timestamp
+FROM link
+JOIN page source ON source.id = link.source_page_id
+WHERE link.target_page_id = $1
+ORDER BY source.title
+"#, page_id)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// The links of a page, in the order of their targets.
+pub(crate) async fn page_outlinks(pool: &Pool<Postgres>, page_id: &uuid::Uuid) -> Result<Vec<PageLink>, Error> {
+    query_as!(PageLink, r#"
+SELECT link.target, link.label, target.id AS "page_id?", target.slug AS "slug?", target.title AS "title?", link.create_timestamp
+FROM link
+LEFT JOIN page target ON target.id = link.target_page_id
+WHERE link.source_page_id = $1
+ORDER BY link.target_slug
+"#, page_id)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// Links between the pages of a garden that point at pages that don't exist yet, with the
+/// page each link is on.
+pub(crate) async fn dangling_links(pool: &Pool<Postgres>, garden_slug: &String) -> Result<Vec<PageLink>, Error> {
+    query_as!(PageLink, r#"
+SELECT link.target, link.label, source.id AS "page_id?", source.slug AS "slug?", source.title AS "title?", link.create_timestamp
+FROM link
+JOIN page source ON source.id = link.source_page_id
+JOIN garden ON garden.id = source.garden_id
+WHERE garden.slug = $1 AND link.target_page_id IS NULL
+ORDER BY link.target_slug, source.title
+"#, garden_slug)
+        .fetch_all(&*pool)
+        .await
+}
+
+pub(crate) async fn tags(pool: &Pool<Postgres>) -> Result<Vec<Tag>, Error> {
+    query_as!(Tag, r#"SELECT id, name FROM tag ORDER BY name"#)
+        .fetch_all(&*pool)
+        .await
+}
+
+pub(crate) async fn tag_by_id(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<Option<Tag>, Error> {
+    query_as!(Tag, r#"SELECT id, name FROM tag WHERE id = $1"#, id)
+        .fetch_optional(&*pool)
+        .await
+}
+
+pub(crate) async fn save_tag(pool: &Pool<Postgres>, name: &str) -> Result<Tag, Error> {
+    query_as!(Tag, r#"INSERT INTO tag (name) VALUES ($1) RETURNING id, name"#, name)
+        .fetch_one(&*pool)
+        .await
+}
+
+pub(crate) async fn update_tag(pool: &Pool<Postgres>, id: &uuid::Uuid, name: &str) -> Result<Option<Tag>, Error> {
+    query_as!(Tag, r#"UPDATE tag SET name = $2 WHERE id = $1 RETURNING id, name"#, id, name)
+        .fetch_optional(&*pool)
+        .await
+}
+
+pub(crate) async fn delete_tag(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<PgQueryResult, Error> {
+    sqlx::query!("DELETE FROM tag WHERE id = $1", id)
+        .execute(&*pool)
+        .await
+}
+
+pub(crate) async fn page_tags(pool: &Pool<Postgres>, page_id: &uuid::Uuid) -> Result<Vec<Tag>, Error> {
+    query_as!(Tag, r#"
+SELECT tag.id, tag.name
+FROM tag
+JOIN page_tag ON page_tag.tag_id = tag.id
+WHERE page_tag.page_id = $1
+ORDER BY tag.name
+"#, page_id)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// Tag a page by hand, creating the tag if it doesn't exist. A tag that came from the content
+/// is kept from then on, even if the hashtag is removed.
+pub(crate) async fn add_page_tag(pool: &Pool<Postgres>, page_id: &uuid::Uuid, name: &str) -> Result<Tag, Error> {
+    let mut tx = pool.begin().await?;
+    let tag = query_as!(Tag, r#"
+INSERT INTO tag (name) VALUES ($1)
+ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+RETURNING id, name
+"#, name)
+        .fetch_one(&mut *tx)
+        .await?;
+    sqlx::query!(r#"
+INSERT INTO page_tag (page_id, tag_id) VALUES ($1, $2)
+ON CONFLICT (page_id, tag_id) DO UPDATE SET from_content = false
+"#, page_id, tag.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(tag)
+}
+
+pub(crate) async fn remove_page_tag(pool: &Pool<Postgres>, page_id: &uuid::Uuid, name: &str) -> Result<PgQueryResult, Error> {
+    sqlx::query!(r#"
+DELETE FROM page_tag
+USING tag
+WHERE tag.id = page_tag.tag_id AND page_tag.page_id = $1 AND tag.name = $2
+"#, page_id, name)
+        .execute(&*pool)
+        .await
+}
+
+/// Replace the tags a page got from the hashtags in its content, creating missing tags.
+pub(crate) async fn save_page_hashtags(pool: &Pool<Postgres>, page_id: &uuid::Uuid, names: &[String]) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(r#"
+INSERT INTO tag (name) SELECT UNNEST($1::varchar[])
+ON CONFLICT (name) DO NOTHING
+"#, names)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(r#"
+DELETE FROM page_tag
+USING tag
+WHERE tag.id = page_tag.tag_id AND page_tag.page_id = $1 AND page_tag.from_content
+  AND NOT (tag.name = ANY($2::varchar[]))
+"#, page_id, names)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(r#"
+INSERT INTO page_tag (page_id, tag_id, from_content)
+SELECT $1, tag.id, true FROM tag WHERE tag.name = ANY($2::varchar[])
+ON CONFLICT (page_id, tag_id) DO NOTHING
+"#, page_id, names)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// The tags of a garden's pages with the number of pages for each, most used first.
+pub(crate) async fn garden_tag_counts(pool: &Pool<Postgres>, garden_slug: &String) -> Result<Vec<TagCount>, Error> {
+    query_as!(TagCount, r#"
+SELECT tag.id, tag.name, COUNT(*) AS "count!"
+FROM tag
+JOIN page_tag ON page_tag.tag_id = tag.id
+JOIN page ON page.id = page_tag.page_id
+JOIN garden ON garden.id = page.garden_id
+WHERE garden.slug = $1
+GROUP BY tag.id, tag.name
+ORDER BY COUNT(*) DESC, tag.name
+"#, garden_slug)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// The published pages of a garden, the only ones that may be exported.
+pub(crate) async fn published_pages(pool: &Pool<Postgres>, garden_id: &uuid::Uuid) -> Result<Vec<Page>, Error> {
+    query_as!(Page, r#"SELECT * FROM page WHERE garden_id = $1 AND published ORDER BY title"#, garden_id)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// The most recently updated published pages of a garden, newest first, optionally only those of
+/// a type or with a tag.
+pub(crate) async fn recent_published_pages(pool: &Pool<Postgres>, garden_id: &uuid::Uuid, page_type: Option<&str>, tag: Option<&str>, limit: i64) -> Result<Vec<Page>, Error> {
+    query_as!(Page, r#"SELECT * FROM page
+WHERE garden_id = $1 AND published
+  AND ($2::varchar IS NULL OR page_type = $2)
+  AND ($3::varchar IS NULL OR EXISTS (
+      SELECT 1 FROM page_tag JOIN tag ON tag.id = page_tag.tag_id
+      WHERE page_tag.page_id = page.id AND tag.name = lower($3)))
+ORDER BY update_timestamp DESC
+LIMIT $4"#, garden_id, page_type, tag, limit)
+        .fetch_all(&*pool)
+        .await
+}
+
+/// The tag names of the published pages of a garden, as pairs of page id and tag name.
+pub(crate) async fn published_page_tags(pool: &Pool<Postgres>, garden_id: &uuid::Uuid) -> Result<Vec<(uuid::Uuid, String)>, Error> {
+    let rows = sqlx::query!(r#"
+SELECT page.id, tag.name
+FROM page
+JOIN page_tag ON page_tag.page_id = page.id
+JOIN tag ON tag.id = page_tag.tag_id
+WHERE page.garden_id = $1 AND page.published
+ORDER BY tag.name
+"#, garden_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.into_iter().map(|row| (row.id, row.name)).collect())
+}
+
+/// The slug and title of every page of a garden, for resolving wiki-links.
+pub(crate) async fn page_titles(pool: &Pool<Postgres>, garden_id: &uuid::Uuid) -> Result<Vec<(String, String)>, Error> {
+    let rows = sqlx::query!("SELECT slug, title FROM page WHERE garden_id = $1", garden_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.into_iter().map(|row| (row.slug, row.title)).collect())
+}
+
+/// Pages, news items and sources matching a web search style query (`"quoted phrases"`, `or`,
+/// `-excluded`), best matches first, with highlighted snippets.
+///
+/// Snippets are only made for the requested page of results, as `ts_headline` reads the whole
+/// text of every row it's given.
+pub(crate) async fn search(pool: &Pool<Postgres>, query: &str, filter: &SearchFilter) -> Result<Vec<SearchHit>, Error> {
+    let headline_options = format!("StartSel={}, StopSel={}, MaxWords=35, MinWords=15, MaxFragments=2, FragmentDelimiter=\" … \"",
+                                   SEARCH_MARK_START, SEARCH_MARK_END);
+    let kinds = Some(&filter.kinds).filter(|kinds| !kinds.is_empty());
+    query_as!(SearchHit, r#"
+WITH q AS (SELECT websearch_to_tsquery('english', $1) AS query),
+hits AS (
+    SELECT 'page' AS kind, page.id, page.title, page.slug, NULL::text AS url, page.content AS body, page.update_timestamp AS timestamp,
+           ts_rank_cd(page_search_vector(page.title, page.content), q.query) AS rank
+    FROM page
+    CROSS JOIN q
+    JOIN garden ON garden.id = page.garden_id
+    WHERE page_search_vector(page.title, page.content) @@ q.query
+      AND ($2::text[] IS NULL OR 'page' = ANY($2))
+      AND ($3::text IS NULL OR garden.slug = $3)
+      AND ($4::varchar IS NULL OR EXISTS (
+          SELECT 1 FROM page_tag JOIN tag ON tag.id = page_tag.tag_id
+          WHERE page_tag.page_id = page.id AND tag.name = lower($4)))
+      AND ($5::timestamptz IS NULL OR page.update_timestamp >= $5)
+      AND ($6::timestamptz IS NULL OR page.update_timestamp < $6)
+    UNION ALL
+    SELECT 'news', news.id, news.title, NULL, news.url, coalesce(news.content_text, news.description, ''), news.published_timestamp,
+           ts_rank_cd(news_search_vector(news.title, news.content_text), q.query)
+    FROM news
+    CROSS JOIN q
+    WHERE news_search_vector(news.title, news.content_text) @@ q.query
+      AND ($2::text[] IS NULL OR 'news' = ANY($2))
+      AND $3::text IS NULL AND $4::varchar IS NULL
+      AND ($5::timestamptz IS NULL OR news.published_timestamp >= $5)
+      AND ($6::timestamptz IS NULL OR news.published_timestamp < $6)
+    UNION ALL
+    SELECT 'source', source.id, source.name, NULL, source.url, coalesce(source.description, ''), source.create_timestamp,
+           ts_rank_cd(source_search_vector(source.name, source.description), q.query)
+    FROM source
+    CROSS JOIN q
+    WHERE source_search_vector(source.name, source.description) @@ q.query
+      AND ($2::text[] IS NULL OR 'source' = ANY($2))
+      AND $3::text IS NULL AND $4::varchar IS NULL
+      AND ($5::timestamptz IS NULL OR source.create_timestamp >= $5)
+      AND ($6::timestamptz IS NULL OR source.create_timestamp < $6)
+),
+top AS (
+    SELECT * FROM hits ORDER BY rank DESC, timestamp DESC LIMIT $7 OFFSET $8
+)
+SELECT top.kind AS "kind!", top.id AS "id!", top.title AS "title!", top.slug AS "slug?", top.url AS "url?",
+       ts_headline('english', top.body, q.query, $9) AS "snippet!",
+       top.timestamp AS "timestamp!", top.rank AS "rank!"
+FROM top
+CROSS JOIN q
+ORDER BY top.rank DESC, top.timestamp DESC
+"#, query, kinds.map(|kinds| kinds.as_slice()), filter.garden.as_deref(), filter.tag.as_deref(), filter.from, filter.to,
+        filter.limit, filter.offset, headline_options)
+        .fetch_all(&*pool)
+        .await
+}
+
+pub(crate) async fn page_revisions(pool: &Pool<Postgres>, page_id: &uuid::Uuid) -> Result<Vec<PageRevision>, Error> {
+    query_as!(PageRevision, r#"SELECT * FROM page_revision WHERE page_id = $1 ORDER BY revision_number DESC"#, page_id)
+        .fetch_all(&*pool)
+        .await
+}
+
+pub(crate) async fn page_revision(pool: &Pool<Postgres>, page_id: &uuid::Uuid, revision_number: i32) -> Result<Option<PageRevision>, Error> {
+    query_as!(PageRevision, r#"SELECT * FROM page_revision WHERE page_id = $1 AND revision_number = $2"#, page_id, revision_number)
+        .fetch_optional(&*pool)
         .await
 }
 
@@ -194,15 +832,203 @@ SELECT * FROM e UNION SELECT id FROM feed WHERE url = $2
 pub(crate) async fn save_news_item(ni: &NewsItem, pool: &Pool<Postgres>) -> anyhow::Result<uuid::Uuid> {
     let rec = sqlx::query!(r#"
 WITH e AS(
-INSERT INTO news (id, title, url, published_timestamp, guid, feed_id)
-VALUES ($1, $2, $3, $4, $5, $6)
+INSERT INTO news (id, title, url, published_timestamp, guid, feed_id, raw_content_path, text_content_path,
+                  language, word_count, reading_time, flesch_reading_ease, flesch_kincaid_grade)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
 ON CONFLICT (guid) DO NOTHING
 RETURNING id
 )
 SELECT * FROM e UNION SELECT id FROM news WHERE guid = $5
         "#,
-        ni.id, ni.title, ni.url, ni.published_timestamp, ni.guid, ni.feed_id)
+        ni.id, ni.title, ni.url, ni.published_timestamp, ni.guid, ni.feed_id, ni.raw_content_path, ni.text_content_path,
+        ni.language, ni.word_count, ni.reading_time, ni.flesch_reading_ease, ni.flesch_kincaid_grade)
         .fetch_one(pool)
         .await?;
     Ok(rec.id.unwrap())
-}
\ No newline at end of file
+}
+
+pub(crate) async fn fetch_profile_by_source_id(pool: &Pool<Postgres>, source_id: &uuid::Uuid) -> Result<Option<FetchProfileRecord>, Error> {
+    query_as!(FetchProfileRecord, r#"SELECT * FROM fetch_profile WHERE source_id = $1"#, source_id)
+        .fetch_optional(&*pool)
+        .await
+}
+
+pub(crate) async fn fetch_profile_by_feed_id(pool: &Pool<Postgres>, feed_id: &uuid::Uuid) -> Result<Option<FetchProfileRecord>, Error> {
+    query_as!(FetchProfileRecord, r#"SELECT * FROM fetch_profile WHERE feed_id = $1"#, feed_id)
+        .fetch_optional(&*pool)
+        .await
+}
+
+/// Find the profiles that may be used for `url`, feed profiles first, along with the URL of
+/// the feed or source each belongs to.
+///
+/// `source_id` finds the source's profile for feeds that haven't been saved yet, the caller
+/// has to check that `url` is on the same origin as the source before using it.
+pub(crate) async fn fetch_profiles_for_url(pool: &Pool<Postgres>, url: &str, source_id: Option<uuid::Uuid>) -> Result<Vec<(FetchProfileRecord, String)>, Error> {
+    let rows = sqlx::query!(r#"SELECT fetch_profile.id, fetch_profile.source_id, fetch_profile.feed_id, fetch_profile.nonce,
+fetch_profile.credentials, fetch_profile.create_timestamp, fetch_profile.update_timestamp,
+coalesce(feed.url, source.url) as "owner_url!"
+FROM fetch_profile
+left join feed on feed.id = fetch_profile.feed_id
+left join source on source.id = fetch_profile.source_id
+WHERE feed.url = $1 or source.url = $1 or fetch_profile.source_id = $2
+ORDER BY fetch_profile.feed_id IS NULL, source.url = $1 DESC"#, url, source_id)
+        .fetch_all(&*pool)
+        .await?;
+    Ok(rows.into_iter()
+        .map(|row| (FetchProfileRecord {
+            id: row.id,
+            source_id: row.source_id,
+            feed_id: row.feed_id,
+            nonce: row.nonce,
+            credentials: row.credentials,
+            create_timestamp: row.create_timestamp,
+            update_timestamp: row.update_timestamp,
+        }, row.owner_url))
+        .collect())
+}
+
+pub(crate) async fn save_source_fetch_profile(pool: &Pool<Postgres>, source_id: &uuid::Uuid, nonce: &[u8], credentials: &[u8]) -> Result<FetchProfileRecord, Error> {
+    query_as!(FetchProfileRecord, r#"
+INSERT INTO fetch_profile (source_id, nonce, credentials)
+VALUES ($1, $2, $3)
+ON CONFLICT (source_id) DO UPDATE SET nonce = $2, credentials = $3, update_timestamp = now()
+RETURNING *
+"#, source_id, nonce, credentials)
+        .fetch_one(pool)
+        .await
+}
+
+pub(crate) async fn save_feed_fetch_profile(pool: &Pool<Postgres>, feed_id: &uuid::Uuid, nonce: &[u8], credentials: &[u8]) -> Result<FetchProfileRecord, Error> {
+    query_as!(FetchProfileRecord, r#"
+INSERT INTO fetch_profile (feed_id, nonce, credentials)
+VALUES ($1, $2, $3)
+ON CONFLICT (feed_id) DO UPDATE SET nonce = $2, credentials = $3, update_timestamp = now()
+RETURNING *
+"#, feed_id, nonce, credentials)
+        .fetch_one(pool)
+        .await
+}
+
+pub(crate) async fn delete_source_fetch_profile(pool: &Pool<Postgres>, source_id: &uuid::Uuid) -> Result<PgQueryResult, Error> {
+    sqlx::query!("DELETE FROM fetch_profile WHERE source_id = $1", source_id)
+        .execute(&*pool)
+        .await
+}
+
+pub(crate) async fn delete_feed_fetch_profile(pool: &Pool<Postgres>, feed_id: &uuid::Uuid) -> Result<PgQueryResult, Error> {
+    sqlx::query!("DELETE FROM fetch_profile WHERE feed_id = $1", feed_id)
+        .execute(&*pool)
+        .await
+}
+
+pub(crate) async fn extraction_rules(pool: &Pool<Postgres>) -> Result<Vec<ExtractionRule>, Error> {
+    query_as!(ExtractionRule, r#"SELECT * FROM extraction_rule ORDER BY domain"#)
+        .fetch_all(pool)
+        .await
+}
+
+pub(crate) async fn extraction_rule_by_id(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<Option<ExtractionRule>, Error> {
+    query_as!(ExtractionRule, r#"SELECT * FROM extraction_rule WHERE id = $1"#, id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Find the rule for `host`, preferring the most specific of its domain and parent domains.
+pub(crate) async fn extraction_rule_for_host(pool: &Pool<Postgres>, host: &str) -> Result<Option<ExtractionRule>, Error> {
+    query_as!(ExtractionRule, r#"SELECT * FROM extraction_rule
+WHERE domain = $1 OR $1 LIKE '%.' || domain
+ORDER BY length(domain) DESC
+LIMIT 1"#, host)
+        .fetch_optional(pool)
+        .await
+}
+
+pub(crate) async fn save_extraction_rule(pool: &Pool<Postgres>, domain: &str, rules: &SiteRules) -> Result<ExtractionRule, Error> {
+    query_as!(ExtractionRule, r#"
+INSERT INTO extraction_rule (domain, content_selector, remove_selectors, date_selector, author_selector)
+VALUES ($1, $2, $3, $4, $5)
+RETURNING *
+"#, domain, rules.content_selector.as_deref(), &rules.remove_selectors, rules.date_selector.as_deref(), rules.author_selector.as_deref())
+        .fetch_one(pool)
+        .await
+}
+
+pub(crate) async fn update_extraction_rule(pool: &Pool<Postgres>, id: &uuid::Uuid, domain: &str, rules: &SiteRules) -> Result<Option<ExtractionRule>, Error> {
+    query_as!(ExtractionRule, r#"
+UPDATE extraction_rule
+SET domain = $2, content_selector = $3, remove_selectors = $4, date_selector = $5, author_selector = $6, update_timestamp = now()
+WHERE id = $1
+RETURNING *
+"#, id, domain, rules.content_selector.as_deref(), &rules.remove_selectors, rules.date_selector.as_deref(), rules.author_selector.as_deref())
+        .fetch_optional(pool)
+        .await
+}
+
+pub(crate) async fn delete_extraction_rule(pool: &Pool<Postgres>, id: &uuid::Uuid) -> Result<PgQueryResult, Error> {
+    sqlx::query!("DELETE FROM extraction_rule WHERE id = $1", id)
+        .execute(pool)
+        .await
+}
+
+/// Replace the chunks of a news item.
+pub(crate) async fn save_news_chunks(pool: &Pool<Postgres>, news_id: &uuid::Uuid, chunks: &[Chunk]) -> Result<(), Error> {
+    let (indexes, starts, ends, tokens, headings, contents) = chunk_columns(chunks);
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM chunk WHERE news_id = $1", news_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(r#"
+INSERT INTO chunk (news_id, chunk_index, start_offset, end_offset, token_count, heading, content)
+SELECT $1, c.chunk_index, c.start_offset, c.end_offset, c.token_count, c.heading, c.content
+FROM UNNEST($2::integer[], $3::integer[], $4::integer[], $5::integer[], $6::text[], $7::text[])
+    AS c(chunk_index, start_offset, end_offset, token_count, heading, content)
+"#, news_id, &indexes, &starts, &ends, &tokens, &headings as &[Option<String>], &contents)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Replace the chunks of a page.
+pub(crate) async fn save_page_chunks(pool: &Pool<Postgres>, page_id: &uuid::Uuid, chunks: &[Chunk]) -> Result<(), Error> {
+    let (indexes, starts, ends, tokens, headings, contents) = chunk_columns(chunks);
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM chunk WHERE page_id = $1", page_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(r#"
+INSERT INTO chunk (page_id, chunk_index, start_offset, end_offset, token_count, heading, content)
+SELECT $1, c.chunk_index, c.start_offset, c.end_offset, c.token_count, c.heading, c.content
+FROM UNNEST($2::integer[], $3::integer[], $4::integer[], $5::integer[], $6::text[], $7::text[])
+    AS c(chunk_index, start_offset, end_offset, token_count, heading, content)
+"#, page_id, &indexes, &starts, &ends, &tokens, &headings as &[Option<String>], &contents)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+type ChunkColumns = (Vec<i32>, Vec<i32>, Vec<i32>, Vec<i32>, Vec<Option<String>>, Vec<String>);
+
+/// Chunks as one array per column, for inserting them with `UNNEST`.
+fn chunk_columns(chunks: &[Chunk]) -> ChunkColumns {
+    (
+        chunks.iter().map(|chunk| chunk.index).collect(),
+        chunks.iter().map(|chunk| chunk.start_offset).collect(),
+        chunks.iter().map(|chunk| chunk.end_offset).collect(),
+        chunks.iter().map(|chunk| chunk.token_count).collect(),
+        chunks.iter().map(|chunk| chunk.heading.clone()).collect(),
+        chunks.iter().map(|chunk| chunk.content.clone()).collect(),
+    )
+}
+
+pub(crate) async fn news_chunks(pool: &Pool<Postgres>, news_id: &uuid::Uuid) -> Result<Vec<ChunkRecord>, Error> {
+    query_as!(ChunkRecord, r#"SELECT * FROM chunk WHERE news_id = $1 ORDER BY chunk_index"#, news_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub(crate) async fn page_chunks(pool: &Pool<Postgres>, page_id: &uuid::Uuid) -> Result<Vec<ChunkRecord>, Error> {
+    query_as!(ChunkRecord, r#"SELECT * FROM chunk WHERE page_id = $1 ORDER BY chunk_index"#, page_id)
+        .fetch_all(pool)
+        .await
+}
@@ -0,0 +1,65 @@
+use atom_syndication::{CategoryBuilder, ContentBuilder, EntryBuilder, FeedBuilder, LinkBuilder};
+use chrono::{DateTime, FixedOffset, Utc};
+
+use crate::models::{Garden, Page};
+
+/// An Atom feed of pages of a garden, for the exported site and the feeds the server serves.
+pub(crate) struct AtomFeed<'a> {
+    pub garden: &'a Garden,
+    /// The URL of the feed itself.
+    pub url: String,
+    /// The URL of the HTML page listing the garden's pages.
+    pub home_url: String,
+    pub updated: DateTime<Utc>,
+    pub entries: Vec<AtomEntry<'a>>,
+}
+
+pub(crate) struct AtomEntry<'a> {
+    pub page: &'a Page,
+    pub url: String,
+    pub tags: &'a [String],
+    /// The rendered content of the page.
+    pub html: &'a str,
+}
+
+pub(crate) fn atom_feed(feed: &AtomFeed) -> String {
+    let entries = feed.entries.iter()
+        .map(|entry| EntryBuilder::default()
+            .title(entry.page.title.clone())
+            .id(format!("urn:uuid:{}", entry.page.id))
+            .links(vec![LinkBuilder::default()
+                .href(entry.url.clone())
+                .rel("alternate".to_string())
+                .mime_type(Some("text/html".to_string()))
+                .build()])
+            .published(Some(DateTime::<FixedOffset>::from(entry.page.create_timestamp)))
+            .updated(entry.page.update_timestamp)
+            .categories(entry.tags.iter()
+                .map(|tag| CategoryBuilder::default().term(tag.clone()).build())
+                .collect::<Vec<_>>())
+            .content(Some(ContentBuilder::default()
+                .value(Some(entry.html.to_string()))
+                .content_type(Some("html".to_string()))
+                .build()))
+            .build())
+        .collect::<Vec<_>>();
+    FeedBuilder::default()
+        .title(feed.garden.title.clone())
+        .id(format!("urn:uuid:{}", feed.garden.id))
+        .links(vec![
+            LinkBuilder::default()
+                .href(feed.url.clone())
+                .rel("self".to_string())
+                .mime_type(Some("application/atom+xml".to_string()))
+                .build(),
+            LinkBuilder::default()
+                .href(feed.home_url.clone())
+                .rel("alternate".to_string())
+                .mime_type(Some("text/html".to_string()))
+                .build(),
+        ])
+        .updated(feed.updated)
+        .entries(entries)
+        .build()
+        .to_string()
+}
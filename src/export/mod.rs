@@ -0,0 +1,445 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Context;
+use kuchiki::traits::*;
+use log::warn;
+use sqlx::{Pool, Postgres};
+use url::Url;
+
+use crate::artifacts::artifact_file;
+use crate::db;
+use crate::extractor::{absolute_url, render_markdown, resolve_wiki_link, wiki_links};
+use crate::models::{Garden, Page};
+
+mod atom;
+
+pub(crate) use atom::{atom_feed, AtomEntry, AtomFeed};
+
+/// Static assets pages can refer to, see `static_support`.
+const ASSET_PATH: &str = "/assets/";
+const ASSET_DIR: &str = "assets";
+
+/// Where referenced artifacts and assets are copied to in the site.
+const ATTACHMENT_DIR: &str = "attachments";
+
+/// How many of the most recently updated pages the Atom feed holds.
+const FEED_ENTRIES: usize = 50;
+
+const STYLESHEET: &str = "body { max-width: 44rem; margin: 2rem auto; padding: 0 1rem; font: 1.05rem/1.6 system-ui, sans-serif; color: #222; }
+header { margin-bottom: 2rem; font-weight: 600; }
+a { color: #2a5db0; }
+img { max-width: 100%; height: auto; }
+pre { overflow-x: auto; padding: 0.75rem; background: #f5f5f5; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ddd; padding: 0.25rem 0.5rem; }
+.anchor { margin-left: 0.4rem; color: #bbb; text-decoration: none; }
+.wiki-link.missing { color: #999; }
+.tags a, .tag-cloud a { margin-right: 0.5rem; }
+.backlinks, .toc { margin-top: 2rem; padding-top: 1rem; border-top: 1px solid #eee; }
+time { color: #777; }
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ExportFormat {
+    Directory,
+    Zip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ExportOptions {
+    /// The URL the site will be published at, for the absolute URLs of the sitemap and the feed.
+    /// Without it they hold URLs relative to the site's root.
+    pub base_url: Option<String>,
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct ExportSummary {
+    pub garden: String,
+    pub pages: usize,
+    pub files: usize,
+    pub output: PathBuf,
+}
+
+/// A published page and the names of its tags.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ExportPage {
+    pub page: Page,
+    pub tags: Vec<String>,
+}
+
+/// What a file of the site holds: generated content, or a stored file to copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Content {
+    Data(Vec<u8>),
+    Copy(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SiteFile {
+    /// Relative to the root of the site, with `/` separators.
+    path: String,
+    content: Content,
+}
+
+/// Export the published pages of a garden as a static site, to a directory or a zip file at
+/// `output`.
+///
+/// Only published pages are loaded, so unpublished ones can't show up anywhere in the site:
+/// wiki-links to them render as missing and their links don't count as backlinks.
+pub(crate) async fn export_garden(pool: &Pool<Postgres>, garden_slug: &String, output: &Path, options: &ExportOptions) -> anyhow::Result<ExportSummary> {
+    let garden = db::garden_by_slug(pool, garden_slug).await
+        .with_context(|| format!("garden with slug: {} not found", garden_slug))?;
+    let mut tags: HashMap<uuid::Uuid, Vec<String>> = HashMap::new();
+    for (page_id, name) in db::published_page_tags(pool, &garden.id).await? {
+        tags.entry(page_id).or_default().push(name);
+    }
+    let pages: Vec<ExportPage> = db::published_pages(pool, &garden.id).await?.into_iter()
+        .map(|page| ExportPage { tags: tags.remove(&page.id).unwrap_or_default(), page })
+        .collect();
+
+    let page_count = pages.len();
+    let files = build_site(&garden, pages, options.base_url.as_deref());
+    let file_count = files.len();
+    let format = options.format;
+    let path = output.to_path_buf();
+    tokio::task::spawn_blocking(move || match format {
+        ExportFormat::Directory => write_directory(&files, &path),
+        ExportFormat::Zip => write_zip(&files, &path),
+    }).await??;
+
+    Ok(ExportSummary {
+        garden: garden.slug,
+        pages: page_count,
+        files: file_count,
+        output: output.to_path_buf(),
+    })
+}
+
+/// The files of the site: `index.html`, a file per page under `pages/` and per tag under
+/// `tags/`, `sitemap.xml`, `atom.xml`, `style.css`, and the artifacts and assets the pages
+/// refer to under `attachments/`.
+fn build_site(garden: &Garden, mut pages: Vec<ExportPage>, base_url: Option<&str>) -> Vec<SiteFile> {
+    let base = base_url.and_then(site_base);
+    pages.sort_by_key(|page| page.page.title.to_lowercase());
+    let titles: Vec<(String, String)> = pages.iter()
+        .map(|page| (page.page.slug.clone(), page.page.title.clone()))
+        .collect();
+    let title_of = |slug: &str| titles.iter().find(|(other, _)| other == slug).map(|(_, title)| title.clone()).unwrap_or_default();
+
+    let mut backlinks: HashMap<String, Vec<String>> = HashMap::new();
+    for page in &pages {
+        for link in wiki_links(&page.page.content) {
+            match resolve_wiki_link(&titles, &link) {
+                Some(target) if target != page.page.slug => backlinks.entry(target).or_default().push(page.page.slug.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    let mut tagged: BTreeMap<String, Vec<&ExportPage>> = BTreeMap::new();
+    for page in &pages {
+        for tag in &page.tags {
+            tagged.entry(tag.clone()).or_default().push(page);
+        }
+    }
+
+    let mut files = Vec::new();
+    let mut attachments: BTreeMap<String, PathBuf> = BTreeMap::new();
+    let mut rendered_pages = HashMap::new();
+    for page in &pages {
+        let rendered = render_markdown(&page.page.content, |link| {
+            resolve_wiki_link(&titles, link).map(|slug| page_file(&slug))
+        });
+        let content = localize_attachments(&rendered.html, "../", &mut attachments);
+        rendered_pages.insert(page.page.slug.clone(), content.clone());
+
+        let mut body = format!("<article>\n<h1>{}</h1>\n<p><time datetime=\"{}\">Updated {}</time></p>\n",
+                               escape(&page.page.title), page.page.update_timestamp.to_rfc3339(),
+                               page.page.update_timestamp.format("%B %-d, %Y"));
+        if !page.tags.is_empty() {
+            body.push_str("<p class=\"tags\">");
+            for tag in &page.tags {
+                body.push_str(&format!("<a href=\"../{}\">#{}</a>", tag_file(tag), escape(tag)));
+            }
+            body.push_str("</p>\n");
+        }
+        if rendered.toc.len() > 2 {
+            body.push_str("<nav class=\"toc\">\n<ul>\n");
+            for entry in &rendered.toc {
+                body.push_str(&format!("<li class=\"toc-{}\"><a href=\"#{}\">{}</a></li>\n", entry.level, entry.id, escape(&entry.title)));
+            }
+            body.push_str("</ul>\n</nav>\n");
+        }
+        body.push_str(&content);
+        body.push_str("</article>\n");
+        if let Some(sources) = backlinks.get(&page.page.slug) {
+            body.push_str("<section class=\"backlinks\">\n<h2>Links to this page</h2>\n<ul>\n");
+            for source in sources {
+                body.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", page_file(source), escape(&title_of(source))));
+            }
+            body.push_str("</ul>\n</section>\n");
+        }
+        files.push(html_file(format!("pages/{}", page_file(&page.page.slug)), &page.page.title, &garden.title, "../", &body));
+    }
+
+    let mut index = format!("<h1>{}</h1>\n<ul class=\"pages\">\n", escape(&garden.title));
+    for page in &pages {
+        index.push_str(&format!("<li><a href=\"pages/{}\">{}</a> <time datetime=\"{}\">{}</time></li>\n",
+                                page_file(&page.page.slug), escape(&page.page.title),
+                                page.page.update_timestamp.to_rfc3339(), page.page.update_timestamp.format("%Y-%m-%d")));
+    }
+    index.push_str("</ul>\n");
+    if !tagged.is_empty() {
+        index.push_str("<h2>Tags</h2>\n<p class=\"tag-cloud\">");
+        for (tag, tag_pages) in &tagged {
+            index.push_str(&format!("<a href=\"{}\">#{}</a> ({})", tag_file(tag), escape(tag), tag_pages.len()));
+        }
+        index.push_str("</p>\n");
+    }
+    files.push(html_file("index.html".to_string(), &garden.title, &garden.title, "", &index));
+
+    for (tag, tag_pages) in &tagged {
+        let mut body = format!("<h1>#{}</h1>\n<ul class=\"pages\">\n", escape(tag));
+        for page in tag_pages {
+            body.push_str(&format!("<li><a href=\"../pages/{}\">{}</a></li>\n", page_file(&page.page.slug), escape(&page.page.title)));
+        }
+        body.push_str("</ul>\n");
+        files.push(html_file(tag_file(tag), &format!("#{}", tag), &garden.title, "../", &body));
+    }
+
+    files.push(SiteFile { path: "sitemap.xml".to_string(), content: Content::Data(sitemap(&pages, &tagged, base.as_ref()).into_bytes()) });
+    files.push(SiteFile { path: "atom.xml".to_string(), content: Content::Data(site_feed(garden, &pages, &rendered_pages, base.as_ref()).into_bytes()) });
+    files.push(SiteFile { path: "style.css".to_string(), content: Content::Data(STYLESHEET.as_bytes().to_vec()) });
+    for (path, source) in attachments {
+        files.push(SiteFile { path, content: Content::Copy(source) });
+    }
+    files
+}
+
+/// The file of a page, relative to the `pages` directory.
+fn page_file(slug: &str) -> String {
+    format!("{}.html", slug::slugify(slug))
+}
+
+/// The file of a tag page, relative to the root of the site.
+fn tag_file(tag: &str) -> String {
+    format!("tags/{}.html", slug::slugify(tag))
+}
+
+/// The URL the site is published at, as a directory the paths of the site's files resolve
+/// against.
+fn site_base(base_url: &str) -> Option<Url> {
+    Url::parse(&format!("{}/", base_url.trim_end_matches('/'))).ok()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A page of the site. `root` is the relative path from the file to the root of the site.
+fn html_file(path: String, title: &str, site_title: &str, root: &str, body: &str) -> SiteFile {
+    let html = format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<link rel="stylesheet" href="{root}style.css">
+<link rel="alternate" type="application/atom+xml" title="{site_title}" href="{root}atom.xml">
+</head>
+<body>
+<header><a href="{root}index.html">{site_title}</a></header>
+<main>
+{body}</main>
+</body>
+</html>
+"#, title = escape(title), site_title = escape(site_title), root = root, body = body);
+    SiteFile { path, content: Content::Data(html.into_bytes()) }
+}
+
+/// Point references to artifacts and assets at copies under `attachments/`, recording which
+/// stored file each copy comes from. `root` is the relative path from the page to the root.
+fn localize_attachments(html: &str, root: &str, attachments: &mut BTreeMap<String, PathBuf>) -> String {
+    let document = kuchiki::parse_html().one(html);
+    for element in document.select("[src], [href]").into_iter().flatten() {
+        let mut attributes = element.attributes.borrow_mut();
+        for name in ["src", "href"] {
+            let url = match attributes.get(name) {
+                Some(url) => url.to_string(),
+                None => continue,
+            };
+            if let Some((path, source)) = attachment(&url) {
+                attributes.insert(name, format!("{}{}", root, path));
+                attachments.insert(path, source);
+            }
+        }
+    }
+
+    let body = document.select_first("body").map(|body| body.as_node().clone()).unwrap_or(document);
+    let mut localized = Vec::new();
+    for child in body.children() {
+        let _ = child.serialize(&mut localized);
+    }
+    String::from_utf8_lossy(&localized).to_string()
+}
+
+/// The path in the site and the stored file of an artifact or asset URL.
+fn attachment(url: &str) -> Option<(String, PathBuf)> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    if let Some(source) = artifact_file(url) {
+        return Some((format!("{}{}", ATTACHMENT_DIR, url), source));
+    }
+    let key = Path::new(url.strip_prefix(ASSET_PATH)?);
+    if key.components().any(|component| !matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    Some((format!("{}{}", ATTACHMENT_DIR, url), Path::new(ASSET_DIR).join(key)))
+}
+
+fn sitemap(pages: &[ExportPage], tagged: &BTreeMap<String, Vec<&ExportPage>>, base: Option<&Url>) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    let lastmod = pages.iter().map(|page| page.page.update_timestamp).max();
+    let mut url = |path: &str, lastmod: Option<chrono::DateTime<chrono::Utc>>| {
+        xml.push_str(&format!("<url><loc>{}</loc>", escape(&absolute_url(base, path))));
+        if let Some(lastmod) = lastmod {
+            xml.push_str(&format!("<lastmod>{}</lastmod>", lastmod.format("%Y-%m-%d")));
+        }
+        xml.push_str("</url>\n");
+    };
+    url("index.html", lastmod);
+    for page in pages {
+        url(&format!("pages/{}", page_file(&page.page.slug)), Some(page.page.update_timestamp));
+    }
+    for (tag, tag_pages) in tagged {
+        url(&tag_file(tag), tag_pages.iter().map(|page| page.page.update_timestamp).max());
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// An Atom feed of the most recently updated pages, with their rendered content.
+fn site_feed(garden: &Garden, pages: &[ExportPage], rendered: &HashMap<String, String>, base: Option<&Url>) -> String {
+    let mut recent: Vec<&ExportPage> = pages.iter().collect();
+    recent.sort_by_key(|page| std::cmp::Reverse(page.page.update_timestamp));
+    recent.truncate(FEED_ENTRIES);
+
+    atom_feed(&AtomFeed {
+        garden,
+        url: absolute_url(base, "atom.xml"),
+        home_url: absolute_url(base, "index.html"),
+        updated: recent.first().map_or(garden.update_timestamp, |page| page.page.update_timestamp),
+        entries: recent.into_iter()
+            .map(|page| AtomEntry {
+                page: &page.page,
+                url: absolute_url(base, &format!("pages/{}", page_file(&page.page.slug))),
+                tags: &page.tags,
+                html: rendered.get(&page.page.slug).map(String::as_str).unwrap_or_default(),
+            })
+            .collect(),
+    })
+}
+
+/// Write the site to a directory, creating it if needed. Missing attachments are skipped.
+fn write_directory(files: &[SiteFile], dir: &Path) -> anyhow::Result<()> {
+    for file in files {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match &file.content {
+            Content::Data(data) => fs::write(&path, data)?,
+            Content::Copy(source) => if let Err(e) = fs::copy(source, &path) {
+                warn!("Skipping attachment {}: {}", source.display(), e);
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Write the site to a zip file. Missing attachments are skipped.
+fn write_zip(files: &[SiteFile], path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut zip = zip::ZipWriter::new(File::create(path)?);
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for file in files {
+        match &file.content {
+            Content::Data(data) => {
+                zip.start_file(&file.path, options)?;
+                zip.write_all(data)?;
+            }
+            Content::Copy(source) => match File::open(source) {
+                Ok(mut source) => {
+                    zip.start_file(&file.path, options)?;
+                    io::copy(&mut source, &mut zip)?;
+                }
+                Err(e) => warn!("Skipping attachment {}: {}", source.display(), e),
+            },
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn page(title: &str, slug: &str, content: &str, tags: &[&str]) -> ExportPage {
+        let timestamp = Utc.with_ymd_and_hms(2023, 9, 1, 12, 0, 0).unwrap();
+        let garden_id = uuid::Uuid::nil();
+        ExportPage {
+            page: Page::new(title.to_string(), slug.to_string(), content.to_string(), garden_id, true,
+                            timestamp, timestamp, "note".to_string()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    fn data<'a>(files: &'a [SiteFile], path: &str) -> &'a str {
+        match files.iter().find(|file| file.path == path).map(|file| &file.content) {
+            Some(Content::Data(data)) => std::str::from_utf8(data).unwrap(),
+            _ => panic!("no file {}", path),
+        }
+    }
+
+    #[test]
+    fn test_build_site() {
+        let timestamp = Utc.with_ymd_and_hms(2023, 8, 1, 12, 0, 0).unwrap();
+        let garden = Garden::new("Streets".to_string(), "streets".to_string(), timestamp, timestamp);
+        let pages = vec![
+            page("Bike Lanes", "bike-lanes", "See [[City Council]] and [[Secret Plans]].\n\n![Map](/artifacts/thumbnails/map/640.webp)", &["cycling"]),
+            page("City Council", "city-council", "Votes on [[bike-lanes|lanes]].", &["cycling", "politics"]),
+        ];
+        let files = build_site(&garden, pages, Some("https://streets.example.com/"));
+
+        let bike_lanes = data(&files, "pages/bike-lanes.html");
+        assert!(bike_lanes.contains(r#"href="city-council.html""#));
+        assert!(bike_lanes.contains(r#"<span class="wiki-link missing">Secret Plans</span>"#));
+        assert!(bike_lanes.contains(r#"src="../attachments/artifacts/thumbnails/map/640.webp""#));
+        assert!(bike_lanes.contains("Links to this page"));
+        assert!(data(&files, "pages/city-council.html").contains(r#"<a href="bike-lanes.html">Bike Lanes</a>"#));
+
+        assert!(data(&files, "index.html").contains(r#"<a href="tags/cycling.html">#cycling</a> (2)"#));
+        assert!(data(&files, "tags/politics.html").contains("../pages/city-council.html"));
+        assert!(data(&files, "sitemap.xml").contains("<loc>https://streets.example.com/pages/bike-lanes.html</loc>"));
+        let feed: atom_syndication::Feed = data(&files, "atom.xml").parse().unwrap();
+        assert_eq!(feed.links()[0].href(), "https://streets.example.com/atom.xml");
+        let entry = feed.entries().iter().find(|entry| entry.title().as_str() == "City Council").unwrap();
+        assert_eq!(entry.links()[0].href(), "https://streets.example.com/pages/city-council.html");
+        assert_eq!(entry.categories().iter().map(|category| category.term()).collect::<Vec<_>>(), vec!["cycling", "politics"]);
+        assert!(entry.content().and_then(|content| content.value()).unwrap().contains(r#"href="bike-lanes.html""#));
+        assert!(files.iter().any(|file| file.path == "attachments/artifacts/thumbnails/map/640.webp"
+            && file.content == Content::Copy(PathBuf::from("downloads/artifacts/thumbnails/map/640.webp"))));
+    }
+}
@@ -0,0 +1,282 @@
+/// Characters per token, the usual rule of thumb for English text and BPE tokenizers.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// How large chunks are and how much consecutive chunks share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChunkOptions {
+    pub max_tokens: usize,
+    /// Tokens of trailing paragraphs repeated at the start of the next chunk of a section.
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            max_tokens: 512,
+            overlap_tokens: 64,
+        }
+    }
+}
+
+/// A passage of a text, with its position in it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct Chunk {
+    pub index: i32,
+    /// Offset of the first character of the chunk in the text, in characters.
+    pub start_offset: i32,
+    /// Offset just past the last character of the chunk in the text, in characters.
+    pub end_offset: i32,
+    pub token_count: i32,
+    /// The Markdown heading of the section the chunk starts in.
+    pub heading: Option<String>,
+    pub content: String,
+}
+
+/// Estimate the number of tokens of a text, without the cost of a real tokenizer.
+pub(crate) fn approximate_tokens(text: &str) -> usize {
+    let chars = text.trim().chars().count();
+    (chars + CHARS_PER_TOKEN - 1) / CHARS_PER_TOKEN
+}
+
+/// A heading, paragraph or part of a paragraph, as a byte range of the text.
+#[derive(Debug, Clone, Copy)]
+struct Unit {
+    start: usize,
+    end: usize,
+    heading: bool,
+}
+
+/// Split extracted text or Markdown into chunks of at most `max_tokens`.
+///
+/// Chunks break at headings and paragraphs, which are either separated by blank lines or on
+/// lines of their own, and only break within a paragraph if it doesn't fit in a chunk by itself.
+/// Every chunk is a slice of `text`, so its offsets point at exactly the passage it holds.
+pub(crate) fn chunk_text(text: &str, options: &ChunkOptions) -> Vec<Chunk> {
+    let max_tokens = options.max_tokens.max(1);
+    let units: Vec<Unit> = blocks(text).into_iter()
+        .flat_map(|unit| split_unit(text, unit, max_tokens))
+        .collect();
+
+    let mut groups: Vec<Vec<Unit>> = Vec::new();
+    let mut current: Vec<Unit> = Vec::new();
+    for unit in units {
+        if current.is_empty() {
+            current.push(unit);
+            continue;
+        }
+        if unit.heading {
+            groups.push(std::mem::take(&mut current));
+            current.push(unit);
+            continue;
+        }
+        let with_unit = approximate_tokens(&text[current[0].start..unit.end]);
+        if with_unit <= max_tokens {
+            current.push(unit);
+            continue;
+        }
+
+        let overlap = overlap(text, &current, options.overlap_tokens);
+        groups.push(std::mem::replace(&mut current, overlap));
+        // drop overlap that would push the next chunk over the limit
+        while !current.is_empty() && approximate_tokens(&text[current[0].start..unit.end]) > max_tokens {
+            current.remove(0);
+        }
+        current.push(unit);
+    }
+    if !current.is_empty() {
+        groups.push(current);
+    }
+
+    let headings = headings(text);
+    groups.iter().enumerate()
+        .map(|(index, group)| {
+            let start = group[0].start;
+            let end = group[group.len() - 1].end;
+            let content = &text[start..end];
+            Chunk {
+                index: index as i32,
+                start_offset: char_offset(text, start),
+                end_offset: char_offset(text, end),
+                token_count: approximate_tokens(content) as i32,
+                heading: headings.iter().rev()
+                    .find(|(position, _)| *position <= start)
+                    .map(|(_, heading)| heading.clone()),
+                content: content.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// The trailing units of a chunk that fit in `overlap_tokens`, never including a heading.
+fn overlap(text: &str, units: &[Unit], overlap_tokens: usize) -> Vec<Unit> {
+    let mut start = units.len();
+    while start > 1 {
+        let candidate = &units[start - 1];
+        if candidate.heading || approximate_tokens(&text[candidate.start..units[units.len() - 1].end]) > overlap_tokens {
+            break;
+        }
+        start -= 1;
+    }
+    units[start..].to_vec()
+}
+
+/// Headings and paragraphs, skipping blank lines. Lines of a paragraph are kept together
+/// unless it's longer than a chunk, so text with one paragraph per line is split line by line.
+fn blocks(text: &str) -> Vec<Unit> {
+    let mut units = Vec::new();
+    let mut paragraph: Option<(usize, usize)> = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        if trimmed.is_empty() || heading_text(trimmed).is_some() {
+            if let Some((start, end)) = paragraph.take() {
+                units.push(Unit { start, end, heading: false });
+            }
+        }
+        if trimmed.is_empty() {
+            continue;
+        }
+        let line_start = start + (line.len() - line.trim_start().len());
+        let line_end = start + line.trim_end().len();
+        if heading_text(trimmed).is_some() {
+            units.push(Unit { start: line_start, end: line_end, heading: true });
+        } else {
+            paragraph = Some(match paragraph {
+                Some((start, _)) => (start, line_end),
+                None => (line_start, line_end),
+            });
+        }
+    }
+    if let Some((start, end)) = paragraph {
+        units.push(Unit { start, end, heading: false });
+    }
+    units
+}
+
+/// Split a unit that's longer than a chunk at lines, then sentences, then words. The pieces are
+/// packed back into chunks by `chunk_text`.
+fn split_unit(text: &str, unit: Unit, max_tokens: usize) -> Vec<Unit> {
+    if approximate_tokens(&text[unit.start..unit.end]) <= max_tokens {
+        return vec![unit];
+    }
+
+    for separators in [&["\n"][..], &[". ", "! ", "? ", "。"][..], &[" "][..]] {
+        let pieces = split_after(text, unit, separators);
+        if pieces.len() > 1 {
+            return pieces.into_iter()
+                .flat_map(|piece| split_unit(text, piece, max_tokens))
+                .collect();
+        }
+    }
+
+    // a single word longer than a chunk, e.g. an inlined data URL
+    let max_chars = max_tokens * CHARS_PER_TOKEN;
+    let mut pieces = Vec::new();
+    let mut start = unit.start;
+    for (count, (i, _)) in text[unit.start..unit.end].char_indices().enumerate() {
+        if count > 0 && count % max_chars == 0 {
+            pieces.push(Unit { start, end: unit.start + i, heading: unit.heading });
+            start = unit.start + i;
+        }
+    }
+    pieces.push(Unit { start, end: unit.end, heading: unit.heading });
+    pieces
+}
+
+/// Split a unit after each separator, trimming whitespace from the pieces.
+fn split_after(text: &str, unit: Unit, separators: &[&str]) -> Vec<Unit> {
+    let slice = &text[unit.start..unit.end];
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < slice.len() {
+        match separators.iter().find(|separator| slice[i..].starts_with(*separator)) {
+            Some(separator) => {
+                i += separator.len();
+                pieces.push((start, i));
+                start = i;
+            }
+            None => i += slice[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    pieces.push((start, slice.len()));
+
+    pieces.into_iter()
+        .filter_map(|(start, end)| {
+            let piece = &slice[start..end];
+            let trimmed_start = start + (piece.len() - piece.trim_start().len());
+            let trimmed_end = start + piece.trim_end().len();
+            (trimmed_start < trimmed_end).then(|| Unit {
+                start: unit.start + trimmed_start,
+                end: unit.start + trimmed_end,
+                heading: false,
+            })
+        })
+        .collect()
+}
+
+/// The text of every Markdown heading, with its byte position.
+fn headings(text: &str) -> Vec<(usize, String)> {
+    let mut headings = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if let Some(heading) = heading_text(trimmed) {
+            headings.push((offset + (line.len() - line.trim_start().len()), heading.to_string()));
+        }
+        offset += line.len();
+    }
+    headings
+}
+
+/// The text of an ATX heading line, `# Title` to `###### Title`.
+fn heading_text(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.starts_with(' ') {
+        return None;
+    }
+    Some(rest.trim().trim_end_matches('#').trim()).filter(|heading| !heading.is_empty())
+}
+
+fn char_offset(text: &str, byte: usize) -> i32 {
+    text[..byte].chars().count() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_text() {
+        let paragraph = |n: usize| format!("Paragraph {} has a few sentences about gardens. It goes on for a while to take up room.", n);
+        let text = format!("# Notes\n\n{}\n\n{}\n\n## Details\n\n{}\n{}\n{}\n",
+                           paragraph(1), paragraph(2), paragraph(3), paragraph(4), paragraph(5));
+        let options = ChunkOptions { max_tokens: 50, overlap_tokens: 25 };
+        let chunks = chunk_text(&text, &options);
+
+        assert!(chunks.len() >= 3);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.index, i as i32);
+            assert!(chunk.token_count <= 50);
+            let passage: String = text.chars().skip(chunk.start_offset as usize).take((chunk.end_offset - chunk.start_offset) as usize).collect();
+            assert_eq!(passage, chunk.content);
+        }
+        // sections start new chunks, and carry their heading
+        assert!(chunks[0].content.starts_with("# Notes"));
+        let details = chunks.iter().position(|chunk| chunk.content.starts_with("## Details")).unwrap();
+        assert!(!chunks[details - 1].content.contains("Details"));
+        assert_eq!(chunks.last().unwrap().heading.as_deref(), Some("Details"));
+        // consecutive chunks of a section overlap by a paragraph
+        assert!(chunks[details + 1].content.starts_with(&paragraph(4)));
+
+        let long_word = "x".repeat(500);
+        assert!(chunk_text(&long_word, &options).iter().all(|chunk| chunk.token_count <= 50));
+        assert!(chunk_text("", &options).is_empty());
+    }
+}
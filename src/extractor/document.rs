@@ -0,0 +1,431 @@
+use std::any::Any;
+use std::io::{Cursor, Read};
+use std::panic;
+
+use bytes::Bytes;
+
+use kuchiki::traits::*;
+use percent_encoding::percent_decode_str;
+use url::Url;
+use zip::ZipArchive;
+
+use crate::extractor::{extract_article_with_rules, Metadata, SiteRules};
+use crate::extractor::readability::block_text;
+
+/// The kinds of documents text can be extracted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DocumentKind {
+    Html,
+    Pdf,
+    Docx,
+    Epub,
+    Text,
+    Markdown,
+}
+
+impl DocumentKind {
+    /// The kind of document with a MIME type, ignoring parameters like `charset`.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        match mime.as_str() {
+            "text/html" | "application/xhtml+xml" => Some(Self::Html),
+            "application/pdf" => Some(Self::Pdf),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Some(Self::Docx),
+            "application/epub+zip" => Some(Self::Epub),
+            "text/markdown" | "text/x-markdown" => Some(Self::Markdown),
+            "text/plain" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    /// The kind of document with a file name or URL path.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let path = path.split(['?', '#']).next().unwrap_or_default().to_lowercase();
+        let extension = path.rsplit_once('.').map(|(_, extension)| extension)?;
+        match extension {
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            "pdf" => Some(Self::Pdf),
+            "docx" => Some(Self::Docx),
+            "epub" => Some(Self::Epub),
+            "md" | "markdown" => Some(Self::Markdown),
+            "txt" | "text" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    /// The kind of document from its leading bytes, for when neither type nor name can be trusted.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"%PDF-") {
+            return Some(Self::Pdf);
+        }
+        if data.starts_with(b"PK\x03\x04") {
+            let mut archive = ZipArchive::new(Cursor::new(data)).ok()?;
+            if archive.by_name("word/document.xml").is_ok() {
+                return Some(Self::Docx);
+            }
+            if zip_text(&mut archive, "mimetype").ok()?.trim() == "application/epub+zip" {
+                return Some(Self::Epub);
+            }
+            return None;
+        }
+        let head = String::from_utf8_lossy(&data[..data.len().min(512)]).trim_start().to_lowercase();
+        if head.starts_with("<!doctype html") || head.starts_with("<html") {
+            return Some(Self::Html);
+        }
+        None
+    }
+
+    /// Pick the kind of a document, preferring its content, then its MIME type, then its name.
+    ///
+    /// Servers often send documents as `application/octet-stream`, or text as `text/html`.
+    pub fn detect(data: &[u8], content_type: Option<&str>, name: Option<&str>) -> Option<Self> {
+        Self::sniff(data)
+            .or_else(|| content_type.and_then(Self::from_content_type))
+            .or_else(|| name.and_then(Self::from_path))
+    }
+
+    /// The file extension documents of this kind are saved with.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Pdf => "pdf",
+            Self::Docx => "docx",
+            Self::Epub => "epub",
+            Self::Text => "txt",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum DocumentError {
+    #[error("unsupported document type")]
+    Unsupported,
+    #[error("unable to read pdf: {0}")]
+    Pdf(String),
+    #[error("unable to read archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("unable to read xml: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("unable to read document: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("missing {0}")]
+    Missing(&'static str),
+    #[error("extraction failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// The text and basic metadata of a document.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub(crate) struct Document {
+    pub kind: DocumentKind,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub page_count: Option<i32>,
+    /// The URL of the lead image, for HTML documents.
+    pub lead_image: Option<String>,
+    /// The HTML of the main content, for HTML documents.
+    #[serde(skip)]
+    pub content_html: Option<String>,
+    /// Canonical URLs the document links to, for HTML documents.
+    pub links: Vec<String>,
+    #[serde(skip)]
+    pub text: String,
+}
+
+impl Document {
+    fn new(kind: DocumentKind, text: String) -> Self {
+        Self {
+            kind,
+            title: None,
+            author: None,
+            page_count: None,
+            lead_image: None,
+            content_html: None,
+            links: vec![],
+            text,
+        }
+    }
+
+    /// The document's metadata, in the shape HTML metadata is stored in.
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            title: self.title.clone(),
+            author: self.author.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Extract the text of a document of any supported kind.
+///
+/// `content_type` and `name` are hints, see `DocumentKind::detect`. `url` is used to resolve
+/// links in HTML documents, and `rules` to extract their content.
+pub(crate) fn extract_document(data: &[u8],
+                               content_type: Option<&str>,
+                               name: Option<&str>,
+                               url: Option<&Url>,
+                               rules: Option<&SiteRules>) -> Result<Document, DocumentError> {
+    let kind = DocumentKind::detect(data, content_type, name).ok_or(DocumentError::Unsupported)?;
+    match kind {
+        DocumentKind::Html => {
+            let article = extract_article_with_rules(&String::from_utf8_lossy(data), url, rules);
+            let mut document = Document::new(kind, article.text);
+            document.title = article.title;
+            document.author = article.byline;
+            document.lead_image = article.lead_image;
+            document.content_html = Some(article.content_html);
+            document.links = article.links;
+            Ok(document)
+        }
+        DocumentKind::Pdf => extract_pdf(data),
+        DocumentKind::Docx => extract_docx(data),
+        DocumentKind::Epub => extract_epub(data),
+        DocumentKind::Markdown => {
+            let text = String::from_utf8_lossy(data).into_owned();
+            let mut document = Document::new(kind, text);
+            document.title = markdown_title(&document.text);
+            Ok(document)
+        }
+        DocumentKind::Text => Ok(Document::new(kind, String::from_utf8_lossy(data).into_owned())),
+    }
+}
+
+/// Like `extract_document`, on a blocking thread so that parsing a large document doesn't
+/// hold up the runtime.
+pub(crate) async fn extract_document_async(data: Bytes,
+                                           content_type: Option<String>,
+                                           name: Option<String>,
+                                           url: Option<Url>,
+                                           rules: Option<SiteRules>) -> Result<Document, DocumentError> {
+    tokio::task::spawn_blocking(move || extract_document(&data, content_type.as_deref(), name.as_deref(), url.as_ref(), rules.as_ref()))
+        .await?
+}
+
+fn extract_pdf(data: &[u8]) -> Result<Document, DocumentError> {
+    // pdf_extract and lopdf panic on some malformed files rather than returning an error
+    let text = panic::catch_unwind(|| pdf_extract::extract_text_from_mem(data))
+        .map_err(|panic| DocumentError::Pdf(panic_message(panic.as_ref())))?
+        .map_err(|e| DocumentError::Pdf(e.to_string()))?;
+    let mut document = Document::new(DocumentKind::Pdf, text);
+
+    // metadata is optional, so a file pdf_extract could read is never rejected because of it
+    if let Ok(Ok(pdf)) = panic::catch_unwind(|| lopdf::Document::load_mem(data)) {
+        document.page_count = i32::try_from(pdf.get_pages().len()).ok();
+        let info = pdf.trailer.get(b"Info").ok()
+            .and_then(|info| match info.as_reference() {
+                Ok(id) => pdf.get_object(id).ok(),
+                Err(_) => Some(info),
+            })
+            .and_then(|info| info.as_dict().ok());
+        if let Some(info) = info {
+            let field = |key: &[u8]| info.get(key).ok()
+                .and_then(|value| value.as_str().ok())
+                .map(pdf_string)
+                .filter(|value| !value.is_empty());
+            document.title = field(b"Title");
+            document.author = field(b"Author");
+        }
+    }
+    Ok(document)
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => panic.downcast_ref::<String>().cloned().unwrap_or_else(|| "malformed pdf".to_string()),
+    }
+}
+
+/// Decode a PDF text string, which is either UTF-16BE with a byte order mark or PDFDocEncoding.
+fn pdf_string(bytes: &[u8]) -> String {
+    let text = match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        // PDFDocEncoding matches Latin-1 for printable characters
+        None => bytes.iter().map(|b| *b as char).collect(),
+    };
+    text.trim().to_string()
+}
+
+fn extract_docx(data: &[u8]) -> Result<Document, DocumentError> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+
+    let xml = zip_text(&mut archive, "word/document.xml")?;
+    let body = roxmltree::Document::parse(&xml)?;
+    let paragraphs: Vec<String> = body.descendants()
+        .filter(|node| node.has_tag_name((WORD_NS, "p")))
+        .map(|paragraph| {
+            paragraph.descendants()
+                .filter_map(|node| match node.tag_name().name() {
+                    "t" if node.tag_name().namespace() == Some(WORD_NS) => node.text().map(|t| t.to_string()),
+                    "tab" => Some("\t".to_string()),
+                    "br" | "cr" => Some("\n".to_string()),
+                    _ => None,
+                })
+                .collect::<String>()
+        })
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .collect();
+    let mut document = Document::new(DocumentKind::Docx, paragraphs.join("\n"));
+
+    // like PDFs, documents with broken metadata still have their text extracted
+    let core = zip_text(&mut archive, "docProps/core.xml").ok();
+    if let Some(core) = core.as_deref().and_then(|core| roxmltree::Document::parse(core).ok()) {
+        document.title = element_text(&core, DC_NS, "title");
+        document.author = element_text(&core, DC_NS, "creator");
+    }
+    let app = zip_text(&mut archive, "docProps/app.xml").ok();
+    if let Some(app) = app.as_deref().and_then(|app| roxmltree::Document::parse(app).ok()) {
+        document.page_count = app.descendants()
+            .find(|node| node.tag_name().name() == "Pages")
+            .and_then(|node| node.text())
+            .and_then(|pages| pages.trim().parse().ok());
+    }
+    Ok(document)
+}
+
+fn extract_epub(data: &[u8]) -> Result<Document, DocumentError> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+
+    let container = zip_text(&mut archive, "META-INF/container.xml")?;
+    let container = roxmltree::Document::parse(&container)?;
+    let opf_path = container.descendants()
+        .find(|node| node.tag_name().name() == "rootfile")
+        .and_then(|node| node.attribute("full-path"))
+        .ok_or(DocumentError::Missing("rootfile"))?
+        .to_string();
+    let opf = zip_text(&mut archive, &opf_path)?;
+    let opf = roxmltree::Document::parse(&opf)?;
+    let base_dir = opf_path.rsplit_once('/').map(|(dir, _)| format!("{}/", dir)).unwrap_or_default();
+
+    // read the chapters in spine order
+    let manifest: Vec<(&str, &str)> = opf.descendants()
+        .filter(|node| node.tag_name().name() == "item")
+        .filter_map(|node| Some((node.attribute("id")?, node.attribute("href")?)))
+        .collect();
+    let chapters: Vec<String> = opf.descendants()
+        .filter(|node| node.tag_name().name() == "itemref")
+        .filter_map(|node| node.attribute("idref"))
+        .filter_map(|idref| manifest.iter().find(|(id, _)| *id == idref))
+        .map(|(_, href)| format!("{}{}", base_dir, percent_decode_str(href).decode_utf8_lossy()))
+        .collect();
+
+    let mut text = Vec::new();
+    for chapter in &chapters {
+        let Ok(html) = zip_text(&mut archive, chapter) else {
+            continue;
+        };
+        let chapter_text = block_text(&kuchiki::parse_html().one(html));
+        if !chapter_text.is_empty() {
+            text.push(chapter_text);
+        }
+    }
+
+    let mut document = Document::new(DocumentKind::Epub, text.join("\n\n"));
+    document.title = element_text(&opf, DC_NS, "title");
+    document.author = element_text(&opf, DC_NS, "creator");
+    Ok(document)
+}
+
+const WORD_NS: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const DC_NS: &str = "http://purl.org/dc/elements/1.1/";
+
+fn zip_text(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String, DocumentError> {
+    let mut file = archive.by_name(name)?;
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+fn element_text(document: &roxmltree::Document, namespace: &str, name: &str) -> Option<String> {
+    document.descendants()
+        .find(|node| node.has_tag_name((namespace, name)))
+        .and_then(|node| node.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+fn markdown_title(markdown: &str) -> Option<String> {
+    markdown.lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_document_malformed_pdf() {
+        // a page without a MediaBox makes pdf_extract panic
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+            "<< /Type /Page /Parent 2 0 R >>",
+        ];
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend(b"xref\n0 4\n0000000000 65535 f \n");
+        for offset in offsets {
+            pdf.extend(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend(format!("trailer\n<< /Size 4 /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", xref).as_bytes());
+
+        for data in [&pdf[..], b"%PDF-1.4\n"] {
+            let result = extract_document(data, Some("application/pdf"), None, None, None);
+            assert!(matches!(result, Err(DocumentError::Pdf(_))), "{:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_extract_document_text_and_markdown() {
+        let markdown = extract_document(b"Intro line\n\n# Garden Notes\n\nSome text.", Some("application/octet-stream"), Some("notes.md"), None, None).unwrap();
+        assert_eq!(markdown.kind, DocumentKind::Markdown);
+        assert_eq!(markdown.title.as_deref(), Some("Garden Notes"));
+
+        let text = extract_document(b"Plain words.", Some("text/plain; charset=utf-8"), None, None, None).unwrap();
+        assert_eq!(text.kind, DocumentKind::Text);
+        assert_eq!(text.text, "Plain words.");
+
+        assert_eq!(DocumentKind::from_path("https://example.com/paper.PDF?download=1"), Some(DocumentKind::Pdf));
+        assert_eq!(DocumentKind::sniff(b"%PDF-1.7\n"), Some(DocumentKind::Pdf));
+        assert!(matches!(extract_document(b"\x00\x01", None, Some("blob.bin"), None, None), Err(DocumentError::Unsupported)));
+    }
+
+    #[test]
+    fn test_extract_document_docx() {
+        use std::io::Write;
+
+        let mut docx = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let files = [
+            ("word/document.xml", r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+<w:p><w:r><w:t>First</w:t></w:r><w:r><w:t xml:space="preserve"> paragraph.</w:t></w:r></w:p>
+<w:p><w:r><w:t>Second paragraph.</w:t></w:r></w:p>
+</w:body></w:document>"#),
+            ("docProps/core.xml", r#"<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Quarterly Report</dc:title><dc:creator>Jane Doe</dc:creator></cp:coreProperties>"#),
+            ("docProps/app.xml", r#"<Properties xmlns="http://schemas.openxmlformats.org/officeDocument/2006/extended-properties"><Pages>3</Pages></Properties>"#),
+        ];
+        for (name, content) in files {
+            docx.start_file(name, zip::write::FileOptions::default()).unwrap();
+            docx.write_all(content.as_bytes()).unwrap();
+        }
+        let data = docx.finish().unwrap().into_inner();
+
+        let document = extract_document(&data, None, None, None, None).unwrap();
+        assert_eq!(document.kind, DocumentKind::Docx);
+        assert_eq!(document.text, "First paragraph.\nSecond paragraph.");
+        assert_eq!(document.title.as_deref(), Some("Quarterly Report"));
+        assert_eq!(document.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(document.page_count, Some(3));
+    }
+}
@@ -0,0 +1,80 @@
+use kuchiki::NodeRef;
+use url::Url;
+
+/// Query parameters that only track where a visitor came from.
+const TRACKING_PARAMS: [&str; 8] = ["fbclid", "gclid", "mc_cid", "mc_eid", "ref", "ref_src", "igshid", "yclid"];
+
+/// Resolve `href` against `base` and normalize it, so that different links to the same page
+/// compare equal.
+///
+/// Fragments and tracking parameters are dropped, and only `http` and `https` URLs are kept.
+pub(crate) fn canonical_url(href: &str, base: Option<&Url>) -> Option<String> {
+    let href = href.trim();
+    let mut url = match base {
+        Some(base) => base.join(href).ok()?,
+        None => Url::parse(href).ok()?,
+    };
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return None;
+    }
+    url.host_str()?;
+
+    url.set_fragment(None);
+    strip_tracking_params(&mut url);
+    Some(url.to_string())
+}
+
+/// Resolve `href` against `base`, leaving it as it is if that isn't possible.
+pub(crate) fn absolute_url(base: Option<&Url>, href: &str) -> String {
+    base.and_then(|base| base.join(href).ok())
+        .map(|url| url.to_string())
+        .unwrap_or_else(|| href.to_string())
+}
+
+/// Remove `utm_` and other tracking parameters from a URL's query.
+pub(crate) fn strip_tracking_params(url: &mut Url) {
+    if url.query().is_none() {
+        return;
+    }
+    let query: Vec<(String, String)> = url.query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+}
+
+/// The distinct canonical URLs linked from a node, other than links back to `base` itself.
+pub(crate) fn outbound_links(node: &NodeRef, base: Option<&Url>) -> Vec<String> {
+    let own = base.and_then(|base| canonical_url(base.as_str(), None));
+    let mut links: Vec<String> = Vec::new();
+    let Ok(anchors) = node.select("a[href]") else {
+        return links;
+    };
+    for anchor in anchors {
+        let href = anchor.attributes.borrow().get("href").unwrap_or_default().to_string();
+        if let Some(link) = canonical_url(&href, base) {
+            if Some(&link) != own.as_ref() && !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_url() {
+        let base = Url::parse("https://blog.example.com/2023/post.html").unwrap();
+        assert_eq!(canonical_url("../about?utm_source=x&id=2#top", Some(&base)).as_deref(), Some("https://blog.example.com/about?id=2"));
+        assert_eq!(canonical_url("HTTPS://Example.COM:443/a?fbclid=1", None).as_deref(), Some("https://example.com/a"));
+        assert_eq!(canonical_url("mailto:jane@example.com", None), None);
+        assert_eq!(canonical_url("javascript:void(0)", Some(&base)), None);
+    }
+}
@@ -0,0 +1,321 @@
+use kuchiki::{NodeData, NodeRef};
+use kuchiki::traits::*;
+use url::Url;
+
+/// Elements whose content is dropped entirely.
+const SKIP_TAGS: [&str; 9] = ["head", "script", "style", "noscript", "template", "iframe", "svg", "button", "form"];
+
+/// Elements that are rendered as a paragraph of their content.
+const BLOCK_TAGS: [&str; 14] = [
+    "address", "article", "aside", "dd", "details", "div", "dl", "dt", "figcaption", "figure",
+    "footer", "header", "main", "section",
+];
+
+/// Convert HTML to CommonMark, keeping headings, emphasis, links, lists, blockquotes, tables,
+/// images and fenced code blocks.
+///
+/// Relative link and image URLs are made absolute against `base`, so that the result can be
+/// stored as page content away from the page it was clipped from.
+pub(crate) fn html_to_markdown(html: &str, base: Option<&Url>) -> String {
+    let document = kuchiki::parse_html().one(html);
+    let root = match document.select_first("body") {
+        Ok(body) => body.as_node().clone(),
+        Err(_) => document,
+    };
+    let converter = Converter { base };
+    tidy(&converter.children(&root), true)
+}
+
+/// The URL of the first image in Markdown, `![alt](url)` or an HTML `img`, if it's absolute.
+pub(crate) fn first_image(markdown: &str) -> Option<String> {
+    let markdown_image = markdown.find("![").and_then(|start| {
+        let rest = &markdown[start..];
+        let open = rest.find("](")? + 2;
+        let close = rest[open..].find(')')? + open;
+        // drop an optional title, `![alt](url "title")`
+        let url = rest[open..close].split_whitespace().next()?.trim_matches(|c| c == '<' || c == '>');
+        Some((start, url.to_string()))
+    });
+    let html_image = markdown.find("<img").and_then(|start| {
+        let tag = &markdown[start..start + markdown[start..].find('>')?];
+        let src = tag.find("src=")? + 4;
+        let quote = tag[src..].chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let url = tag[src + 1..].split(quote).next()?;
+        Some((start, url.to_string()))
+    });
+    [markdown_image, html_image].into_iter().flatten()
+        .min_by_key(|(position, _)| *position)
+        .map(|(_, url)| url)
+        .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+}
+
+struct Converter<'a> {
+    base: Option<&'a Url>,
+}
+
+impl Converter<'_> {
+    fn children(&self, node: &NodeRef) -> String {
+        node.children().map(|child| self.node(&child)).collect()
+    }
+
+    fn node(&self, node: &NodeRef) -> String {
+        let element = match node.data() {
+            NodeData::Text(text) => return escape(&collapse_whitespace(&text.borrow())),
+            NodeData::Element(element) => element,
+            _ => return String::new(),
+        };
+        let tag = element.name.local.to_string();
+        let attribute = |name: &str| element.attributes.borrow().get(name).map(|v| v.trim().to_string());
+
+        match tag.as_str() {
+            t if SKIP_TAGS.contains(&t) => String::new(),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = tag[1..].parse::<usize>().unwrap_or(1);
+                let text = single_line(&self.children(node));
+                if text.is_empty() {
+                    String::new()
+                } else {
+                    format!("\n\n{} {}\n\n", "#".repeat(level), text)
+                }
+            }
+            "p" => format!("\n\n{}\n\n", self.children(node).trim()),
+            t if BLOCK_TAGS.contains(&t) => format!("\n\n{}\n\n", self.children(node).trim()),
+            "br" => "  \n".to_string(),
+            "hr" => "\n\n---\n\n".to_string(),
+            "strong" | "b" => wrap(&self.children(node), "**"),
+            "em" | "i" => wrap(&self.children(node), "_"),
+            "del" | "s" | "strike" => wrap(&self.children(node), "~~"),
+            "code" | "kbd" | "samp" => inline_code(&node.text_contents()),
+            "pre" => code_block(node),
+            "a" => {
+                let text = self.children(node).trim().to_string();
+                match attribute("href").filter(|href| !href.is_empty() && !href.starts_with("javascript:")) {
+                    Some(href) if !text.is_empty() => format!("[{}]({})", text, self.absolute(&href)),
+                    _ => text,
+                }
+            }
+            "img" => match attribute("src").filter(|src| !src.is_empty()) {
+                Some(src) => format!("![{}]({})", escape(&attribute("alt").unwrap_or_default()), self.absolute(&src)),
+                None => String::new(),
+            },
+            "ul" | "ol" => self.list(node, tag == "ol", attribute("start")),
+            "blockquote" => {
+                let quoted = tidy(&self.children(node), true)
+                    .lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("\n\n{}\n\n", quoted)
+            }
+            "table" => self.table(node),
+            _ => self.children(node),
+        }
+    }
+
+    fn list(&self, node: &NodeRef, ordered: bool, start: Option<String>) -> String {
+        let mut number = start.and_then(|start| start.parse::<usize>().ok()).unwrap_or(1);
+        let mut items = Vec::new();
+        for item in node.children().filter(|child| is_tag(child, "li")) {
+            let marker = if ordered { format!("{}. ", number) } else { "- ".to_string() };
+            number += 1;
+            // keep list items tight, nested blocks go on the following lines
+            let content = tidy(self.children(&item).trim(), false);
+            let indent = " ".repeat(marker.len());
+            let lines: Vec<String> = content.lines().enumerate()
+                .map(|(i, line)| match i {
+                    0 => format!("{}{}", marker, line),
+                    _ if line.is_empty() => String::new(),
+                    _ => format!("{}{}", indent, line),
+                })
+                .collect();
+            items.push(if lines.is_empty() { marker.trim_end().to_string() } else { lines.join("\n") });
+        }
+        format!("\n\n{}\n\n", items.join("\n"))
+    }
+
+    fn table(&self, node: &NodeRef) -> String {
+        let rows: Vec<Vec<String>> = node.descendants()
+            .filter(|row| is_tag(row, "tr"))
+            .map(|row| {
+                row.children()
+                    .filter(|cell| is_tag(cell, "th") || is_tag(cell, "td"))
+                    .map(|cell| single_line(&self.children(&cell)).replace('|', "\\|"))
+                    .collect()
+            })
+            .filter(|cells: &Vec<String>| !cells.is_empty())
+            .collect();
+        let columns = rows.iter().map(|cells| cells.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return String::new();
+        }
+
+        let row = |cells: &[String]| {
+            let padded: Vec<&str> = (0..columns).map(|i| cells.get(i).map_or("", |c| c.as_str())).collect();
+            format!("| {} |", padded.join(" | "))
+        };
+        let mut lines = vec![row(&rows[0]), format!("|{}", " --- |".repeat(columns))];
+        lines.extend(rows[1..].iter().map(|cells| row(cells)));
+        format!("\n\n{}\n\n", lines.join("\n"))
+    }
+
+    fn absolute(&self, href: &str) -> String {
+        self.base.and_then(|base| base.join(href).ok())
+            .map(|url| url.to_string())
+            .unwrap_or_else(|| href.to_string())
+            .replace(' ', "%20")
+            .replace(')', "%29")
+    }
+}
+
+fn is_tag(node: &NodeRef, tag: &str) -> bool {
+    node.as_element().map_or(false, |e| &*e.name.local == tag)
+}
+
+/// Fence a `pre` block, taking the language from a `language-` or `lang-` class.
+fn code_block(node: &NodeRef) -> String {
+    let code = node.text_contents();
+    let language = node.inclusive_descendants()
+        .filter_map(|n| n.as_element().and_then(|e| e.attributes.borrow().get("class").map(|c| c.to_string())))
+        .flat_map(|class| class.split_whitespace().map(|c| c.to_string()).collect::<Vec<_>>())
+        .find_map(|class| class.strip_prefix("language-").or(class.strip_prefix("lang-")).map(|l| l.to_string()))
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run(&code, '`').max(2) + 1);
+    format!("\n\n{}{}\n{}\n{}\n\n", fence, language, code.trim_end_matches('\n'), fence)
+}
+
+fn inline_code(code: &str) -> String {
+    let code = collapse_whitespace(code);
+    if code.trim().is_empty() {
+        return String::new();
+    }
+    let fence = "`".repeat(longest_run(&code, '`') + 1);
+    let padding = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+    format!("{}{}{}{}{}", fence, padding, code, padding, fence)
+}
+
+fn longest_run(s: &str, c: char) -> usize {
+    s.split(|ch| ch != c).map(|run| run.len()).max().unwrap_or(0)
+}
+
+/// Wrap inline content in a delimiter, keeping surrounding whitespace outside of it.
+fn wrap(content: &str, delimiter: &str) -> String {
+    let trimmed = content.trim();
+    if trimmed.is_empty() {
+        return content.to_string();
+    }
+    let leading = if content.starts_with(char::is_whitespace) { " " } else { "" };
+    let trailing = if content.ends_with(char::is_whitespace) { " " } else { "" };
+    format!("{}{}{}{}{}", leading, delimiter, trimmed, delimiter, trailing)
+}
+
+fn single_line(content: &str) -> String {
+    content.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !last_whitespace {
+                result.push(' ');
+            }
+            last_whitespace = true;
+        } else {
+            result.push(c);
+            last_whitespace = false;
+        }
+    }
+    result
+}
+
+/// Escape the characters that would otherwise start emphasis, code or links, and a leading
+/// `#`, `-`, `+`, `>` or `1.` that would start a heading, list or blockquote when the text
+/// begins a line.
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+
+    let start = result.len() - result.trim_start().len();
+    let rest = &result[start..];
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    let marker = if rest.starts_with(['#', '-', '+', '>']) {
+        Some(start)
+    } else if digits > 0 && rest[digits..].starts_with(['.', ')']) {
+        Some(start + digits)
+    } else {
+        None
+    };
+    if let Some(marker) = marker {
+        result.insert(marker, '\\');
+    }
+    result
+}
+
+/// Collapse runs of blank lines, or drop them if `blank_lines` is false, leaving code blocks alone.
+fn tidy(markdown: &str, blank_lines: bool) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut fence: Option<String> = None;
+    for line in markdown.lines() {
+        match &fence {
+            Some(open) => {
+                if line.trim_end() == open {
+                    fence = None;
+                }
+                lines.push(line);
+            }
+            None => {
+                let trimmed = line.trim_start();
+                if trimmed.starts_with("```") {
+                    fence = Some(trimmed.chars().take_while(|c| *c == '`').collect());
+                    lines.push(line);
+                } else if line.trim().is_empty() {
+                    if blank_lines && lines.last().map_or(false, |last| !last.is_empty()) {
+                        lines.push("");
+                    }
+                } else if line.ends_with("  ") {
+                    lines.push(line);
+                } else {
+                    lines.push(line.trim_end());
+                }
+            }
+        }
+    }
+    while lines.last().map_or(false, |last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractor::get_test_content;
+    use crate::extractor::tests::fixture;
+
+    use super::*;
+
+    #[test]
+    fn test_html_to_markdown() {
+        let url = Url::parse("https://notes.example.com/posts/clipping").unwrap();
+        let markdown = html_to_markdown(&fixture("clipping"), Some(&url));
+        assert_eq!(markdown, get_test_content("etc/fixtures/extractor/clipping.md".to_string()).trim_end());
+    }
+
+    #[test]
+    fn test_html_to_markdown_escapes_line_starts() {
+        let html = "<p># not a heading</p><p>- not a list</p><p>+ nor this</p><p>1. or this</p><p>2023) either</p><p>> not quoted</p><p>a - b and 1. c</p>";
+        assert_eq!(html_to_markdown(html, None), "\\# not a heading\n\n\\- not a list\n\n\\+ nor this\n\n1\\. or this\n\n2023\\) either\n\n\\> not quoted\n\na - b and 1. c");
+    }
+
+    #[test]
+    fn test_first_image() {
+        assert_eq!(first_image("Intro\n\n![A garden](https://example.com/garden.png \"Garden\")").as_deref(), Some("https://example.com/garden.png"));
+        assert_eq!(first_image(r#"<img src="https://example.com/a.jpg"> then ![b](https://example.com/b.jpg)"#).as_deref(), Some("https://example.com/a.jpg"));
+        assert_eq!(first_image("![relative](images/c.png)"), None);
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use kuchiki::NodeRef;
+use kuchiki::traits::*;
+use serde_json::Value;
+use url::Url;
+
+use crate::extractor::links::absolute_url;
+use crate::extractor::normalize_whitespace;
+use crate::extractor::readability::parse_date;
+
+/// The metadata a page declares about itself, normalized across OpenGraph, Twitter cards,
+/// Dublin Core and schema.org JSON-LD.
+#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Metadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    /// The canonical URL of the page.
+    pub url: Option<String>,
+    /// The kind of page, e.g. `article` or `website`.
+    pub kind: Option<String>,
+    pub image: Option<String>,
+    pub author: Option<String>,
+    pub publisher: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+    pub modified: Option<DateTime<Utc>>,
+    pub language: Option<String>,
+    pub keywords: Vec<String>,
+    pub twitter_site: Option<String>,
+    /// The organization the page is about, if it declares one in JSON-LD.
+    pub organization: Option<Organization>,
+}
+
+/// A schema.org `Organization`, with the fields that map onto a `Company`.
+#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+pub(crate) struct Organization {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub logo: Option<String>,
+    pub description: Option<String>,
+    pub address: Option<String>,
+    pub ticker: Option<String>,
+    pub num_employees_min: Option<i32>,
+    pub num_employees_max: Option<i32>,
+    pub same_as: Vec<String>,
+}
+
+/// Extract the metadata of an HTML document.
+///
+/// Values are taken from JSON-LD first, then OpenGraph, Twitter cards, Dublin Core, and finally
+/// the plain HTML `title`, `meta` and `link` elements. `url` is used to make URLs absolute.
+pub(crate) fn extract_metadata(html: &str, url: Option<&Url>) -> Metadata {
+    let document = kuchiki::parse_html().one(html);
+    let meta = meta_values(&document);
+    let first = |keys: &[&str]| keys.iter()
+        .filter_map(|key| meta.get(*key).and_then(|values| values.first()))
+        .next()
+        .cloned();
+
+    let json_ld = json_ld_objects(&document);
+    let article = json_ld.iter().find(|object| has_type(object, is_article_type))
+        .or_else(|| json_ld.iter().find(|object| has_type(object, |t| t == "WebPage")));
+    let organization = json_ld.iter().find(|object| has_type(object, is_organization_type))
+        .or_else(|| article.and_then(|article| article.get("publisher")).filter(|p| p.is_object()))
+        .map(|object| to_organization(object, url));
+    let person = json_ld.iter().find(|object| has_type(object, |t| t == "Person"));
+    let ld = |key: &str| article.and_then(|article| article.get(key));
+
+    let title = ld("headline").or_else(|| ld("name")).and_then(text)
+        .or_else(|| first(&["og:title", "twitter:title", "dc.title", "dcterms.title"]))
+        .or_else(|| document.select_first("title").ok()
+            .map(|title| normalize_whitespace(&title.text_contents()))
+            .filter(|title| !title.is_empty()));
+    let description = ld("description").and_then(text)
+        .or_else(|| first(&["og:description", "twitter:description", "dc.description", "dcterms.description", "description"]));
+    let site_name = first(&["og:site_name", "application-name"])
+        .or_else(|| ld("publisher").and_then(text))
+        .or_else(|| organization.as_ref().and_then(|o| o.name.clone()));
+    let canonical = document.select_first("link[rel='canonical']").ok()
+        .and_then(|link| link.attributes.borrow().get("href").map(|href| href.to_string()));
+    let canonical_url = ld("url").and_then(text)
+        .or_else(|| first(&["og:url"]))
+        .or(canonical)
+        .map(|href| absolute_url(url, &href));
+    let kind = article.and_then(|article| article.get("@type")).and_then(text)
+        .or_else(|| first(&["og:type"]));
+    let image = ld("image").and_then(image_url)
+        .or_else(|| first(&["og:image", "og:image:url", "twitter:image", "twitter:image:src"]))
+        .map(|src| absolute_url(url, &src));
+    let author = ld("author").and_then(text)
+        .or_else(|| person.and_then(|person| person.get("name")).and_then(text))
+        .or_else(|| first(&["author", "article:author", "dc.creator", "dcterms.creator", "twitter:creator"])
+            .filter(|author| !author.starts_with("http")));
+    let publisher = ld("publisher").and_then(text)
+        .or_else(|| first(&["dc.publisher", "dcterms.publisher", "article:publisher"])
+            .filter(|publisher| !publisher.starts_with("http")));
+    let published = ld("datePublished").and_then(text)
+        .or_else(|| first(&["article:published_time", "dc.date", "dcterms.created", "dcterms.date", "date", "pubdate"]))
+        .and_then(|date| parse_date(&date));
+    let modified = ld("dateModified").and_then(text)
+        .or_else(|| first(&["article:modified_time", "og:updated_time", "dcterms.modified"]))
+        .and_then(|date| parse_date(&date));
+    let language = ld("inLanguage").and_then(text)
+        .or_else(|| first(&["og:locale", "dc.language", "dcterms.language", "language"]))
+        .or_else(|| document.select_first("html").ok()
+            .and_then(|html| html.attributes.borrow().get("lang").map(|lang| lang.to_string())))
+        .map(|language| language.replace('_', "-"))
+        .filter(|language| !language.is_empty());
+
+    let mut keywords: Vec<String> = Vec::new();
+    let declared = ld("keywords").map(strings).unwrap_or_default().into_iter()
+        .chain(meta.get("keywords").into_iter().flatten().cloned())
+        .chain(meta.get("dc.subject").into_iter().flatten().cloned())
+        .chain(meta.get("article:tag").into_iter().flatten().cloned());
+    for keyword in declared.flat_map(|k| k.split(',').map(|k| k.trim().to_string()).collect::<Vec<_>>()) {
+        if !keyword.is_empty() && !keywords.iter().any(|k| k.eq_ignore_ascii_case(&keyword)) {
+            keywords.push(keyword);
+        }
+    }
+
+    Metadata {
+        title,
+        description,
+        site_name,
+        url: canonical_url,
+        kind,
+        image,
+        author,
+        publisher,
+        published,
+        modified,
+        language,
+        keywords,
+        twitter_site: first(&["twitter:site"]),
+        organization,
+    }
+}
+
+/// The image of the JSON-LD article or page of a document, preferring articles.
+pub(crate) fn json_ld_image(document: &NodeRef) -> Option<String> {
+    let objects = json_ld_objects(document);
+    objects.iter().filter(|object| has_type(object, is_article_type))
+        .chain(objects.iter().filter(|object| !has_type(object, is_article_type)))
+        .filter(|object| !has_type(object, is_organization_type))
+        .find_map(|object| object.get("image").and_then(image_url))
+}
+
+/// Every `meta` value, keyed by its lower cased `property`, `name` or `itemprop`.
+fn meta_values(document: &NodeRef) -> HashMap<String, Vec<String>> {
+    let mut values: HashMap<String, Vec<String>> = HashMap::new();
+    let Ok(metas) = document.select("meta") else {
+        return values;
+    };
+    for meta in metas {
+        let attributes = meta.attributes.borrow();
+        let Some(content) = attributes.get("content").map(|c| c.trim().to_string()).filter(|c| !c.is_empty()) else {
+            continue;
+        };
+        for key in ["property", "name", "itemprop"] {
+            if let Some(key) = attributes.get(key) {
+                values.entry(key.trim().to_lowercase()).or_default().push(content.clone());
+            }
+        }
+    }
+    values
+}
+
+/// The objects of every JSON-LD script, with arrays and `@graph`s flattened.
+fn json_ld_objects(document: &NodeRef) -> Vec<Value> {
+    let mut objects = Vec::new();
+    if let Ok(scripts) = document.select("script[type='application/ld+json']") {
+        for script in scripts {
+            if let Ok(value) = serde_json::from_str::<Value>(script.text_contents().trim()) {
+                flatten_json_ld(value, &mut objects);
+            }
+        }
+    }
+    objects
+}
+
+fn flatten_json_ld(value: Value, objects: &mut Vec<Value>) {
+    match value {
+        Value::Array(values) => values.into_iter().for_each(|value| flatten_json_ld(value, objects)),
+        Value::Object(mut object) => {
+            if let Some(graph) = object.remove("@graph") {
+                flatten_json_ld(graph, objects);
+            }
+            objects.push(Value::Object(object));
+        }
+        _ => {}
+    }
+}
+
+fn has_type<F>(object: &Value, f: F) -> bool where F: Fn(&str) -> bool {
+    match object.get("@type") {
+        Some(Value::String(t)) => f(t.as_str()),
+        Some(Value::Array(types)) => types.iter().filter_map(|t| t.as_str()).any(f),
+        _ => false,
+    }
+}
+
+fn is_article_type(t: &str) -> bool {
+    t.ends_with("Article") || t == "BlogPosting" || t == "Report"
+}
+
+fn is_organization_type(t: &str) -> bool {
+    t.ends_with("Organization") || t == "Corporation" || t == "LocalBusiness"
+}
+
+/// The text of a JSON-LD value: a string, the name of an object, or the first of an array.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(normalize_whitespace(s)).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Object(object) => ["name", "@value", "url"].iter()
+            .filter_map(|key| object.get(*key))
+            .find_map(text),
+        Value::Array(values) => values.iter().find_map(text),
+        _ => None,
+    }
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(values) => values.iter().filter_map(text).collect(),
+        value => text(value).into_iter().collect(),
+    }
+}
+
+fn image_url(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Object(object) => object.get("url").or(object.get("contentUrl")).and_then(image_url),
+        Value::Array(values) => values.iter().find_map(image_url),
+        _ => None,
+    }
+}
+
+fn to_organization(object: &Value, base: Option<&Url>) -> Organization {
+    let (num_employees_min, num_employees_max) = object.get("numberOfEmployees")
+        .map(employee_range)
+        .unwrap_or((None, None));
+    Organization {
+        name: object.get("legalName").or(object.get("name")).and_then(text),
+        url: object.get("url").and_then(text).map(|href| absolute_url(base, &href)),
+        logo: object.get("logo").and_then(image_url).map(|src| absolute_url(base, &src)),
+        description: object.get("description").and_then(text),
+        address: object.get("address").and_then(address),
+        // "NASDAQ: EXMP" and "EXMP" both reduce to the symbol
+        ticker: object.get("tickerSymbol").and_then(text)
+            .map(|ticker| ticker.rsplit(':').next().unwrap_or_default().trim().to_string())
+            .filter(|ticker| !ticker.is_empty()),
+        num_employees_min,
+        num_employees_max,
+        same_as: object.get("sameAs").map(strings).unwrap_or_default(),
+    }
+}
+
+fn address(value: &Value) -> Option<String> {
+    match value {
+        Value::Object(object) => {
+            let parts: Vec<String> = ["streetAddress", "addressLocality", "addressRegion", "postalCode", "addressCountry"].iter()
+                .filter_map(|key| object.get(*key).and_then(text))
+                .collect();
+            Some(parts.join(", ")).filter(|address| !address.is_empty())
+        }
+        Value::Array(values) => values.iter().find_map(address),
+        value => text(value),
+    }
+}
+
+/// `numberOfEmployees` is either a number or a `QuantitativeValue` with a value or a range.
+fn employee_range(value: &Value) -> (Option<i32>, Option<i32>) {
+    let number = |value: Option<&Value>| value.and_then(|v| match v {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.replace([',', '+'], "").trim().parse::<i64>().ok(),
+        _ => None,
+    }).and_then(|n| i32::try_from(n).ok());
+
+    match value {
+        Value::Object(object) => match number(object.get("value")) {
+            Some(n) => (Some(n), Some(n)),
+            None => (number(object.get("minValue")), number(object.get("maxValue"))),
+        },
+        value => {
+            let n = number(Some(value));
+            (n, n)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractor::tests::fixture;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_metadata() {
+        let url = Url::parse("https://acme.example/news/rocket-skates?utm_source=feed").unwrap();
+        let metadata = extract_metadata(&fixture("metadata"), Some(&url));
+        assert_eq!(metadata.title.as_deref(), Some("Acme Launches Rocket Skates"));
+        assert_eq!(metadata.description.as_deref(), Some("OpenGraph description."));
+        assert_eq!(metadata.site_name.as_deref(), Some("Acme News"));
+        assert_eq!(metadata.url.as_deref(), Some("https://acme.example/news/rocket-skates"));
+        assert_eq!(metadata.kind.as_deref(), Some("NewsArticle"));
+        assert_eq!(metadata.image.as_deref(), Some("https://cdn.acme.example/skates-large.jpg"));
+        assert_eq!(metadata.author.as_deref(), Some("Road Runner"));
+        assert_eq!(metadata.published.map(|d| d.to_rfc3339()).as_deref(), Some("2023-08-01T10:00:00+00:00"));
+        assert_eq!(metadata.language.as_deref(), Some("en"));
+        assert_eq!(metadata.keywords, vec!["Skates", "Launch", "rockets"]);
+        assert_eq!(metadata.twitter_site.as_deref(), Some("@acme"));
+
+        let organization = metadata.organization.unwrap();
+        assert_eq!(organization.name.as_deref(), Some("Acme Corporation"));
+        assert_eq!(organization.url.as_deref(), Some("https://acme.example/"));
+        assert_eq!(organization.logo.as_deref(), Some("https://acme.example/logo.png"));
+        assert_eq!(organization.ticker.as_deref(), Some("ACME"));
+        assert_eq!(organization.num_employees_min, Some(1000));
+        assert_eq!(organization.num_employees_max, Some(5000));
+        assert_eq!(organization.address.as_deref(), Some("1 Desert Road, Tucson, US"));
+    }
+
+    #[test]
+    fn test_extract_metadata_without_title() {
+        let metadata = extract_metadata("<html><body><p>No head at all.</p></body></html>", None);
+        assert_eq!(metadata.title, None);
+        assert_eq!(metadata.organization, None);
+        assert!(metadata.keywords.is_empty());
+    }
+}
@@ -5,21 +5,45 @@ use std::io::Read;
 use kuchiki::traits::*;
 use kuchiki::{NodeRef};
 
+mod chunk;
+mod document;
+mod links;
+mod markdown;
+mod metadata;
+mod readability;
+mod render;
+mod rules;
+mod sanitize;
+mod stats;
+mod wiki;
+
+pub(crate) use chunk::{chunk_text, Chunk, ChunkOptions};
+pub(crate) use document::{extract_document, extract_document_async, DocumentKind};
+pub(crate) use links::{absolute_url, canonical_url};
+pub(crate) use markdown::{first_image, html_to_markdown};
+pub(crate) use metadata::{extract_metadata, Metadata, Organization};
+pub(crate) use readability::{extract_article, extract_article_with_rules};
+pub(crate) use render::{render_markdown, Rendered};
+pub(crate) use rules::SiteRules;
+pub(crate) use sanitize::{is_tracking_pixel, sanitize_html};
+pub(crate) use stats::{text_stats, TextStats};
+pub(crate) use wiki::{hashtags, replace_wiki_links, resolve_wiki_link, wiki_links, WikiLink};
+
 #[allow(dead_code)]
-pub(crate) async fn extract_text_from_str(content: String, ignore_whitespace: bool) -> Result<String, Box<dyn Error>> {
+pub(crate) fn extract_text_from_str(content: String, ignore_whitespace: bool) -> Result<String, Box<dyn Error>> {
     let document = kuchiki::parse_html().from_utf8().read_from(&mut content.as_bytes())?;
-    process_doc(document, ignore_whitespace).await
+    process_doc(document, ignore_whitespace)
 }
 
 #[allow(dead_code)]
-pub(crate) async fn extract_text_from_file(path: String, ignore_whitespace: bool) -> Result<String, Box<dyn Error>> {
+pub(crate) fn extract_text_from_file(path: String, ignore_whitespace: bool) -> Result<String, Box<dyn Error>> {
     let mut input: Box<dyn io::Read> = Box::new(std::fs::File::open(path).expect("Failed to open file"));
     let document = kuchiki::parse_html().from_utf8().read_from(&mut input)?;
-    process_doc(document, ignore_whitespace).await
+    process_doc(document, ignore_whitespace)
 }
 
 #[allow(dead_code)]
-pub(crate) async fn process_doc(document: NodeRef, ignore_whitespace: bool) -> Result<String, Box<dyn Error>> {
+pub(crate) fn process_doc(document: NodeRef, ignore_whitespace: bool) -> Result<String, Box<dyn Error>> {
     let mut output_buffer = String::new();
     let select_result = document.select("body");
     match select_result {
@@ -46,6 +70,9 @@ fn serialize_text(node: &NodeRef, ignore_whitespace: bool) -> String {
         if ignore_whitespace && text_node.borrow().trim().is_empty() {
             continue;
         }
+        if is_script_text(text_node.as_node()) {
+            continue;
+        }
 
         result.push_str(&text_node.borrow());
 
@@ -57,10 +84,38 @@ fn serialize_text(node: &NodeRef, ignore_whitespace: bool) -> String {
     result
 }
 
+/// Collapse runs of whitespace, including newlines, into single spaces.
+pub(crate) fn normalize_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether a text node is the body of a script or stylesheet rather than page text.
+fn is_script_text(node: &NodeRef) -> bool {
+    node.parent()
+        .and_then(|parent| parent.as_element().map(|e| e.name.local.to_string()))
+        .map_or(false, |tag| tag == "script" || tag == "style" || tag == "noscript")
+}
+
 #[allow(dead_code)]
 pub(crate) fn get_test_content(path: String) -> String {
     let mut file: File = File::open(path).unwrap();
     let mut contents = String::new();
     file.read_to_string(&mut contents).unwrap();
     contents
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) fn fixture(name: &str) -> String {
+        get_test_content(format!("etc/fixtures/extractor/{}.html", name))
+    }
+
+    #[test]
+    fn test_extract_text_skips_scripts() {
+        let text = extract_text_from_str(fixture("no-article"), true).unwrap();
+        assert!(text.contains("Extraction should still find this text"));
+        assert!(!text.contains("console.log"));
+    }
+}
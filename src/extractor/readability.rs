@@ -0,0 +1,587 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use kuchiki::{Node, NodeData, NodeRef};
+use kuchiki::traits::*;
+use url::Url;
+
+use crate::extractor::links::{absolute_url, outbound_links};
+use crate::extractor::metadata::json_ld_image;
+use crate::extractor::normalize_whitespace;
+use crate::extractor::rules::SiteRules;
+
+/// Elements that never contain article content.
+const REMOVE_TAGS: [&str; 14] = [
+    "script", "style", "noscript", "iframe", "form", "nav", "footer", "aside",
+    "svg", "button", "input", "select", "textarea", "template",
+];
+
+/// Class and id fragments of elements that are usually boilerplate.
+const UNLIKELY: [&str; 23] = [
+    "banner", "breadcrumb", "combx", "comment", "community", "consent", "cookie", "disqus",
+    "footer", "gdpr", "header", "menu", "modal", "nav", "newsletter", "popup", "promo",
+    "related", "share", "sidebar", "social", "sponsor", "subscribe",
+];
+
+/// Class and id fragments of elements that are usually the article.
+const LIKELY: [&str; 9] = ["article", "body", "content", "entry", "main", "page", "post", "story", "text"];
+
+/// Class and id fragments that lower an element's score.
+const NEGATIVE: [&str; 10] = ["ad-", "advert", "comment", "footer", "meta", "related", "share", "shoutbox", "sidebar", "widget"];
+
+/// Class and id fragments that raise an element's score.
+const POSITIVE: [&str; 7] = ["article", "blog", "body", "content", "entry", "post", "story"];
+
+/// Elements whose text is scored as a paragraph.
+const SCORE_TAGS: [&str; 4] = ["p", "pre", "td", "blockquote"];
+
+/// Elements that start a new line when converting to plain text.
+const BLOCK_TAGS: [&str; 21] = [
+    "address", "article", "blockquote", "dd", "div", "dl", "dt", "figcaption", "figure",
+    "h1", "h2", "h3", "h4", "h5", "h6", "li", "ol", "p", "pre", "section", "ul",
+];
+
+/// Paragraphs shorter than this don't count towards their parent's score.
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// The main content of a webpage, with the boilerplate around it removed.
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize)]
+pub(crate) struct Article {
+    pub title: Option<String>,
+    pub byline: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+    pub lead_image: Option<String>,
+    /// The cleaned HTML of the main content.
+    pub content_html: String,
+    /// The main content as plain text, one paragraph per line.
+    pub text: String,
+    /// Canonical URLs of the pages the main content links to.
+    pub links: Vec<String>,
+}
+
+/// Extract the main content of an HTML document.
+///
+/// This follows the approach of Mozilla's Readability: boilerplate elements are removed,
+/// every paragraph adds to the score of its parent and grandparent, scores are reduced by
+/// link density, and the best scoring element is taken along with any siblings that look
+/// like they belong to it. `url` is used to make image URLs absolute.
+pub(crate) fn extract_article(html: &str, url: Option<&Url>) -> Article {
+    extract_article_with_rules(html, url, None)
+}
+
+/// Like `extract_article`, applying a site's rules before falling back to the heuristics.
+pub(crate) fn extract_article_with_rules(html: &str, url: Option<&Url>, rules: Option<&SiteRules>) -> Article {
+    let document = kuchiki::parse_html().one(html);
+
+    if let Some(rules) = rules {
+        rules.remove(&document);
+    }
+
+    let title = extract_title(&document);
+    let byline = rules.and_then(|rules| rules.author(&document))
+        .or_else(|| extract_byline(&document));
+    let published = rules.and_then(|rules| rules.published(&document))
+        .or_else(|| extract_published(&document));
+    let meta_image = meta_content(&document, &["meta[property='og:image']", "meta[property='og:image:url']"])
+        .or_else(|| json_ld_image(&document))
+        .or_else(|| meta_content(&document, &["meta[name='twitter:image']"]));
+
+    let selected = rules.map(|rules| rules.content(&document)).unwrap_or_default();
+    let content = match selected.as_slice() {
+        [] => {
+            remove_boilerplate(&document);
+            match top_candidate(&document) {
+                Some(candidate) => collect_content(&candidate),
+                None => match document.select_first("body") {
+                    Ok(body) => body.as_node().clone(),
+                    Err(_) => document.clone(),
+                },
+            }
+        }
+        [node] => node.clone(),
+        nodes => {
+            let wrapper = new_div();
+            nodes.iter().for_each(|node| wrapper.append(node.clone()));
+            wrapper
+        }
+    };
+    // selected content keeps its sidebars and headers, the selector said they belong there
+    if !selected.is_empty() {
+        remove_tags(&content);
+    }
+    clean_content(&content);
+
+    let lead_image = meta_image.or_else(|| largest_image(&content))
+        .map(|src| absolute_url(url, &src));
+
+    let links = outbound_links(&content, url);
+
+    let mut content_html = Vec::new();
+    let _ = content.serialize(&mut content_html);
+
+    Article {
+        title,
+        byline,
+        published,
+        lead_image,
+        content_html: String::from_utf8_lossy(&content_html).into_owned(),
+        text: block_text(&content),
+        links,
+    }
+}
+
+/// Images smaller than this in either dimension are icons, spacers or tracking pixels.
+const MIN_IMAGE_SIZE: u32 = 100;
+
+/// The `src` of the largest image in the content, by its declared size.
+///
+/// Images without a declared size count as just large enough, so the first of them is taken
+/// when no image declares one.
+fn largest_image(content: &NodeRef) -> Option<String> {
+    let images = content.select("img").ok()?;
+    let mut largest: Option<(u32, String)> = None;
+    for image in images {
+        let attributes = image.attributes.borrow();
+        let Some(src) = attributes.get("src").or(attributes.get("data-src")).map(|src| src.trim().to_string()) else {
+            continue;
+        };
+        if src.is_empty() || src.starts_with("data:") {
+            continue;
+        }
+        let dimension = |name: &str| attributes.get(name)
+            .and_then(|value| value.trim().trim_end_matches("px").parse::<u32>().ok());
+        let area = match (dimension("width"), dimension("height")) {
+            (Some(width), _) if width < MIN_IMAGE_SIZE => continue,
+            (_, Some(height)) if height < MIN_IMAGE_SIZE => continue,
+            (Some(width), Some(height)) => width.saturating_mul(height),
+            (Some(side), None) | (None, Some(side)) => side.saturating_mul(side),
+            (None, None) => MIN_IMAGE_SIZE * MIN_IMAGE_SIZE,
+        };
+        if largest.as_ref().map_or(true, |(largest_area, _)| area > *largest_area) {
+            largest = Some((area, src));
+        }
+    }
+    largest.map(|(_, src)| src)
+}
+
+fn meta_content(document: &NodeRef, selectors: &[&str]) -> Option<String> {
+    selectors.iter()
+        .filter_map(|selector| document.select_first(selector).ok())
+        .filter_map(|meta| meta.attributes.borrow().get("content").map(|c| c.trim().to_string()))
+        .find(|content| !content.is_empty())
+}
+
+fn extract_title(document: &NodeRef) -> Option<String> {
+    if let Some(title) = meta_content(document, &["meta[property='og:title']", "meta[name='twitter:title']"]) {
+        return Some(title);
+    }
+    let title = document.select_first("title").ok()
+        .map(|title| normalize_whitespace(&title.text_contents()))
+        .filter(|title| !title.is_empty());
+    match title {
+        // drop a trailing site name, e.g. "Post title | Site"
+        Some(title) => {
+            let stripped = [" | ", " - ", " — ", " :: "].iter()
+                .filter_map(|separator| title.rsplit_once(separator).map(|(head, _)| head.trim().to_string()))
+                .find(|head| head.split_whitespace().count() >= 3);
+            Some(stripped.unwrap_or(title))
+        }
+        None => document.select_first("h1").ok()
+            .map(|h1| normalize_whitespace(&h1.text_contents()))
+            .filter(|h1| !h1.is_empty()),
+    }
+}
+
+fn extract_byline(document: &NodeRef) -> Option<String> {
+    if let Some(author) = meta_content(document, &["meta[name='author']", "meta[property='article:author']"]) {
+        if !author.starts_with("http") {
+            return Some(author);
+        }
+    }
+    ["[rel='author']", "[itemprop='author']", ".byline", ".author", ".post-author"].iter()
+        .filter_map(|selector| document.select_first(selector).ok())
+        .map(|node| normalize_whitespace(&node.text_contents()))
+        .map(|byline| byline.trim_start_matches("By ").trim_start_matches("by ").to_string())
+        .find(|byline| !byline.is_empty() && byline.len() < 100)
+}
+
+fn extract_published(document: &NodeRef) -> Option<DateTime<Utc>> {
+    let meta = meta_content(document, &[
+        "meta[property='article:published_time']",
+        "meta[name='date']",
+        "meta[name='pubdate']",
+        "meta[itemprop='datePublished']",
+    ]);
+    let time = || document.select_first("time[datetime]").ok()
+        .and_then(|time| time.attributes.borrow().get("datetime").map(|dt| dt.to_string()));
+    meta.or_else(time).and_then(|dt| parse_date(&dt))
+}
+
+pub(crate) fn parse_date(dt: &str) -> Option<DateTime<Utc>> {
+    let dt = dt.trim();
+    DateTime::parse_from_rfc3339(dt)
+        .or_else(|_| DateTime::parse_from_rfc2822(dt))
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(dt.get(..10)?, "%Y-%m-%d").ok()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| Utc.from_utc_datetime(&d)))
+}
+
+fn tag_name(node: &NodeRef) -> Option<String> {
+    node.as_element().map(|e| e.name.local.to_string())
+}
+
+fn class_and_id(node: &NodeRef) -> String {
+    match node.as_element() {
+        Some(element) => {
+            let attributes = element.attributes.borrow();
+            format!("{} {}", attributes.get("class").unwrap_or_default(), attributes.get("id").unwrap_or_default())
+                .to_lowercase()
+        }
+        None => String::new(),
+    }
+}
+
+fn is_unlikely(node: &NodeRef) -> bool {
+    let tag = tag_name(node).unwrap_or_default();
+    if ["html", "body", "article", "main"].contains(&tag.as_str()) {
+        return false;
+    }
+    let names = class_and_id(node);
+    let role = node.as_element()
+        .and_then(|e| e.attributes.borrow().get("role").map(|r| r.to_string()))
+        .unwrap_or_default();
+    ["navigation", "banner", "complementary", "contentinfo", "dialog"].contains(&role.as_str())
+        || (UNLIKELY.iter().any(|u| names.contains(u)) && !LIKELY.iter().any(|l| names.contains(l)))
+}
+
+fn remove_boilerplate(document: &NodeRef) {
+    let mut to_remove: Vec<NodeRef> = Vec::new();
+    for node in document.descendants() {
+        match node.data() {
+            NodeData::Comment(_) => to_remove.push(node.clone()),
+            NodeData::Element(element) => {
+                let tag = element.name.local.to_string();
+                if REMOVE_TAGS.contains(&tag.as_str()) || (tag == "header" && node.ancestors().all(|a| tag_name(&a).as_deref() != Some("article"))) {
+                    to_remove.push(node.clone());
+                } else if is_unlikely(&node) {
+                    to_remove.push(node.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+    for node in to_remove {
+        node.detach();
+    }
+}
+
+/// Remove scripts, forms and other elements that are never content, and comments.
+fn remove_tags(content: &NodeRef) {
+    let to_remove: Vec<NodeRef> = content.descendants()
+        .filter(|node| match node.data() {
+            NodeData::Comment(_) => true,
+            NodeData::Element(element) => ["script", "style", "noscript", "iframe", "form", "button", "input", "select", "textarea", "template"]
+                .contains(&&*element.name.local),
+            _ => false,
+        })
+        .collect();
+    for node in to_remove {
+        node.detach();
+    }
+}
+
+fn class_weight(node: &NodeRef) -> f64 {
+    let names = class_and_id(node);
+    let mut weight = 0.0;
+    if NEGATIVE.iter().any(|n| names.contains(n)) {
+        weight -= 25.0;
+    }
+    if POSITIVE.iter().any(|p| names.contains(p)) {
+        weight += 25.0;
+    }
+    weight
+}
+
+fn initial_score(node: &NodeRef) -> f64 {
+    let base = match tag_name(node).as_deref() {
+        Some("article") | Some("main") => 10.0,
+        Some("div") => 5.0,
+        Some("pre") | Some("td") | Some("blockquote") => 3.0,
+        Some("address") | Some("ol") | Some("ul") | Some("dl") | Some("dd") | Some("dt") | Some("li") => -3.0,
+        Some("h1") | Some("h2") | Some("h3") | Some("h4") | Some("h5") | Some("h6") | Some("th") => -5.0,
+        _ => 0.0,
+    };
+    base + class_weight(node)
+}
+
+/// The share of a node's text that is inside links.
+fn link_density(node: &NodeRef) -> f64 {
+    let text_length = normalize_whitespace(&node.text_contents()).chars().count();
+    if text_length == 0 {
+        return 0.0;
+    }
+    let link_length: usize = node.select("a").map(|links| {
+        links.map(|a| normalize_whitespace(&a.text_contents()).chars().count()).sum()
+    }).unwrap_or(0);
+    link_length as f64 / text_length as f64
+}
+
+fn top_candidate(document: &NodeRef) -> Option<NodeRef> {
+    let mut scores: HashMap<*const Node, (NodeRef, f64)> = HashMap::new();
+
+    for node in document.descendants() {
+        let tag = tag_name(&node);
+        if !tag.as_deref().map_or(false, |t| SCORE_TAGS.contains(&t)) {
+            continue;
+        }
+        let text = normalize_whitespace(&node.text_contents());
+        let length = text.chars().count();
+        if length < MIN_PARAGRAPH_LENGTH {
+            continue;
+        }
+
+        let score = 1.0 + text.matches(',').count() as f64 + (length as f64 / 100.0).min(3.0);
+        for (level, ancestor) in node.ancestors().take(3).enumerate() {
+            if ancestor.as_element().is_none() || tag_name(&ancestor).as_deref() == Some("html") {
+                break;
+            }
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                _ => 3.0 * level as f64,
+            };
+            let entry = scores.entry(Rc::as_ptr(&ancestor.0))
+                .or_insert_with(|| (ancestor.clone(), initial_score(&ancestor)));
+            entry.1 += score / divider;
+        }
+    }
+
+    scores.into_values()
+        .map(|(node, score)| {
+            let scaled = score * (1.0 - link_density(&node));
+            (node, scaled)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(node, _)| node)
+}
+
+/// Take the candidate, plus any of its siblings that look like part of the same content.
+fn collect_content(candidate: &NodeRef) -> NodeRef {
+    let parent = match candidate.parent() {
+        Some(parent) if tag_name(&parent).as_deref() != Some("html") => parent,
+        _ => return candidate.clone(),
+    };
+
+    let candidate_names = class_and_id(candidate);
+    let siblings: Vec<NodeRef> = parent.children().filter(|c| c.as_element().is_some()).collect();
+    let wrapper = new_div();
+
+    for sibling in siblings {
+        let include = if sibling == *candidate {
+            true
+        } else if !candidate_names.trim().is_empty() && class_and_id(&sibling) == candidate_names {
+            true
+        } else if tag_name(&sibling).as_deref() == Some("p") {
+            let text = normalize_whitespace(&sibling.text_contents());
+            let density = link_density(&sibling);
+            (text.chars().count() > 80 && density < 0.25)
+                || (density == 0.0 && text.ends_with('.') && text.chars().count() > 0)
+        } else {
+            false
+        };
+        if include {
+            wrapper.append(sibling);
+        }
+    }
+
+    wrapper
+}
+
+fn new_div() -> NodeRef {
+    let fragment = kuchiki::parse_html().one("<div></div>");
+    let div = fragment.select_first("div").expect("a div").as_node().clone();
+    div.detach();
+    div
+}
+
+/// Remove what's left of the boilerplate inside the content, and presentational attributes.
+fn clean_content(content: &NodeRef) {
+    let mut to_remove: Vec<NodeRef> = Vec::new();
+    for node in content.descendants() {
+        let Some(tag) = tag_name(&node) else {
+            continue;
+        };
+        let text = normalize_whitespace(&node.text_contents());
+        let has_media = node.select_first("img, video, picture").is_ok();
+        match tag.as_str() {
+            "p" if text.is_empty() && !has_media => to_remove.push(node.clone()),
+            "div" | "section" | "ul" | "ol" | "table" if !has_media && link_density(&node) > 0.5 => {
+                to_remove.push(node.clone())
+            }
+            _ => {}
+        }
+    }
+    for node in to_remove {
+        node.detach();
+    }
+
+    for node in content.inclusive_descendants() {
+        if let Some(element) = node.as_element() {
+            let mut attributes = element.attributes.borrow_mut();
+            let names: Vec<String> = attributes.map.keys().map(|name| name.local.to_string()).collect();
+            for name in names {
+                if name == "style" || name == "class" || name == "id" || name.starts_with("on") {
+                    attributes.remove(name.as_str());
+                }
+            }
+        }
+    }
+}
+
+/// Convert a node to plain text, separating block elements with blank lines.
+pub(crate) fn block_text(node: &NodeRef) -> String {
+    let mut output = String::new();
+    append_text(node, &mut output);
+    output.lines()
+        .map(normalize_whitespace)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn append_text(node: &NodeRef, output: &mut String) {
+    for child in node.children() {
+        match child.data() {
+            NodeData::Text(text) => output.push_str(&text.borrow()),
+            NodeData::Element(element) => {
+                let tag = element.name.local.to_string();
+                if tag == "br" {
+                    output.push('\n');
+                    continue;
+                }
+                let block = BLOCK_TAGS.contains(&tag.as_str());
+                if block {
+                    output.push('\n');
+                }
+                append_text(&child, output);
+                if block {
+                    output.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractor::tests::fixture;
+
+    use super::*;
+
+    #[test]
+    fn test_extract_article_blog_post() {
+        let url = Url::parse("https://notes.example.com/posts/garden").unwrap();
+        let article = extract_article(&fixture("blog-post"), Some(&url));
+        assert_eq!(article.title.as_deref(), Some("Why I Keep a Digital Garden"));
+        assert_eq!(article.byline.as_deref(), Some("Jane Doe"));
+        assert_eq!(article.published.map(|d| d.to_rfc3339()).as_deref(), Some("2023-06-14T09:30:00+00:00"));
+        assert_eq!(article.lead_image.as_deref(), Some("https://notes.example.com/images/garden.jpg"));
+        assert!(article.text.contains("A digital garden is a collection of notes"));
+        assert!(article.text.contains("hello, garden"));
+        assert!(!article.text.contains("cookies"));
+        assert!(!article.text.contains("Recent posts"));
+        assert!(!article.text.contains("All rights reserved"));
+        assert!(!article.text.contains("dataLayer"));
+        assert!(!article.content_html.contains("class="));
+    }
+
+    #[test]
+    fn test_extract_article_news() {
+        let url = Url::parse("https://daily.example.com/2023/07/bike-lanes").unwrap();
+        let article = extract_article(&fixture("news-article"), Some(&url));
+        assert_eq!(article.title.as_deref(), Some("City council approves new bike lanes"));
+        assert_eq!(article.byline.as_deref(), Some("Sam Reporter"));
+        assert_eq!(article.published.map(|d| d.date_naive().to_string()).as_deref(), Some("2023-07-01"));
+        assert_eq!(article.lead_image.as_deref(), Some("https://daily.example.com/photos/bike-lane.jpg"));
+        assert!(article.text.contains("voted 7-2"));
+        assert!(article.text.contains("on-street parking"));
+        assert!(!article.text.contains("Share on Twitter"));
+        assert!(!article.text.contains("Council debates parking rules"));
+        assert!(!article.text.contains("Subscribe for unlimited access"));
+        assert!(!article.text.contains("Weather"));
+    }
+
+    #[test]
+    fn test_extract_article_without_markup() {
+        let article = extract_article(&fixture("no-article"), None);
+        assert_eq!(article.title.as_deref(), Some("Example"));
+        assert_eq!(article.byline, None);
+        assert!(article.text.contains("This page has no semantic markup"));
+        assert!(!article.text.contains("tracking"));
+    }
+
+    #[test]
+    fn test_extract_article_links() {
+        let url = Url::parse("https://daily.example.com/2023/07/bike-lanes").unwrap();
+        let article = extract_article(&fixture("news-article"), Some(&url));
+        // share and related links are boilerplate, not citations
+        assert!(article.links.is_empty());
+
+        let html = r##"<html><body><article>
+<p>As <a href="https://other.example.org/post?utm_medium=rss">another blog</a> noted, and we covered <a href="/2023/06/parking#update">before</a>, the plan has a long history of debate in the city council.</p>
+<p>See <a href="https://other.example.org/post">the original</a> or <a href="#comments">comments</a> for a detailed breakdown of every vote that was cast.</p>
+</article></body></html>"##;
+        let article = extract_article(html, Some(&url));
+        assert_eq!(article.links, vec!["https://other.example.org/post", "https://daily.example.com/2023/06/parking"]);
+    }
+
+    #[test]
+    fn test_extract_article_with_rules() {
+        let html = r#"<html><head><title>Markets rally as rates hold steady at the central bank</title></head><body>
+<div class="story-body">
+<span class="writer">Alex Analyst</span><span class="stamp">2023-08-02</span>
+<p>Stocks rose on Wednesday after the central bank left its benchmark rate unchanged for a second month.</p>
+<div class="inline-promo">Sign up for our markets newsletter</div>
+<p>Analysts said the decision had been widely expected, but the tone of the statement surprised some investors.</p>
+</div>
+<div class="recirc"><p>Most read: a very long list of unrelated stories that would otherwise outscore the article body text.</p></div>
+</body></html>"#;
+        let rules = SiteRules {
+            content_selector: Some(".story-body".to_string()),
+            remove_selectors: vec![".inline-promo".to_string()],
+            date_selector: Some(".stamp".to_string()),
+            author_selector: Some(".writer".to_string()),
+        };
+        assert_eq!(rules.validate(), Ok(()));
+
+        let article = extract_article_with_rules(html, None, Some(&rules));
+        assert_eq!(article.byline.as_deref(), Some("Alex Analyst"));
+        assert_eq!(article.published.map(|d| d.date_naive().to_string()).as_deref(), Some("2023-08-02"));
+        assert!(article.text.contains("left its benchmark rate unchanged"));
+        assert!(!article.text.contains("newsletter"));
+        assert!(!article.text.contains("Most read"));
+
+        let invalid = SiteRules { remove_selectors: vec!["div[".to_string()], ..Default::default() };
+        assert_eq!(invalid.validate(), Err("div[".to_string()));
+    }
+
+    #[test]
+    fn test_lead_image() {
+        let url = Url::parse("https://daily.example.com/2023/07/bike-lanes").unwrap();
+        let html = r#"<html><body><article>
+<p><img src="/icons/share.png" width="16" height="16"> The council met on Tuesday to discuss the long awaited plan for the city's bike lanes.</p>
+<p><img src="/photos/small.jpg" width="300" height="200"> Residents spoke for and against the plan during the public comment period, which ran late.</p>
+<p><img src="/photos/large.jpg" width="1200" height="800"> The final vote was seven to two in favor, and construction is expected to start next spring.</p>
+</article></body></html>"#;
+        let article = extract_article(html, Some(&url));
+        assert_eq!(article.lead_image.as_deref(), Some("https://daily.example.com/photos/large.jpg"));
+
+        let json_ld = html.replace("<html><body>", r#"<html><head><script type="application/ld+json">{"@type": "NewsArticle", "image": {"url": "https://cdn.example.com/lead.jpg"}}</script></head><body>"#);
+        let article = extract_article(&json_ld, Some(&url));
+        assert_eq!(article.lead_image.as_deref(), Some("https://cdn.example.com/lead.jpg"));
+    }
+}
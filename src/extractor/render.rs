@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag};
+use syntect::html::{ClassedHTMLGenerator, ClassStyle};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::extractor::wiki::{replace_wiki_links, WikiLink};
+
+/// Prefix of the CSS classes of highlighted code, e.g. `hl-keyword`.
+const HIGHLIGHT_CLASS_PREFIX: &str = "hl-";
+
+/// A heading of rendered Markdown, for a table of contents.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct TocEntry {
+    pub level: u32,
+    /// The `id` of the heading element, to link to as `#id`.
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct Rendered {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+}
+
+/// Render CommonMark with GFM tables, task lists, strikethrough and footnotes to sanitized HTML.
+///
+/// Wiki-links become links to `page_url(link)`, or `<span class="wiki-link missing">` for pages
+/// that don't exist. Headings get an `id` and an anchor link to it, and fenced code blocks with a
+/// known language are highlighted with `hl-` classes, leaving the colors to the stylesheet.
+pub(crate) fn render_markdown(markdown: &str, page_url: impl Fn(&WikiLink) -> Option<String>) -> Rendered {
+    let markdown = replace_wiki_links(markdown, |link, label, _| match page_url(link) {
+        Some(url) => format!("<a class=\"wiki-link\" href=\"{}\">{}</a>",
+                             html_escape(&url), html_escape(label)),
+        None => format!("<span class=\"wiki-link missing\">{}</span>", html_escape(label)),
+    });
+
+    let options = Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH;
+    let events: Vec<Event> = Parser::new_ext(&markdown, options).collect();
+
+    let mut toc = Vec::new();
+    let mut ids = HashSet::new();
+    let mut output: Vec<Event> = Vec::with_capacity(events.len());
+    let mut i = 0;
+    while i < events.len() {
+        match &events[i] {
+            Event::Start(Tag::Heading(level, _, _)) => {
+                let end = end_of(&events, i);
+                let title = events[i + 1..end].iter()
+                    .filter_map(|event| match event {
+                        Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect::<String>();
+                let id = unique_id(&title, &mut ids);
+                let level = *level as u32;
+                output.push(Event::Html(format!("<h{} id=\"{}\">", level, id).into()));
+                output.extend(events[i + 1..end].iter().cloned());
+                output.push(Event::Html(format!("<a class=\"anchor\" href=\"#{}\">#</a></h{}>\n", id, level).into()));
+                toc.push(TocEntry { level, id, title: title.trim().to_string() });
+                i = end + 1;
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let end = end_of(&events, i);
+                let code = events[i + 1..end].iter()
+                    .filter_map(|event| match event {
+                        Event::Text(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect::<String>();
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().map(str::to_string),
+                    CodeBlockKind::Indented => None,
+                };
+                output.push(Event::Html(highlight(&code, language.as_deref()).into()));
+                i = end + 1;
+            }
+            event => {
+                output.push(event.clone());
+                i += 1;
+            }
+        }
+    }
+
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, output.into_iter());
+    Rendered {
+        html: sanitize(&unsafe_html),
+        toc,
+    }
+}
+
+/// The index of the event that ends the element started at `start`.
+fn end_of(events: &[Event], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, event) in events.iter().enumerate().skip(start) {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    events.len() - 1
+}
+
+/// A slug of a heading, numbered if an earlier heading has the same one.
+fn unique_id(title: &str, ids: &mut HashSet<String>) -> String {
+    let base = Some(slug::slugify(title)).filter(|slug| !slug.is_empty())
+        .unwrap_or_else(|| "section".to_string());
+    let mut id = base.clone();
+    let mut n = 1;
+    while !ids.insert(id.clone()) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    id
+}
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// A code block, highlighted if its language is known.
+fn highlight(code: &str, language: Option<&str>) -> String {
+    let syntaxes = syntaxes();
+    let syntax = language.and_then(|language| syntaxes.find_syntax_by_token(language));
+    let highlighted = syntax.and_then(|syntax| {
+        let mut generator = ClassedHTMLGenerator::new_with_class_style(
+            syntax, syntaxes, ClassStyle::SpacedPrefixed { prefix: HIGHLIGHT_CLASS_PREFIX });
+        for line in LinesWithEndings::from(code) {
+            generator.parse_html_for_line_which_includes_newline(line).ok()?;
+        }
+        Some(generator.finalize())
+    });
+    let class = language.map(|language| format!(" class=\"language-{}\"", html_escape(language)))
+        .unwrap_or_default();
+    format!("<pre><code{}>{}</code></pre>\n", class, highlighted.unwrap_or_else(|| html_escape(code)))
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Remove anything unsafe from rendered Markdown, which may contain raw HTML, while keeping what
+/// the renderer adds: heading ids, classes for wiki-links and highlighting, task list checkboxes
+/// and footnote references.
+fn sanitize(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_generic_attributes(["id", "class"])
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .link_rel(Some("noopener noreferrer"))
+        .clean(html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_markdown() {
+        let content = "# Bike lanes\n\nSee [[City Council|the council]] and [[Missing Page]].\n\n\
+- [x] survey streets\n- [ ] paint lanes\n\n\
+| Street | Lanes |\n|---|---|\n| Main | 2 |\n\n\
+Funded by the city.[^1]\n\n[^1]: Budget of 2023.\n\n\
+## Bike lanes\n\n```rust\nfn main() {}\n```\n\n<script>alert(1)</script><p onclick=\"steal()\">Hi</p>\n";
+        let rendered = render_markdown(content, |link| {
+            (link.slug() == "city-council").then(|| "/pages/city-council".to_string())
+        });
+
+        assert_eq!(rendered.toc, vec![
+            TocEntry { level: 1, id: "bike-lanes".to_string(), title: "Bike lanes".to_string() },
+            TocEntry { level: 2, id: "bike-lanes-1".to_string(), title: "Bike lanes".to_string() },
+        ]);
+        let html = &rendered.html;
+        assert!(html.contains(r#"<h1 id="bike-lanes">"#));
+        assert!(html.contains(r##"href="#bike-lanes""##));
+        assert!(html.contains(r#"href="/pages/city-council""#));
+        assert!(html.contains(">the council</a>"));
+        assert!(html.contains(r#"<span class="wiki-link missing">Missing Page</span>"#));
+        assert!(html.contains(r#"type="checkbox""#));
+        assert!(html.contains("<table>"));
+        assert!(html.contains("footnote-definition"));
+        assert!(html.contains(r#"class="language-rust""#));
+        assert!(html.contains("hl-"));
+        assert!(!html.contains("script"));
+        assert!(!html.contains("onclick"));
+    }
+}
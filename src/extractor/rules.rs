@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use kuchiki::NodeRef;
+use kuchiki::Selectors;
+
+use crate::extractor::readability::parse_date;
+
+/// Site specific CSS selectors, for sites the generic heuristics get wrong.
+///
+/// Every selector is optional, anything a rule leaves out is found the usual way.
+#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+pub(crate) struct SiteRules {
+    /// The element(s) holding the article, used instead of scoring candidates.
+    pub content_selector: Option<String>,
+    /// Elements removed before anything else is extracted, e.g. inline ads or newsletter boxes.
+    #[serde(default)]
+    pub remove_selectors: Vec<String>,
+    /// The element holding the publication date, read from its `datetime` or `content`
+    /// attribute, or its text.
+    pub date_selector: Option<String>,
+    /// The element holding the author's name.
+    pub author_selector: Option<String>,
+}
+
+impl SiteRules {
+    /// Check that every selector compiles, returning the first one that doesn't.
+    pub fn validate(&self) -> Result<(), String> {
+        self.content_selector.iter()
+            .chain(self.remove_selectors.iter())
+            .chain(self.date_selector.iter())
+            .chain(self.author_selector.iter())
+            .try_for_each(|selector| match Selectors::compile(selector) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(selector.clone()),
+            })
+    }
+
+    pub(crate) fn remove(&self, document: &NodeRef) {
+        for selector in &self.remove_selectors {
+            for node in select_all(document, selector) {
+                node.detach();
+            }
+        }
+    }
+
+    pub(crate) fn content(&self, document: &NodeRef) -> Vec<NodeRef> {
+        self.content_selector.as_deref()
+            .map(|selector| select_all(document, selector))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn author(&self, document: &NodeRef) -> Option<String> {
+        let selector = self.author_selector.as_deref()?;
+        select_all(document, selector).iter()
+            .map(|node| node.text_contents().split_whitespace().collect::<Vec<_>>().join(" "))
+            .map(|author| author.trim_start_matches("By ").trim_start_matches("by ").to_string())
+            .find(|author| !author.is_empty())
+    }
+
+    pub(crate) fn published(&self, document: &NodeRef) -> Option<DateTime<Utc>> {
+        let selector = self.date_selector.as_deref()?;
+        select_all(document, selector).iter().find_map(|node| {
+            let attribute = node.as_element().and_then(|element| {
+                let attributes = element.attributes.borrow();
+                attributes.get("datetime").or(attributes.get("content")).map(|value| value.to_string())
+            });
+            attribute.and_then(|value| parse_date(&value))
+                .or_else(|| parse_date(&node.text_contents()))
+        })
+    }
+}
+
+/// Every node matching `selector`, or none if it doesn't compile.
+fn select_all(document: &NodeRef, selector: &str) -> Vec<NodeRef> {
+    match document.select(selector) {
+        Ok(nodes) => nodes.map(|node| node.as_node().clone()).collect(),
+        Err(_) => vec![],
+    }
+}
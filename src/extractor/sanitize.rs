@@ -0,0 +1,93 @@
+use ammonia::UrlRelative;
+use kuchiki::NodeRef;
+use kuchiki::traits::*;
+use url::Url;
+
+use crate::extractor::links::strip_tracking_params;
+
+/// Image URLs that identify open tracking pixels.
+const TRACKING_MARKERS: [&str; 5] = ["track", "pixel", "beacon", "/open", "/o.gif"];
+
+/// Whether an image is a tracking pixel: tiny, hidden, or served from a tracking URL.
+pub(crate) fn is_tracking_pixel(attributes: &kuchiki::Attributes) -> bool {
+    let tiny = |name: &str| attributes.get(name)
+        .map(|value| value.trim_end_matches("px").trim().parse::<u32>().map_or(false, |v| v <= 1))
+        .unwrap_or(false);
+    let style = attributes.get("style").unwrap_or_default().replace(' ', "").to_lowercase();
+    let src = attributes.get("src").unwrap_or_default().to_lowercase();
+
+    tiny("width") || tiny("height")
+        || style.contains("display:none")
+        || style.contains("width:1px")
+        || style.contains("height:1px")
+        || TRACKING_MARKERS.iter().any(|m| src.contains(m))
+}
+
+/// Make HTML safe to show inside our own pages.
+///
+/// Scripts, styles, iframes, forms, event handlers and `javascript:` URLs are removed, along
+/// with tracking pixels and tracking parameters on links. Relative URLs are made absolute
+/// against `base`, or removed without one, and links open without a referrer.
+pub(crate) fn sanitize_html(html: &str, base: Option<&Url>) -> String {
+    let document = kuchiki::parse_html().one(html);
+
+    let pixels: Vec<NodeRef> = document.select("img").into_iter().flatten()
+        .filter(|image| is_tracking_pixel(&image.attributes.borrow()))
+        .map(|image| image.as_node().clone())
+        .collect();
+    for pixel in pixels {
+        pixel.detach();
+    }
+    for link in document.select("a[href]").into_iter().flatten() {
+        let mut attributes = link.attributes.borrow_mut();
+        let href = attributes.get("href").unwrap_or_default().trim().to_string();
+        let resolved = match base {
+            Some(base) => base.join(&href).ok(),
+            None => Url::parse(&href).ok(),
+        };
+        if let Some(mut url) = resolved.filter(|url| url.scheme() == "http" || url.scheme() == "https") {
+            strip_tracking_params(&mut url);
+            attributes.insert("href", url.to_string());
+        }
+    }
+
+    let body = document.select_first("body").map(|body| body.as_node().clone()).unwrap_or(document);
+    let mut cleaned = Vec::new();
+    for child in body.children() {
+        let _ = child.serialize(&mut cleaned);
+    }
+
+    let url_relative = match base {
+        Some(base) => UrlRelative::RewriteWithBase(base.clone()),
+        None => UrlRelative::Deny,
+    };
+    ammonia::Builder::default()
+        .url_relative(url_relative)
+        .link_rel(Some("noopener noreferrer"))
+        .clean(&String::from_utf8_lossy(&cleaned))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_html() {
+        let url = Url::parse("https://daily.example.com/2023/07/bike-lanes").unwrap();
+        let html = r#"<p onclick="steal()">The council <a href="/votes?utm_source=feed&id=7#result">voted</a>.</p>
+<script>alert(1)</script><iframe src="https://ads.example.com/"></iframe>
+<img src="/photos/lanes.jpg" alt="Lanes"><img src="https://mail.example.com/track/open.gif" width="1" height="1">
+<a href="javascript:alert(1)">click</a>"#;
+        let sanitized = sanitize_html(html, Some(&url));
+
+        assert!(!sanitized.contains("onclick"));
+        assert!(!sanitized.contains("script"));
+        assert!(!sanitized.contains("iframe"));
+        assert!(!sanitized.contains("javascript:"));
+        assert!(!sanitized.contains("track/open.gif"));
+        assert!(sanitized.contains(r#"href="https://daily.example.com/votes?id=7#result""#));
+        assert!(sanitized.contains(r#"rel="noopener noreferrer""#));
+        assert!(sanitized.contains(r#"src="https://daily.example.com/photos/lanes.jpg""#));
+    }
+}
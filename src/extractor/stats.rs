@@ -0,0 +1,123 @@
+/// Words read per minute, for the reading time estimate.
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Detected languages below this confidence are left unset.
+const MIN_LANGUAGE_CONFIDENCE: f64 = 0.5;
+
+/// Language and readability statistics of an extracted text.
+#[derive(Debug, Clone, PartialEq, Default, serde::Serialize)]
+pub(crate) struct TextStats {
+    /// ISO 639-3 code of the detected language, e.g. `eng` or `deu`.
+    pub language: Option<String>,
+    pub word_count: i32,
+    /// Estimated reading time in minutes.
+    pub reading_time: i32,
+    /// Flesch reading ease, higher is easier, most text falls between 0 and 100.
+    pub flesch_reading_ease: Option<f64>,
+    /// Flesch-Kincaid grade level, the US school grade needed to understand the text.
+    pub flesch_kincaid_grade: Option<f64>,
+}
+
+/// Compute the language, length and readability of a text.
+///
+/// The readability formulas were made for English, for other languages they're only useful
+/// to compare texts of the same language.
+pub(crate) fn text_stats(text: &str) -> TextStats {
+    let words: Vec<&str> = text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .collect();
+    let word_count = words.len();
+    if word_count == 0 {
+        return TextStats::default();
+    }
+
+    let language = whatlang::detect(text)
+        .filter(|info| info.confidence() >= MIN_LANGUAGE_CONFIDENCE)
+        .map(|info| info.lang().code().to_string());
+
+    let sentences = sentence_count(text).max(1) as f64;
+    let syllables: usize = words.iter().map(|word| syllable_count(word)).sum();
+    let words_per_sentence = word_count as f64 / sentences;
+    let syllables_per_word = syllables as f64 / word_count as f64;
+
+    TextStats {
+        language,
+        word_count: word_count as i32,
+        reading_time: ((word_count + WORDS_PER_MINUTE - 1) / WORDS_PER_MINUTE) as i32,
+        flesch_reading_ease: Some(round(206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word)),
+        flesch_kincaid_grade: Some(round(0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59)),
+    }
+}
+
+/// Sentences end with terminal punctuation followed by whitespace, or the end of the text.
+fn sentence_count(text: &str) -> usize {
+    let mut count = 0;
+    let mut chars = text.chars().peekable();
+    let mut in_sentence = false;
+    while let Some(c) = chars.next() {
+        if matches!(c, '.' | '!' | '?' | '。' | '！' | '？') {
+            if in_sentence && chars.peek().map_or(true, |next| next.is_whitespace()) {
+                count += 1;
+                in_sentence = false;
+            }
+        } else if c.is_alphanumeric() {
+            in_sentence = true;
+        }
+    }
+    if in_sentence {
+        count += 1;
+    }
+    count
+}
+
+/// Approximate syllables as groups of vowels, ignoring a silent trailing `e`.
+fn syllable_count(word: &str) -> usize {
+    let word: Vec<char> = word.chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if word.is_empty() {
+        return 0;
+    }
+
+    let is_vowel = |c: &char| "aeiouyàáâäæèéêëìíîïòóôöøùúûüÿ".contains(*c);
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in &word {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    if count > 1 && word.ends_with(&['e']) && !word.ends_with(&['l', 'e']) {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractor::extract_article;
+    use crate::extractor::tests::fixture;
+
+    use super::*;
+
+    #[test]
+    fn test_text_stats() {
+        let article = extract_article(&fixture("news-article"), None);
+        let stats = text_stats(&article.text);
+        assert_eq!(stats.language.as_deref(), Some("eng"));
+        assert!(stats.word_count > 60 && stats.word_count < 100);
+        assert_eq!(stats.reading_time, 1);
+        assert!(stats.flesch_reading_ease.unwrap() > 0.0);
+
+        let german = text_stats("Der Stadtrat hat am Dienstag mit großer Mehrheit beschlossen, neue geschützte Radwege zu bauen.");
+        assert_eq!(german.language.as_deref(), Some("deu"));
+        assert_eq!(text_stats("  "), TextStats::default());
+    }
+}
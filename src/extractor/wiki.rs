@@ -0,0 +1,211 @@
+/// A `[[target]]` or `[[target|label]]` link in a page's Markdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WikiLink {
+    /// The page title or slug as written, without any `#heading` part.
+    pub target: String,
+    pub label: Option<String>,
+}
+
+impl WikiLink {
+    /// The slug pages are matched on, so `[[Bike Lanes]]` and `[[bike-lanes]]` are one link.
+    pub fn slug(&self) -> String {
+        slug::slugify(&self.target)
+    }
+}
+
+/// The slug of the page a wiki-link points at among `pages`, pairs of slug and title. Links
+/// match on slug first and title second, the same way `db::save_page_links` does.
+pub(crate) fn resolve_wiki_link(pages: &[(String, String)], link: &WikiLink) -> Option<String> {
+    let slug = link.slug();
+    pages.iter().find(|(page_slug, _)| *page_slug == slug)
+        .or_else(|| pages.iter().find(|(_, title)| title.to_lowercase() == link.target.to_lowercase()))
+        .map(|(page_slug, _)| page_slug.clone())
+}
+
+/// The distinct wiki-links of a page, in order of appearance, outside code blocks and code spans.
+///
+/// Embeds (`![[target]]`) count as links too.
+pub(crate) fn wiki_links(markdown: &str) -> Vec<WikiLink> {
+    let mut links: Vec<WikiLink> = Vec::new();
+    for text in prose(markdown) {
+        for link in text_links(text) {
+            if !links.iter().any(|other| other.slug() == link.slug()) {
+                links.push(link);
+            }
+        }
+    }
+    links
+}
+
+/// The distinct `#hashtags` of a page, lower cased, in order of appearance, outside code blocks
+/// and code spans.
+///
+/// A tag starts with a letter after whitespace or the start of a line, and goes on with letters,
+/// digits, `_`, `-` and `/`, so `#1`, `# Heading` and the fragment of `page#section` aren't tags.
+pub(crate) fn hashtags(markdown: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for text in prose(markdown) {
+        let mut previous: Option<char> = None;
+        for (i, c) in text.char_indices() {
+            let starts_tag = c == '#' && previous.map_or(true, char::is_whitespace);
+            previous = Some(c);
+            if !starts_tag {
+                continue;
+            }
+            let rest = &text[i + 1..];
+            if !rest.chars().next().map_or(false, char::is_alphabetic) {
+                continue;
+            }
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '/'))
+                .unwrap_or(rest.len());
+            let tag = rest[..end].trim_end_matches(['-', '/']).to_lowercase();
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+/// Replace every wiki-link outside code blocks and code spans with `replace(link, label, embed)`,
+/// where `label` is the label or else the target. Embeds are replaced like links, including their
+/// `!`, with `embed` set.
+pub(crate) fn replace_wiki_links(markdown: &str, mut replace: impl FnMut(&WikiLink, &str, bool) -> String) -> String {
+    let mut replaced = String::with_capacity(markdown.len());
+    for (text, code) in segments(markdown) {
+        if code {
+            replaced.push_str(text);
+            continue;
+        }
+        let mut rest = text;
+        while let Some(open) = rest.find("[[") {
+            let inner = &rest[open + 2..];
+            let close = match inner.find("]]") {
+                Some(close) => close,
+                None => break,
+            };
+            match parse_link(&inner[..close]) {
+                Some(link) => {
+                    let embed = rest[..open].ends_with('!');
+                    let start = if embed { open - 1 } else { open };
+                    replaced.push_str(&rest[..start]);
+                    let label = link.label.clone().unwrap_or_else(|| link.target.clone());
+                    replaced.push_str(&replace(&link, &label, embed));
+                }
+                None => replaced.push_str(&rest[..open + 2 + close + 2]),
+            }
+            rest = &inner[close + 2..];
+        }
+        replaced.push_str(rest);
+    }
+    replaced
+}
+
+/// The parts of each line outside fenced code blocks and inline code spans.
+fn prose(markdown: &str) -> Vec<&str> {
+    segments(markdown).into_iter()
+        .filter(|(text, code)| !code && !text.is_empty())
+        .map(|(text, _)| text)
+        .collect()
+}
+
+/// The whole of `markdown`, split into code blocks and code spans, flagged `true`, and the text
+/// between them. Concatenating the parts gives back `markdown`.
+fn segments(markdown: &str) -> Vec<(&str, bool)> {
+    let mut parts = Vec::new();
+    let mut fence: Option<&str> = None;
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            parts.push((line, true));
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            parts.push((line, true));
+            continue;
+        }
+
+        let mut rest = line;
+        while let Some(start) = rest.find('`') {
+            match rest[start + 1..].find('`') {
+                Some(end) => {
+                    parts.push((&rest[..start], false));
+                    parts.push((&rest[start..start + end + 2], true));
+                    rest = &rest[start + end + 2..];
+                }
+                // an unclosed backtick is just a backtick
+                None => break,
+            }
+        }
+        parts.push((rest, false));
+    }
+    parts
+}
+
+fn text_links(text: &str) -> Vec<WikiLink> {
+    let mut links = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find("[[") {
+        let inner = &rest[open + 2..];
+        let close = match inner.find("]]") {
+            Some(close) => close,
+            None => break,
+        };
+        if let Some(link) = parse_link(&inner[..close]) {
+            links.push(link);
+        }
+        rest = &inner[close + 2..];
+    }
+    links
+}
+
+fn parse_link(inner: &str) -> Option<WikiLink> {
+    if inner.contains('[') {
+        return None;
+    }
+    let (target, label) = match inner.split_once('|') {
+        Some((target, label)) => (target, Some(label.trim()).filter(|label| !label.is_empty())),
+        None => (inner, None),
+    };
+    let target = target.split(['#', '^']).next().unwrap_or_default().trim();
+    if target.is_empty() || slug::slugify(target).is_empty() {
+        return None;
+    }
+    Some(WikiLink {
+        target: target.to_string(),
+        label: label.map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wiki_links() {
+        let content = "See [[Bike Lanes]] and [[city-council|the council]], or [[bike-lanes#History]] again.\n\
+`[[Not A Link]]` is code, ![[Lane Map]] is an embed and [[]] is empty.\n\
+```\n[[Fenced]]\n```\n";
+        let links = wiki_links(content);
+
+        assert_eq!(links, vec![
+            WikiLink { target: "Bike Lanes".to_string(), label: None },
+            WikiLink { target: "city-council".to_string(), label: Some("the council".to_string()) },
+            WikiLink { target: "Lane Map".to_string(), label: None },
+        ]);
+        assert_eq!(links[0].slug(), "bike-lanes");
+    }
+
+    #[test]
+    fn test_hashtags() {
+        let content = "# Bike lanes\n\nNotes on #Cycling and #urban-planning/streets, see issue #12.\n\
+Mail me at me@example.com#anchor or read `#not-a-tag` and [the plan](https://example.com/plan#cycling).\n\
+```\n#fenced\n```\n#cycling again";
+
+        assert_eq!(hashtags(content), vec!["cycling", "urban-planning/streets"]);
+    }
+}
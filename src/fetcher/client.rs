@@ -0,0 +1,403 @@
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use reqwest::header::{HeaderMap, CONTENT_TYPE, LOCATION, USER_AGENT};
+use reqwest::{redirect, Proxy};
+use url::Url;
+use webpage::{HTML, HTTP, Webpage};
+
+use crate::config::Config;
+use crate::fetcher::profile::{CredentialsError, CredentialsKey, FetchProfile};
+
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/114.0.0.0 Safari/537.36";
+
+/// Errors that can occur while fetching a remote resource.
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum FetchError {
+    #[error("invalid url: {0}")]
+    InvalidUrl(String),
+
+    #[error("error building http client: {0}")]
+    Client(reqwest::Error),
+
+    #[error("error fetching {url}: {source}")]
+    Request { url: String, source: reqwest::Error },
+
+    #[error("timed out reading body of {0}")]
+    ReadTimeout(String),
+
+    #[error("body of {url} exceeds the maximum of {max} bytes")]
+    BodyTooLarge { url: String, max: usize },
+
+    #[error("too many redirects fetching {0}")]
+    TooManyRedirects(String),
+
+    #[error("unexpected status {status} fetching {url}")]
+    Status { url: String, status: u16 },
+
+    #[error("error parsing html of {url}: {source}")]
+    Html { url: String, source: std::io::Error },
+
+    #[error(transparent)]
+    Credentials(#[from] CredentialsError),
+}
+
+/// Limits and network settings applied to every fetch.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FetchOptions {
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub max_body_size: usize,
+    pub max_redirects: u32,
+    pub proxy: Option<String>,
+    pub allow_insecure: bool,
+    pub user_agent: String,
+    /// Base64 encoded key used to decrypt fetch profiles.
+    pub credentials_key: Option<String>,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            max_body_size: 10 * 1024 * 1024, /* 10mb */
+            max_redirects: 10,
+            proxy: None,
+            allow_insecure: false,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            credentials_key: std::env::var("FETCH_CREDENTIALS_KEY").ok(),
+        }
+    }
+}
+
+impl From<&Config> for FetchOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(config.fetch_connect_timeout),
+            read_timeout: Duration::from_secs(config.fetch_read_timeout),
+            max_body_size: config.fetch_max_body_size,
+            max_redirects: config.fetch_max_redirects,
+            proxy: config.fetch_proxy.clone(),
+            allow_insecure: config.fetch_allow_insecure,
+            credentials_key: config.fetch_credentials_key.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The result of a successful fetch.
+#[derive(Debug, Clone)]
+pub(crate) struct FetchResponse {
+    /// The final URL after following redirects.
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub remote_addr: Option<SocketAddr>,
+    pub transfer_time: Duration,
+    pub redirect_count: u32,
+}
+
+impl FetchResponse {
+    /// The body decoded as UTF-8, replacing any invalid sequences.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Convert into a `Webpage` so the existing metadata extraction can be used on it.
+    pub fn into_webpage(self) -> Result<Webpage, FetchError> {
+        let body = self.text();
+        let html = HTML::from_string(body.clone(), Some(self.url.clone()))
+            .map_err(|source| FetchError::Html { url: self.url.clone(), source })?;
+        let headers = self.headers.iter()
+            .map(|(name, value)| format!("{}: {}", name, value.to_str().unwrap_or_default()))
+            .collect();
+        let http = HTTP {
+            ip: self.remote_addr.map(|addr| addr.ip().to_string()).unwrap_or_default(),
+            transfer_time: self.transfer_time,
+            redirect_count: self.redirect_count,
+            content_type: self.content_type.unwrap_or_default(),
+            response_code: self.status as u32,
+            headers,
+            url: self.url,
+            body,
+        };
+        Ok(Webpage { http, html })
+    }
+}
+
+/// An async HTTP client for fetching webpages and feeds without blocking the runtime.
+///
+/// Cheap to clone, the underlying connection pool is shared.
+#[derive(Debug, Clone)]
+pub(crate) struct FetchClient {
+    client: reqwest::Client,
+    options: FetchOptions,
+    credentials_key: Option<CredentialsKey>,
+}
+
+impl FetchClient {
+    pub fn new(options: FetchOptions) -> Result<Self, FetchError> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(options.connect_timeout)
+            // redirects are followed manually so they can be counted and limited
+            .redirect(redirect::Policy::none())
+            .danger_accept_invalid_certs(options.allow_insecure)
+            .gzip(true)
+            .brotli(true);
+
+        if let Some(proxy) = &options.proxy {
+            builder = builder.proxy(Proxy::all(proxy).map_err(FetchError::Client)?);
+        }
+
+        let credentials_key = options.credentials_key.as_deref()
+            .map(CredentialsKey::from_base64)
+            .transpose()?;
+
+        let client = builder.build().map_err(FetchError::Client)?;
+        Ok(Self { client, options, credentials_key })
+    }
+
+    /// The key for encrypting and decrypting fetch profiles.
+    pub fn credentials_key(&self) -> Result<&CredentialsKey, CredentialsError> {
+        self.credentials_key.as_ref().ok_or(CredentialsError::MissingKey)
+    }
+
+    /// Fetch `url` with a GET request, following redirects up to the configured limit.
+    pub async fn get(&self, url: &str) -> Result<FetchResponse, FetchError> {
+        self.get_with(url, None).await
+    }
+
+    /// Fetch `url` applying the credentials, cookies and headers of `profile`.
+    ///
+    /// The profile is only applied to requests for the origin it is bound to, so credentials
+    /// aren't leaked to other hosts, whether `url` or a redirect points there.
+    pub async fn get_with(&self, url: &str, profile: Option<&FetchProfile>) -> Result<FetchResponse, FetchError> {
+        let start = Instant::now();
+        let mut current = Url::parse(url).map_err(|_| FetchError::InvalidUrl(url.to_string()))?;
+        let profile = profile.filter(|profile| profile.applies_to(&current));
+        if let Some(profile) = profile {
+            profile.apply_to_url(&mut current);
+        }
+        let mut redirect_count = 0;
+        // errors are logged and returned to API clients, so they mustn't contain the profile's tokens
+        let redacted = |url: &Url| {
+            let mut url = url.clone();
+            if let Some(profile) = profile {
+                profile.strip_from_url(&mut url);
+            }
+            url.to_string()
+        };
+
+        let response = loop {
+            let mut request = self.client.get(current.clone())
+                .header(USER_AGENT, &self.options.user_agent);
+            if let Some(profile) = profile.filter(|profile| profile.applies_to(&current)) {
+                request = profile.apply_to_request(request);
+            }
+
+            let response = tokio::time::timeout(
+                self.options.read_timeout,
+                request.send(),
+            )
+                .await
+                .map_err(|_| FetchError::ReadTimeout(redacted(&current)))?
+                .map_err(|source| FetchError::Request { url: redacted(&current), source: source.without_url() })?;
+
+            if !response.status().is_redirection() {
+                break response;
+            }
+
+            if redirect_count >= self.options.max_redirects {
+                return Err(FetchError::TooManyRedirects(url.to_string()));
+            }
+
+            let location = response.headers().get(LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| FetchError::InvalidUrl(redacted(&current)))?;
+            current = current.join(location).map_err(|_| FetchError::InvalidUrl(location.to_string()))?;
+            redirect_count += 1;
+        };
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(FetchError::Status { url: redacted(&current), status: status.as_u16() });
+        }
+
+        let remote_addr = response.remote_addr();
+        let headers = response.headers().clone();
+        let content_type = headers.get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let url = redacted(&current);
+        let body = self.read_body(&url, response).await?;
+
+        Ok(FetchResponse {
+            url,
+            status: status.as_u16(),
+            content_type,
+            headers,
+            body,
+            remote_addr,
+            transfer_time: start.elapsed(),
+            redirect_count,
+        })
+    }
+
+    /// Fetch `url` applying `profile`, and convert the response into a `Webpage`.
+    pub async fn get_webpage_with(&self, url: &str, profile: Option<&FetchProfile>) -> Result<Webpage, FetchError> {
+        self.get_with(url, profile).await?.into_webpage()
+    }
+
+    /// Read the body of `response`, where `url` is the redacted URL to report in errors.
+    async fn read_body(&self, url: &str, mut response: reqwest::Response) -> Result<Bytes, FetchError> {
+        let max = self.options.max_body_size;
+        if response.content_length().map_or(false, |len| len as usize > max) {
+            return Err(FetchError::BodyTooLarge { url: url.to_string(), max });
+        }
+
+        let mut body = BytesMut::new();
+        loop {
+            let chunk = tokio::time::timeout(self.options.read_timeout, response.chunk())
+                .await
+                .map_err(|_| FetchError::ReadTimeout(url.to_string()))?
+                .map_err(|source| FetchError::Request { url: url.to_string(), source: source.without_url() })?;
+            match chunk {
+                Some(chunk) => {
+                    if body.len() + chunk.len() > max {
+                        return Err(FetchError::BodyTooLarge { url: url.to_string(), max });
+                    }
+                    body.extend_from_slice(&chunk);
+                }
+                None => break,
+            }
+        }
+
+        Ok(body.freeze())
+    }
+}
+
+/// A shared client using the default `FetchOptions`, for code paths without an `ApiContext`.
+pub(crate) fn default_client() -> &'static FetchClient {
+    static CLIENT: OnceLock<FetchClient> = OnceLock::new();
+    CLIENT.get_or_init(|| FetchClient::new(FetchOptions::default()).expect("Unable to build fetch client"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fetcher::profile::tests::token_profile;
+    use reqwest::header::AUTHORIZATION;
+
+    use crate::fetcher::profile::FetchAuth;
+
+    use super::*;
+
+    /// Serve `app` on a free local port, returning its base URL.
+    fn serve(app: axum::Router) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+        format!("http://{}", addr)
+    }
+
+    fn test_client(options: FetchOptions) -> FetchClient {
+        FetchClient::new(FetchOptions { credentials_key: None, ..options }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_errors_omit_profile_tokens() {
+        let app = axum::Router::new()
+            .route("/private", axum::routing::get(|| async { axum::http::StatusCode::FORBIDDEN }));
+        let base = serve(app);
+        let client = test_client(FetchOptions::default());
+        let profile = token_profile().bound_to(Url::parse(&base).unwrap().origin());
+
+        let result = client.get_with(&format!("{}/private", base), Some(&profile)).await;
+        assert!(matches!(result, Err(FetchError::Status { status: 403, .. })), "{:?}", result);
+        let error = result.err().unwrap();
+        assert!(!error.to_string().contains("s3cret"), "{}", error);
+        assert!(!format!("{:?}", error).contains("s3cret"), "{:?}", error);
+
+        // nothing listens on the port of a dropped listener, so the request itself fails
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = format!("http://127.0.0.1:{}/private", port);
+        let profile = profile.bound_to(Url::parse(&url).unwrap().origin());
+        let error = client.get_with(&url, Some(&profile)).await.err().unwrap();
+        assert!(matches!(error, FetchError::Request { .. }), "{:?}", error);
+        assert!(!error.to_string().contains("s3cret"), "{}", error);
+        assert!(!format!("{:?}", error).contains("s3cret"), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_profile_only_sent_to_its_origin() {
+        let echo = || axum::Router::new().route("/", axum::routing::get(|uri: axum::http::Uri, headers: axum::http::HeaderMap| async move {
+            let authorization = headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok()).unwrap_or_default().to_string();
+            format!("{} {}", uri.query().unwrap_or_default(), authorization)
+        }));
+        let feed_host = serve(echo());
+        let article_host = serve(echo());
+        let client = test_client(FetchOptions::default());
+        let profile = FetchProfile {
+            auth: Some(FetchAuth::Bearer { token: "t0ken".to_string() }),
+            ..token_profile()
+        }.bound_to(Url::parse(&feed_host).unwrap().origin());
+
+        let feed = client.get_with(&format!("{}/", feed_host), Some(&profile)).await.unwrap().text();
+        assert_eq!(feed, "token=s3cret Bearer t0ken");
+
+        let article = client.get_with(&format!("{}/", article_host), Some(&profile)).await.unwrap().text();
+        assert_eq!(article.trim(), "");
+
+        // a profile that wasn't loaded for any feed or source isn't applied at all
+        let unbound = client.get_with(&format!("{}/", feed_host), Some(&token_profile())).await.unwrap().text();
+        assert_eq!(unbound.trim(), "");
+    }
+
+    #[test]
+    fn test_fetch_options_verify_tls_by_default() {
+        assert!(!FetchOptions::default().allow_insecure);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_redirect_loop() {
+        let app = axum::Router::new()
+            .route("/loop", axum::routing::get(|| async { axum::response::Redirect::temporary("/loop") }));
+        let base = serve(app);
+        let client = test_client(FetchOptions { max_redirects: 3, ..Default::default() });
+
+        let result = client.get(&format!("{}/loop", base)).await;
+        assert!(matches!(result, Err(FetchError::TooManyRedirects(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_body_too_large() {
+        let app = axum::Router::new()
+            .route("/small", axum::routing::get(|| async { "a".repeat(1024) }))
+            .route("/large", axum::routing::get(|| async { "a".repeat(1025) }));
+        let base = serve(app);
+        let client = test_client(FetchOptions { max_body_size: 1024, ..Default::default() });
+
+        let response = client.get(&format!("{}/small", base)).await.unwrap();
+        assert_eq!(response.body.len(), 1024);
+        let result = client.get(&format!("{}/large", base)).await;
+        assert!(matches!(result, Err(FetchError::BodyTooLarge { max: 1024, .. })), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_read_timeout() {
+        let app = axum::Router::new()
+            .route("/slow", axum::routing::get(|| async {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                "too late"
+            }));
+        let base = serve(app);
+        let client = test_client(FetchOptions { read_timeout: std::time::Duration::from_millis(100), ..Default::default() });
+
+        let result = client.get(&format!("{}/slow", base)).await;
+        assert!(matches!(result, Err(FetchError::ReadTimeout(_))), "{:?}", result);
+    }
+}
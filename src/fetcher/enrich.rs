@@ -0,0 +1,135 @@
+use log::{error, info};
+use sqlx::{Pool, Postgres};
+use url::Url;
+
+use crate::artifacts::save_thumbnails;
+use crate::db;
+use crate::extractor::{canonical_url, chunk_text, extract_metadata, text_stats, ChunkOptions, Metadata};
+use crate::fetcher::FetchClient;
+use crate::models::Company;
+
+/// Fill in the author and description of a news item.
+pub(crate) async fn enrich_news(pool: &Pool<Postgres>, news_id: &uuid::Uuid, metadata: &Metadata) {
+    if metadata.author.is_none() && metadata.description.is_none() {
+        return;
+    }
+    if let Err(e) = db::update_news_metadata(pool, news_id, &metadata.author, &metadata.description).await {
+        error!("Error saving metadata for news item {}: {}", news_id, e);
+    }
+}
+
+/// Save the language and readability of the text extracted for a news item.
+pub(crate) async fn save_news_text_stats(pool: &Pool<Postgres>, news_id: &uuid::Uuid, text: &str) {
+    if let Err(e) = db::update_news_text_stats(pool, news_id, &text_stats(text)).await {
+        error!("Error saving text stats for news item {}: {}", news_id, e);
+    }
+}
+
+/// Split the text extracted for a news item into chunks, replacing any chunks of an earlier
+/// extraction.
+pub(crate) async fn chunk_news(pool: &Pool<Postgres>, news_id: &uuid::Uuid, text: &str) {
+    let chunks = chunk_text(text, &ChunkOptions::default());
+    if let Err(e) = db::save_news_chunks(pool, news_id, &chunks).await {
+        error!("Error saving chunks of news item {}: {}", news_id, e);
+    }
+}
+
+/// Save the outbound links of the news item at `url`, and resolve links to it saved earlier.
+pub(crate) async fn link_news(pool: &Pool<Postgres>, news_id: &uuid::Uuid, url: &str, links: &[String]) {
+    let links: Vec<(String, String)> = links.iter()
+        .filter_map(|link| {
+            let host = Url::parse(link).ok()?.host_str()?.trim_start_matches("www.").to_string();
+            Some((link.clone(), host))
+        })
+        .collect();
+    match db::save_news_links(pool, news_id, &links).await {
+        Ok(saved) => info!("Saved {} links of news item {}", saved, news_id),
+        Err(e) => error!("Error saving links of news item {}: {}", news_id, e),
+    }
+
+    if let Some(canonical) = canonical_url(url, None) {
+        if let Err(e) = db::resolve_news_links(pool, news_id, &canonical).await {
+            error!("Error resolving links to news at {}: {}", url, e);
+        }
+    }
+}
+
+/// Download an image and store its thumbnails, returning the URL of the thumbnail to show.
+pub(crate) async fn fetch_thumbnail(client: &FetchClient, image_url: &str) -> Option<String> {
+    let response = match client.get(image_url).await {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Error fetching image {}: {}", image_url, e);
+            return None;
+        }
+    };
+    let id = uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, image_url.as_bytes()).to_string();
+    // decoding and encoding images is CPU bound
+    let result = tokio::task::spawn_blocking(move || save_thumbnails(&id, &response.body)).await;
+    match result.map_err(anyhow::Error::from).and_then(|thumbnail| thumbnail) {
+        Ok(thumbnail_url) => Some(thumbnail_url),
+        Err(e) => {
+            error!("Error making thumbnails of {}: {}", image_url, e);
+            None
+        }
+    }
+}
+
+/// Store thumbnails of the lead image of a news item.
+pub(crate) async fn save_news_thumbnail(client: &FetchClient, pool: &Pool<Postgres>, news_id: &uuid::Uuid, image_url: Option<&str>) {
+    let Some(image_url) = image_url else {
+        return;
+    };
+    if let Some(thumbnail_url) = fetch_thumbnail(client, image_url).await {
+        if let Err(e) = db::update_news_thumbnail(pool, news_id, &thumbnail_url).await {
+            error!("Error saving thumbnail for news item {}: {}", news_id, e);
+        }
+    }
+}
+
+/// Store thumbnails of a source's preview image.
+pub(crate) async fn save_source_thumbnail(client: &FetchClient, pool: &Pool<Postgres>, source_id: &uuid::Uuid, image_url: Option<&str>) {
+    let Some(image_url) = image_url else {
+        return;
+    };
+    if let Some(thumbnail_url) = fetch_thumbnail(client, image_url).await {
+        if let Err(e) = db::update_source_thumbnail(pool, source_id, &thumbnail_url).await {
+            error!("Error saving thumbnail for source {}: {}", source_id, e);
+        }
+    }
+}
+
+/// Store thumbnails of an image in a page's content.
+pub(crate) async fn save_page_thumbnail(client: FetchClient, pool: Pool<Postgres>, page_id: uuid::Uuid, image_url: String) {
+    if let Some(thumbnail_url) = fetch_thumbnail(&client, &image_url).await {
+        if let Err(e) = db::update_page_thumbnail(&pool, &page_id, &thumbnail_url).await {
+            error!("Error saving thumbnail for page {}: {}", page_id, e);
+        }
+    }
+}
+
+/// Fetch a company's website and fill in the fields it declares about itself.
+///
+/// Only the JSON-LD `Organization` carries company details, the page's own description is
+/// used when it has none.
+pub(crate) async fn enrich_company(client: &FetchClient, pool: &Pool<Postgres>, company: &Company) -> anyhow::Result<Company> {
+    let url = company.url.as_deref().filter(|url| !url.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("company {} has no url", company.id))?;
+    let response = client.get(url).await?;
+    let metadata = extract_metadata(&response.text(), Url::parse(&response.url).ok().as_ref());
+
+    let mut organization = metadata.organization.clone().unwrap_or_default();
+    organization.description = organization.description.or(metadata.description);
+
+    let company = db::update_company_metadata(pool, &company.id, &organization).await?;
+    info!("Company enriched from {}: {}", url, company.id);
+    Ok(company)
+}
+
+/// Like `enrich_company`, logging rather than returning errors.
+pub(crate) async fn try_enrich_company(client: FetchClient, pool: Pool<Postgres>, company: Company) {
+    if let Err(e) = enrich_company(&client, &pool, &company).await {
+        error!("Error enriching company {}: {}", company.id, e);
+    }
+}
+
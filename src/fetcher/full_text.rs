@@ -0,0 +1,101 @@
+use atom_syndication::Entry;
+use log::{error, info};
+use rss::Item;
+use sqlx::{Pool, Postgres};
+
+use crate::db;
+use crate::extractor::{extract_document_async, extract_metadata, extract_text_from_str, DocumentKind};
+use crate::fetcher::{site_rules, FetchClient, FetchProfile};
+use crate::fetcher::enrich::{chunk_news, enrich_news, link_news, save_news_text_stats, save_news_thumbnail};
+
+/// Feed content with at least this much text is considered the full article, not a teaser.
+const FULL_CONTENT_MIN_CHARS: usize = 1500;
+
+/// Whether an RSS item already carries the full article.
+pub(crate) fn rss_item_has_full_content(item: &Item) -> bool {
+    has_full_content(item.content.as_deref().or(item.description.as_deref()))
+}
+
+/// Whether an Atom entry already carries the full article.
+pub(crate) fn atom_entry_has_full_content(entry: &Entry) -> bool {
+    let content = entry.content.as_ref().and_then(|content| content.value.as_deref());
+    has_full_content(content.or(entry.summary.as_ref().map(|summary| summary.value.as_str())))
+}
+
+fn has_full_content(content: Option<&str>) -> bool {
+    match content {
+        Some(content) => extract_text_from_str(content.to_string(), true)
+            .map_or(false, |text| text.trim().chars().count() >= FULL_CONTENT_MIN_CHARS),
+        None => false,
+    }
+}
+
+/// Fetch the article of a news item and store its main content as HTML and text.
+///
+/// Articles that turn out to be PDFs or other documents only get their text stored.
+pub(crate) async fn fetch_full_text(client: &FetchClient,
+                                    pool: &Pool<Postgres>,
+                                    profile: Option<&FetchProfile>,
+                                    news_id: uuid::Uuid,
+                                    url: &str) -> anyhow::Result<()> {
+    let response = client.get_with(url, profile).await?;
+    let base = url::Url::parse(&response.url).ok();
+    // servers that don't say what they sent are almost always sending HTML
+    let content_type = response.content_type.clone().or(Some("text/html".to_string()));
+    let rules = site_rules(pool, &response.url).await;
+    let document = extract_document_async(response.body.clone(), content_type, Some(response.url.clone()), base.clone(), rules).await?;
+
+    db::update_news_content(pool, &news_id, document.content_html.as_deref(), &document.text).await?;
+    let metadata = match document.kind {
+        DocumentKind::Html => extract_metadata(&response.text(), base.as_ref()),
+        _ => document.metadata(),
+    };
+    enrich_news(pool, &news_id, &metadata).await;
+    save_news_text_stats(pool, &news_id, &document.text).await;
+    chunk_news(pool, &news_id, &document.text).await;
+    link_news(pool, &news_id, url, &document.links).await;
+    save_news_thumbnail(client, pool, &news_id, document.lead_image.as_deref()).await;
+    info!("Full text saved for news item: {}", news_id);
+    Ok(())
+}
+
+/// Like `fetch_full_text`, logging rather than returning errors.
+pub(crate) async fn try_fetch_full_text(client: &FetchClient,
+                                        pool: &Pool<Postgres>,
+                                        profile: Option<&FetchProfile>,
+                                        news_id: uuid::Uuid,
+                                        url: &str) -> bool {
+    match fetch_full_text(client, pool, profile, news_id, url).await {
+        Ok(_) => true,
+        Err(e) => {
+            error!("Error fetching full text of {}: {}", url, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atom_syndication::{Content, Text};
+
+    use super::*;
+
+    #[test]
+    fn test_full_content_detection() {
+        let teaser = "<p>The council voted on the new bike lanes. <a href=\"/more\">Read more</a></p>";
+        let article = format!("<p>{}</p>", "The council voted on the new bike lanes after a long debate. ".repeat(30));
+
+        let mut item = Item::default();
+        assert!(!rss_item_has_full_content(&item));
+        item.set_description(teaser.to_string());
+        assert!(!rss_item_has_full_content(&item));
+        item.set_content(article.clone());
+        assert!(rss_item_has_full_content(&item));
+
+        let mut entry = Entry::default();
+        entry.set_summary(Some(Text::html(teaser)));
+        assert!(!atom_entry_has_full_content(&entry));
+        entry.set_content(Some(Content { value: Some(article), ..Default::default() }));
+        assert!(atom_entry_has_full_content(&entry));
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use log::{error, info};
+use opml::Outline;
+use sqlx::{Pool, Postgres};
+
+use crate::fetcher::{collect_outlines, ContentFetch, create_timestamped_dir, download_content, FetchClient, FetchProfile, ingest_feed, safe_filename, save_error, try_fetch_full_text};
+use crate::fetcher::queue::{CancelToken, WorkQueue};
+use crate::config::Config;
+use crate::models::Source;
+
+/// The NATS subject progress events are published on.
+pub(crate) const PROGRESS_SUBJECT: &str = "ingest_progress";
+
+/// Only the most recent failures are kept on the progress report.
+const MAX_FAILURES: usize = 100;
+
+/// How many hours a finished ingestion can still be looked up.
+const FINISHED_JOB_RETENTION_HOURS: i64 = 24;
+
+/// Only the most recently finished ingestions are kept.
+const MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IngestStatus {
+    Running,
+    Completed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct IngestFailure {
+    pub url: String,
+    pub message: String,
+}
+
+/// A snapshot of how far a bulk ingestion has got.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub(crate) struct IngestProgress {
+    pub job_id: uuid::Uuid,
+    pub name: String,
+    pub status: IngestStatus,
+    pub outlines_total: usize,
+    pub outlines_done: usize,
+    pub items_saved: usize,
+    pub items_failed: usize,
+    pub downloads_queued: usize,
+    pub downloads_done: usize,
+    pub failures: Vec<IngestFailure>,
+    pub create_timestamp: chrono::DateTime<Utc>,
+    pub finish_timestamp: Option<chrono::DateTime<Utc>>,
+}
+
+/// An event published to NATS as the ingestion progresses.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum IngestEvent<'a> {
+    OutlineDone { job_id: uuid::Uuid, url: &'a str, items_saved: usize },
+    ItemSaved { job_id: uuid::Uuid, url: &'a str },
+    Failure { job_id: uuid::Uuid, url: &'a str, message: &'a str },
+    Finished { progress: &'a IngestProgress },
+}
+
+/// Settings for the bulk ingestion work queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IngestSettings {
+    pub concurrency: usize,
+    pub per_host: usize,
+}
+
+impl From<&Config> for IngestSettings {
+    fn from(config: &Config) -> Self {
+        Self {
+            concurrency: config.ingest_concurrency,
+            per_host: config.ingest_per_host_concurrency,
+        }
+    }
+}
+
+impl Default for IngestSettings {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            per_host: 2,
+        }
+    }
+}
+
+/// A handle to a running bulk ingestion, used to report and cancel it.
+#[derive(Clone)]
+pub(crate) struct IngestJob {
+    progress: Arc<Mutex<IngestProgress>>,
+    cancel: CancelToken,
+    nc: Option<nats::Connection>,
+}
+
+impl IngestJob {
+    pub fn new(name: String, nc: Option<nats::Connection>) -> Self {
+        let progress = IngestProgress {
+            job_id: uuid::Uuid::new_v4(),
+            name,
+            status: IngestStatus::Running,
+            outlines_total: 0,
+            outlines_done: 0,
+            items_saved: 0,
+            items_failed: 0,
+            downloads_queued: 0,
+            downloads_done: 0,
+            failures: vec![],
+            create_timestamp: Utc::now(),
+            finish_timestamp: None,
+        };
+        Self {
+            progress: Arc::new(Mutex::new(progress)),
+            cancel: CancelToken::new(),
+            nc,
+        }
+    }
+
+    pub fn id(&self) -> uuid::Uuid {
+        self.progress.lock().unwrap().job_id
+    }
+
+    pub fn progress(&self) -> IngestProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    fn update<F>(&self, f: F) where F: FnOnce(&mut IngestProgress) {
+        f(&mut self.progress.lock().unwrap());
+    }
+
+    fn publish(&self, event: IngestEvent) {
+        if let Some(nc) = &self.nc {
+            let payload = serde_json::to_vec(&event).expect("Failed to serialize IngestEvent");
+            if let Err(e) = nc.publish(PROGRESS_SUBJECT, payload) {
+                error!("Error publishing {}: {}", PROGRESS_SUBJECT, e);
+            }
+        }
+    }
+
+    fn outline_done(&self, url: &str, saved: usize, failed: usize) {
+        self.update(|p| {
+            p.outlines_done += 1;
+            p.items_saved += saved;
+            p.items_failed += failed;
+        });
+        self.publish(IngestEvent::OutlineDone { job_id: self.id(), url, items_saved: saved });
+    }
+
+    fn download_queued(&self) {
+        self.update(|p| p.downloads_queued += 1);
+    }
+
+    fn download_done(&self, url: &str) {
+        self.update(|p| p.downloads_done += 1);
+        self.publish(IngestEvent::ItemSaved { job_id: self.id(), url });
+    }
+
+    fn failure(&self, url: &str, message: &str) {
+        error!("Ingestion failure for {}: {}", url, message);
+        self.update(|p| {
+            if p.failures.len() >= MAX_FAILURES {
+                p.failures.remove(0);
+            }
+            p.failures.push(IngestFailure { url: url.to_string(), message: message.to_string() });
+        });
+        self.publish(IngestEvent::Failure { job_id: self.id(), url, message });
+    }
+
+    fn finish(&self) {
+        let status = if self.cancel.is_cancelled() { IngestStatus::Cancelled } else { IngestStatus::Completed };
+        self.update(|p| {
+            p.status = status;
+            p.finish_timestamp = Some(Utc::now());
+        });
+        let progress = self.progress();
+        self.publish(IngestEvent::Finished { progress: &progress });
+    }
+}
+
+/// The bulk ingestions known to the API, running or finished.
+#[derive(Clone, Default)]
+pub(crate) struct IngestJobs {
+    jobs: Arc<Mutex<HashMap<uuid::Uuid, IngestJob>>>,
+}
+
+impl IngestJobs {
+    /// Track `job`, forgetting finished jobs that are too old or too many.
+    pub fn insert(&self, job: IngestJob) {
+        let mut jobs = self.jobs.lock().unwrap();
+        evict_finished(&mut jobs, Utc::now());
+        jobs.insert(job.id(), job);
+    }
+
+    pub fn get(&self, id: &uuid::Uuid) -> Option<IngestJob> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<IngestProgress> {
+        let mut jobs: Vec<IngestProgress> = self.jobs.lock().unwrap()
+            .values()
+            .map(|job| job.progress())
+            .collect();
+        jobs.sort_by_key(|p| std::cmp::Reverse(p.create_timestamp));
+        jobs
+    }
+}
+
+/// Remove the jobs that finished before the retention period, and the oldest finished jobs
+/// beyond `MAX_FINISHED_JOBS`. Running jobs are always kept so they can be cancelled.
+fn evict_finished(jobs: &mut HashMap<uuid::Uuid, IngestJob>, now: chrono::DateTime<Utc>) {
+    let mut finished: Vec<(chrono::DateTime<Utc>, uuid::Uuid)> = jobs.iter()
+        .filter_map(|(id, job)| job.progress().finish_timestamp.map(|finished| (finished, *id)))
+        .collect();
+    finished.sort_by_key(|(finished, _)| std::cmp::Reverse(*finished));
+    for (index, (finished, id)) in finished.into_iter().enumerate() {
+        if index >= MAX_FINISHED_JOBS || now - finished > chrono::Duration::hours(FINISHED_JOB_RETENTION_HOURS) {
+            jobs.remove(&id);
+        }
+    }
+}
+
+/// Start ingesting an OPML document in the background, returning the job tracking it.
+pub(crate) async fn spawn_opml_ingest(client: FetchClient,
+                                      pool: Pool<Postgres>,
+                                      nc: Option<nats::Connection>,
+                                      name: String,
+                                      opml: String,
+                                      settings: IngestSettings) -> std::io::Result<IngestJob> {
+    let dir_path = create_timestamped_dir(&name).await?;
+    let job = IngestJob::new(name, nc);
+    tokio::spawn(ingest_opml(client, pool, job.clone(), opml, dir_path, settings));
+    Ok(job)
+}
+
+/// Ingest every RSS outline of an OPML document.
+///
+/// Outlines, and the articles of each feed, are queued on a `WorkQueue` so that feeds are
+/// fetched concurrently while each host only sees a couple of requests at a time.
+pub(crate) async fn ingest_opml(client: FetchClient,
+                                pool: Pool<Postgres>,
+                                job: IngestJob,
+                                opml: String,
+                                dir_path: String,
+                                settings: IngestSettings) {
+    let document = match opml::OPML::from_str(&opml) {
+        Ok(document) => document,
+        Err(e) => {
+            job.failure(&job.progress().name, &format!("Unable to parse OPML: {}", e));
+            job.finish();
+            return;
+        }
+    };
+
+    let mut outlines = Vec::new();
+    for outline in document.body.outlines {
+        collect_outlines(&outline, &mut outlines);
+    }
+    job.update(|p| p.outlines_total = outlines.len());
+
+    let queue = WorkQueue::new(settings.concurrency, settings.per_host, job.cancel.clone());
+    for outline in outlines {
+        let url = outline.xml_url.clone().or(outline.html_url.clone()).unwrap_or_default();
+        let task = ingest_outline(queue.clone(), client.clone(), pool.clone(), job.clone(), dir_path.clone(), outline);
+        queue.spawn(&url, task);
+    }
+
+    queue.wait().await;
+    job.finish();
+    info!("Finished ingestion {:?}", job.progress());
+}
+
+async fn ingest_outline(queue: WorkQueue,
+                        client: FetchClient,
+                        pool: Pool<Postgres>,
+                        job: IngestJob,
+                        dir_path: String,
+                        outline: Outline) {
+    info!("processing: {:?}", outline);
+
+    let html_url = match &outline.html_url {
+        Some(html_url) => html_url.clone(),
+        None => {
+            job.outline_done(&outline.text, 0, 0);
+            return;
+        }
+    };
+
+    // save source
+    let source = Source::new(outline.text.clone(), html_url, 5);
+    let source_id = match source.save(&pool).await {
+        Ok(id) => id,
+        Err(e) => {
+            job.failure(&source.url, &format!("{}: {}", save_error("source", &source.url), e));
+            job.outline_done(&source.url, 0, 0);
+            return;
+        }
+    };
+
+    let feed_url = match &outline.xml_url {
+        Some(feed_url) => feed_url.clone(),
+        None => {
+            job.outline_done(&source.url, 0, 0);
+            return;
+        }
+    };
+
+    // create directory for feed
+    let url_simplified = feed_url.replace("https://", "").replace("http://", "").replace("www.", "");
+    let feed_slug = slug::slugify(url_simplified);
+    let feed_dir = format!("{}/{}", dir_path, safe_filename(&feed_slug).await);
+    let content_dir = format!("{}/{}", feed_dir, "content");
+    if let Err(e) = tokio::fs::create_dir_all(&content_dir).await {
+        job.failure(&feed_url, &format!("Unable to create directory: {}", e));
+        job.outline_done(&feed_url, 0, 0);
+        return;
+    }
+
+    let ingest = match ingest_feed(&client, source_id, &feed_url, &feed_dir, &pool, false).await {
+        Ok(Some(ingest)) => ingest,
+        Ok(None) => {
+            job.failure(&feed_url, "Unable to fetch feed");
+            job.outline_done(&feed_url, 0, 0);
+            return;
+        }
+        Err(e) => {
+            job.failure(&feed_url, &format!("{}: {}", save_error("feed", &feed_url), e));
+            job.outline_done(&feed_url, 0, 0);
+            return;
+        }
+    };
+    job.outline_done(&feed_url, ingest.items_saved, ingest.items_failed);
+
+    // queue the article downloads so they're spread across hosts too
+    let profile: Arc<Option<FetchProfile>> = Arc::new(ingest.profile);
+    for (news_id, title_slug, content_url) in ingest.content {
+        let Some(content_url) = content_url else {
+            job.failure(&feed_url, &format!("No content URL found for item: {}", title_slug));
+            continue;
+        };
+
+        job.download_queued();
+        let client = client.clone();
+        let pool = pool.clone();
+        let profile = profile.clone();
+        let job = job.clone();
+        let content_dir = content_dir.clone();
+        queue.spawn(&content_url.clone(), async move {
+            let fetch = ContentFetch { client: &client, pool: &pool, profile: profile.as_ref().as_ref() };
+            if download_content(&fetch, &content_dir, news_id, &title_slug, Some(content_url.clone()), true).await {
+                job.download_done(&content_url);
+            } else {
+                job.failure(&content_url, "Unable to download content");
+            }
+        });
+    }
+
+    // and the articles of new items of full-text feeds
+    for (news_id, url) in ingest.full_text {
+        job.download_queued();
+        let client = client.clone();
+        let pool = pool.clone();
+        let profile = profile.clone();
+        let job = job.clone();
+        queue.spawn(&url.clone(), async move {
+            if try_fetch_full_text(&client, &pool, profile.as_ref().as_ref(), news_id, &url).await {
+                job.download_done(&url);
+            } else {
+                job.failure(&url, "Unable to fetch full text");
+            }
+        });
+    }
+}
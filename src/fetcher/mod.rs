@@ -1,7 +1,8 @@
 use std::env;
-use std::fs::{self, File};
-use std::io::{Error, Write};
+use std::fs::File;
+use std::io::Error;
 
+use anyhow::Context;
 use atom_syndication::{Entry, Feed, Link};
 use chrono::{DateTime, NaiveDate, Utc};
 use log::{error, info, LevelFilter, warn};
@@ -12,21 +13,72 @@ use serde::Serialize;
 use simplelog::{ColorChoice, CombinedLogger, Config, TerminalMode, TermLogger, WriteLogger};
 use sqlx::{Pool, Postgres};
 use url::Url;
-use webpage::{Webpage, WebpageOptions};
-use crate::extractor::extract_text_from_str;
+use webpage::Webpage;
+use crate::extractor::{extract_article_with_rules, extract_document_async, extract_metadata, DocumentKind, Metadata, SiteRules};
 use crate::{db, models};
 use crate::models::{WebpageRequest, WebpageResponse, Source};
 
+mod client;
+mod enrich;
+mod full_text;
+mod ingest;
+mod profile;
+mod queue;
+
+pub(crate) use client::{default_client, FetchClient, FetchError, FetchOptions, FetchResponse};
+pub(crate) use enrich::{save_page_thumbnail, try_enrich_company};
+pub(crate) use ingest::{ingest_opml, spawn_opml_ingest, IngestJob, IngestJobs, IngestSettings};
+pub(crate) use profile::{CredentialsError, FetchProfile, FetchProfileSummary};
+use full_text::{atom_entry_has_full_content, rss_item_has_full_content, try_fetch_full_text};
+
 // use crate::extract::extract_text_from_str;
 // use crate::models::Source;
 
-pub(crate) async fn fetch_url(req: &WebpageRequest) -> Result<Webpage, Error> {
-    let html_options = WebpageOptions { allow_insecure: true, ..Default::default() };
-    Webpage::from_url(&*req.url, html_options)
+pub(crate) async fn fetch_url(client: &FetchClient, pool: &Pool<Postgres>, req: &WebpageRequest) -> Result<Webpage, FetchError> {
+    let profile = load_fetch_profile(client, pool, &req.url, None).await;
+    client.get_webpage_with(&req.url, profile.as_ref()).await
+}
+
+/// Fetch and extract the article of a news item now, rather than waiting for its feed to be
+/// fetched again, using the fetch profile of its feed when the article is on the same origin.
+pub(crate) async fn extract_news(client: &FetchClient, pool: &Pool<Postgres>, news_item: &models::NewsItem) -> anyhow::Result<()> {
+    let profile = match db::feed_by_id(pool, &news_item.feed_id).await {
+        Ok(feed) => load_fetch_profile(client, pool, &feed.url, Some(feed.source_id)).await,
+        Err(_) => None,
+    };
+    full_text::fetch_full_text(client, pool, profile.as_ref(), news_item.id, &news_item.url).await
+}
+
+/// Load and decrypt the fetch profile for `url`, if there is one.
+///
+/// The profile is bound to the origin of `url`, so it is only applied to requests for that
+/// origin. A source's profile is only used if the source is on the same origin as `url`.
+///
+/// Failures are logged rather than returned so that a broken profile doesn't stop
+/// the fetch from being attempted.
+pub(crate) async fn load_fetch_profile(client: &FetchClient, pool: &Pool<Postgres>, url: &str, source_id: Option<uuid::Uuid>) -> Option<FetchProfile> {
+    let origin = Url::parse(url).ok()?.origin();
+    let records = match db::fetch_profiles_for_url(pool, url, source_id).await {
+        Ok(records) => records,
+        Err(e) => {
+            error!("Error loading fetch profile for {}: {}", url, e);
+            return None;
+        }
+    };
+    let (record, _) = records.into_iter()
+        .find(|(_, owner_url)| Url::parse(owner_url).map_or(false, |owner_url| owner_url.origin() == origin))?;
+    let decrypted = client.credentials_key().and_then(|key| key.decrypt(&record));
+    match decrypted {
+        Ok(profile) => Some(profile.bound_to(origin)),
+        Err(e) => {
+            error!("Error decrypting fetch profile {}: {}", record.id, e);
+            None
+        }
+    }
 }
 
 #[allow(dead_code)]
-async fn fetch() -> Result<(), Error> {
+async fn fetch() -> anyhow::Result<()> {
     let args: Vec<String> = env::args().collect();
     info!("{:?}", args);
     if args.len() != 2 {
@@ -43,21 +95,22 @@ async fn fetch() -> Result<(), Error> {
 
     let url = &args[1];
 
-    let dir_path = create_timestamped_dir(url).await;
+    let dir_path = create_timestamped_dir(url).await?;
 
     let pool = db::get_pool().await;
+    let client = default_client();
 
     if url.starts_with("http") {
         info!("Handling url: {}", url);
         // let dir = dir_path.clone();
-        handle_url(&dir_path, url, &pool).await.expect("Error handling url");
+        handle_url(client, &dir_path, url, &pool).await.context("Error handling url")?;
     } else if url.starts_with("feed!") {
         info!("Handling Feed url: {}", url);
         let orig_feed_url = url.replace("feed!", "");
         let feed_url = get_feed_url(&url, orig_feed_url).await;
         // TODO create a new source record
         let source_id = uuid::Uuid::try_from("5f4c7adf-2236-428b-9db6-7fbab59b4507").unwrap();
-        handle_feed(source_id, &feed_url, &dir_path, &pool).await.expect("Feed error");
+        handle_feed(client, source_id, &feed_url, &dir_path, &pool).await.context("Feed error")?;
     } else if url.starts_with("opml!") {
         info!("Handling OPML url: {}", url);
 
@@ -65,23 +118,25 @@ async fn fetch() -> Result<(), Error> {
         let opml_url = url.replace("opml!", "");
 
         // determine if url is local or remote
-        if opml_url.starts_with("http") {} else {
+        let opml_file = if opml_url.starts_with("http") {
+            let response = client.get(&opml_url).await.context("Unable to fetch OPML")?;
+            response.text()
+        } else {
             // assume local file
-            let opml_file = fs::read_to_string(opml_url).expect("Unable to read file");
-            let opml = opml::OPML::from_str(&opml_file).expect("Unable to parse OPML");
-
-            // first, get all the rss outlines from opml
-            let mut outlines = Vec::new();
-            for outline in opml.body.outlines {
-                collect_outlines(&outline, &mut outlines);
+            tokio::fs::read_to_string(&opml_url).await.with_context(|| format!("Unable to read {}", opml_url))?
+        };
+
+        // publish progress like ingestions started through the API, if NATS is reachable
+        let nats_url = env::var("NATS_URL").unwrap_or_else(|_| "nats://localhost:4222".to_string());
+        let nc = match nats::connect(&nats_url) {
+            Ok(nc) => Some(nc),
+            Err(e) => {
+                warn!("Unable to connect to {}, not publishing progress: {}", nats_url, e);
+                None
             }
-
-            // then, handle each outline
-            for outline in outlines {
-                let dir = dir_path.clone();
-                handle_opml_outline(&dir, &outline, &pool).await;
-            }
-        }
+        };
+        let job = IngestJob::new(url.to_string(), nc);
+        ingest_opml(client.clone(), pool, job, opml_file, dir_path, IngestSettings::default()).await;
     } else {
         error!("Unknown url type: {}", url);
     }
@@ -101,49 +156,23 @@ fn collect_outlines(outline: &Outline, outlines: &mut Vec<Outline>) {
     }
 }
 
-async fn handle_opml_outline(dir_path: &str, outline: &Outline, pool: &Pool<Postgres>) {
-    info!("processing: {:?}", outline);
-
-    // save source
-    if outline.html_url.is_some() {
-        let html_url = outline.html_url.clone().unwrap();
-        let source = Source::new(outline.text.clone(), html_url, 5);
-        let source_id = source.save(&pool).await.expect(save_error("source", source.url.as_str()).as_str());
-        // save feed
-        if outline.xml_url.is_some() {
-            let feed_url = outline.xml_url.clone().unwrap();
-
-            // create feed slug
-            let url_simplified = feed_url.replace("https://", "").replace("http://", "").replace("www.", "");
-            let feed_slug = slug::slugify(url_simplified);
-
-            // create directory for feed
-            let safe_feed_slug = safe_filename(&feed_slug).await;
-            let feed_dir = format!("{}/{}", dir_path, safe_feed_slug);
-            fs::create_dir_all(&feed_dir).expect("Unable to create directory");
-
-            handle_feed(source_id, &feed_url, &feed_dir, pool).await.expect(save_error("feed", &feed_url).as_str());
-        }
-    }
-}
-
 fn save_error(thing: &str, id: &str) -> String {
     format!("Error saving {}: {}", thing, id)
 }
 
-async fn create_timestamped_dir(url: &str) -> String {
+async fn create_timestamped_dir(url: &str) -> Result<String, Error> {
     // Generate timestamped directory and slug
     let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
     let url_simplified = url.replace("https://", "").replace("http://", "").replace("www.", "");
     let slug = slug::slugify(url_simplified);
     let dir_path = format!("downloads/{}_{}", timestamp, slug);
-    fs::create_dir_all(&dir_path).expect("Unable to create directory");
-    dir_path
+    tokio::fs::create_dir_all(&dir_path).await?;
+    Ok(dir_path)
 }
 
-async fn handle_url(dir_path: &str, url: &str, pool: &Pool<Postgres>) -> anyhow::Result<()> {
-    let html_options = WebpageOptions { allow_insecure: true, ..Default::default() };
-    let webpage_result = Webpage::from_url(&url, html_options);
+async fn handle_url(client: &FetchClient, dir_path: &str, url: &str, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    let profile = load_fetch_profile(client, pool, url, None).await;
+    let webpage_result = client.get_webpage_with(url, profile.as_ref()).await;
     let webpage = match webpage_result {
         Ok(v) => v,
         Err(e) => {
@@ -152,31 +181,42 @@ async fn handle_url(dir_path: &str, url: &str, pool: &Pool<Postgres>) -> anyhow:
         }
     };
 
+    let metadata = extract_metadata(&webpage.http.body, Url::parse(&webpage.http.url).ok().as_ref());
+
     // save source to db
-    let source = webpage_to_source(&webpage);
-    source.save(pool).await.expect("Error saving source");
+    let source = webpage_to_source(&webpage, &metadata);
+    let source_id = source.save(pool).await.context("Error saving source")?;
+    enrich::save_source_thumbnail(client, pool, &source_id, metadata.image.as_deref()).await;
 
     info!("source: {:?}", source);
 
     let content = &webpage.http.body;
     write_file(dir_path, "content.html", &content).await?;
     write_json_file(dir_path, "html-info.json", &webpage).await?;
+    write_json_file(dir_path, "metadata.json", &metadata).await?;
 
     // If there's a feed available, write it to a file
     if webpage.html.feed.is_some() {
         let orig_feed_url = webpage.html.feed.unwrap();
         let feed_url = get_feed_url(&url, orig_feed_url).await;
-        handle_feed(source.id, &feed_url, &dir_path, pool).await.expect("Feed error");
+        handle_feed(client, source_id, &feed_url, &dir_path, pool).await.context("Feed error")?;
     }
 
     Ok(())
 }
 
-fn webpage_to_source(webpage: &Webpage) -> Source {
-    let title = webpage.html.title.clone().unwrap();
+fn webpage_to_source(webpage: &Webpage, metadata: &Metadata) -> Source {
     let url = webpage.http.url.clone();
+    // not every page has a title, fall back to the host so the source can still be saved
+    let title = metadata.site_name.clone()
+        .or(metadata.title.clone())
+        .or(webpage.html.title.clone())
+        .or(Url::parse(&url).ok().and_then(|u| u.host_str().map(|host| host.to_string())))
+        .unwrap_or(url.clone());
     let type_id = 5; // Website
-    Source::new(title, url, type_id)
+    let mut source = Source::new(title.chars().take(100).collect(), url, type_id);
+    source.description = metadata.description.clone();
+    source
 }
 
 async fn get_feed_url(url: &str, orig_feed_url: String) -> String {
@@ -203,8 +243,7 @@ async fn get_feed_url(url: &str, orig_feed_url: String) -> String {
 async fn write_file(dir_path: &str, file_name: &str, content: &String) -> Result<String, Error>
 {
     let content_path = format!("{}/{}", dir_path, file_name);
-    let mut file: fs::File = fs::File::create(&content_path)?;
-    file.write_all(content.as_bytes())?;
+    tokio::fs::write(&content_path, content).await?;
     Ok(content_path)
 }
 
@@ -212,20 +251,49 @@ async fn write_json_file<T>(dir_path: &str, file_name: &str, content: &T) -> Res
     where T: ?Sized + Serialize
 {
     let info_path = format!("{}/{}", dir_path, file_name);
-    let mut file: fs::File = fs::File::create(&info_path)?;
     let info_json = serde_json::to_string_pretty(&content)?;
-    file.write_all(&info_json.as_bytes())?;
+    tokio::fs::write(&info_path, info_json).await?;
     Ok(info_path)
 }
 
-async fn handle_feed(source_id: uuid::Uuid, feed_url: &str, dir_path: &str, pool: &Pool<Postgres>) -> Result<(), Error> {
-    let feed_options = WebpageOptions { allow_insecure: true, ..Default::default() };
-    let feed_webpage_result = Webpage::from_url(&feed_url, feed_options);
+async fn handle_feed(client: &FetchClient, source_id: uuid::Uuid, feed_url: &str, dir_path: &str, pool: &Pool<Postgres>) -> Result<(), Error> {
+    if let Some(ingest) = ingest_feed(client, source_id, feed_url, dir_path, pool, true).await? {
+        for (news_id, url) in &ingest.full_text {
+            try_fetch_full_text(client, pool, ingest.profile.as_ref(), *news_id, url).await;
+        }
+    }
+    Ok(())
+}
+
+/// What was saved from a single feed, and the item content still to be downloaded.
+#[derive(Default)]
+struct FeedIngest {
+    items_saved: usize,
+    items_failed: usize,
+    /// News id, title slug and content URL of each item whose content hasn't been downloaded
+    /// yet. The id is missing if the item couldn't be saved.
+    content: Vec<(Option<uuid::Uuid>, String, Option<String>)>,
+    /// New items of a full-text feed whose article still has to be fetched.
+    full_text: Vec<(uuid::Uuid, String)>,
+    profile: Option<FetchProfile>,
+}
+
+/// Fetch a feed, save it and its items, and optionally download each item's content.
+///
+/// Returns `None` if the feed itself couldn't be fetched.
+async fn ingest_feed(client: &FetchClient,
+                     source_id: uuid::Uuid,
+                     feed_url: &str,
+                     dir_path: &str,
+                     pool: &Pool<Postgres>,
+                     save_content_files: bool) -> Result<Option<FeedIngest>, Error> {
+    let profile = load_fetch_profile(client, pool, feed_url, Some(source_id)).await;
+    let feed_webpage_result = client.get_webpage_with(feed_url, profile.as_ref()).await;
     let feed_webpage = match feed_webpage_result {
         Ok(v) => v,
         Err(e) => {
             error!("Error fetching feed webpage: {}; {}", feed_url, e);
-            return Ok(());
+            return Ok(None);
         }
     };
 
@@ -237,10 +305,12 @@ async fn handle_feed(source_id: uuid::Uuid, feed_url: &str, dir_path: &str, pool
     // Write the feed info to a file
     write_json_file(dir_path, "feed-info.json", &feed_webpage).await?;
 
-    let rss_parse_result = handle_rss_feed(dir_path, feed_content.to_string(), true).await;
+    let mut ingest = FeedIngest::default();
+
+    let rss_parse_result = handle_rss_feed(dir_path, feed_content.to_string()).await;
     if rss_parse_result.is_err() {
         info!("Trying to parse as Atom feed...");
-        let atom_parse_result = handle_atom_feed(dir_path, feed_content, true).await;
+        let atom_parse_result = handle_atom_feed(dir_path, feed_content).await;
         if atom_parse_result.is_err() {
             info!("Error parsing Atom feed: {}", atom_parse_result.err().unwrap());
         } else {
@@ -252,19 +322,42 @@ async fn handle_feed(source_id: uuid::Uuid, feed_url: &str, dir_path: &str, pool
 
             // save feed to db
             let feed: models::Feed = feed_webpage_to_feed(source_id, title, feed_type, &feed_webpage);
-            feed.save(pool).await.expect("Error saving feed");
+            let feed_id = match feed.save(pool).await {
+                Ok(id) => id,
+                Err(e) => {
+                    info!("Feed not saved (possibly duplicate): {}", e);
+                    feed.id
+                }
+            };
+            let full_text = is_full_text_feed(pool, &feed_id).await;
 
             let entries: Vec<Entry> = atom.entries;
             if entries.len() == 0 {
                 error!("No entries found in Atom feed");
             } else {
                 for entry in entries {
-                    let news_item = entry_to_news_item(feed.id, &entry);
+                    let Some(news_item) = entry_to_news_item(feed_id, &entry) else {
+                        ingest.items_failed += 1;
+                        error!("No link found for entry: {}", entry.id);
+                        continue;
+                    };
                     let result = news_item.save(pool).await;
-                    match result {
-                        Ok(_) => {}
-                        Err(e) => info!("Error saving news item: {}", e)
-                    }
+                    let news_id = match result {
+                        Ok(id) => {
+                            ingest.items_saved += 1;
+                            if full_text && id == news_item.id && !atom_entry_has_full_content(&entry) {
+                                ingest.full_text.push((id, news_item.url.clone()));
+                            }
+                            Some(id)
+                        }
+                        Err(e) => {
+                            ingest.items_failed += 1;
+                            info!("Error saving news item: {}", e);
+                            None
+                        }
+                    };
+                    let title_slug = slug::slugify(entry.title.clone().value);
+                    ingest.content.push((news_id, title_slug, get_atom_content_url(&entry.links).await));
                 }
             }
         }
@@ -279,38 +372,87 @@ async fn handle_feed(source_id: uuid::Uuid, feed_url: &str, dir_path: &str, pool
         let feed: models::Feed = feed_webpage_to_feed(source_id, title, feed_type, &feed_webpage);
         let maybe_id = feed.save(pool).await;
 
-        match maybe_id {
-            Ok(id) => info!("Feed saved successfully: {}", id),
-            Err(e) => info!("Feed not saved (possibly duplicate): {}", e)
-        }
+        let feed_id = match maybe_id {
+            Ok(id) => {
+                info!("Feed saved successfully: {}", id);
+                id
+            }
+            Err(e) => {
+                info!("Feed not saved (possibly duplicate): {}", e);
+                feed.id
+            }
+        };
+
+        let full_text = is_full_text_feed(pool, &feed_id).await;
 
         let items: Vec<Item> = channel.clone().items;
         if items.len() == 0 {
             error!("No items found in RSS feed: {:?}", channel);
         } else {
             for item in items {
-                let news_item = item_to_news_item(feed.id, &item);
+                let Some(news_item) = item_to_news_item(feed_id, &item) else {
+                    ingest.items_failed += 1;
+                    error!("No link found for item: {:?}", item.title);
+                    continue;
+                };
                 let maybe_id = news_item.save(pool).await;
-                match maybe_id {
-                    Ok(id) => info!("News item saved successfully: {}", id),
-                    Err(e) => info!("News item not saved (possibly duplicate): {}", e)
+                let news_id = match maybe_id {
+                    Ok(id) => {
+                        ingest.items_saved += 1;
+                        info!("News item saved successfully: {}", id);
+                        if full_text && id == news_item.id && !rss_item_has_full_content(&item) {
+                            ingest.full_text.push((id, news_item.url.clone()));
+                        }
+                        Some(id)
+                    }
+                    Err(e) => {
+                        ingest.items_failed += 1;
+                        info!("News item not saved (possibly duplicate): {}", e);
+                        None
+                    }
+                };
+                match &item.title {
+                    Some(title) => ingest.content.push((news_id, slug::slugify(title), item.link.clone())),
+                    None => error!("No title found for item: {:?}", item),
                 }
             }
         }
     }
 
-    Ok(())
+    // items are saved first, so that downloading their content can fill in their metadata
+    if save_content_files {
+        let content_dir_path = format!("{}/{}", dir_path, "content");
+        tokio::fs::create_dir_all(&content_dir_path).await?;
+        let fetch = ContentFetch { client, pool, profile: profile.as_ref() };
+        for (news_id, title_slug, content_url) in ingest.content.drain(..) {
+            download_content(&fetch, &content_dir_path, news_id, &title_slug, content_url, true).await;
+        }
+    }
+
+    ingest.profile = profile;
+    Ok(Some(ingest))
 }
 
-/// Convert an RSS item to a NewsItem
-fn item_to_news_item(feed_id: uuid::Uuid, item: &Item) -> models::NewsItem {
+/// Whether the feed is set to fetch the full article of each new item.
+async fn is_full_text_feed(pool: &Pool<Postgres>, feed_id: &uuid::Uuid) -> bool {
+    match db::feed_by_id(pool, feed_id).await {
+        Ok(feed) => feed.full_text,
+        Err(e) => {
+            error!("Error loading feed {}: {}", feed_id, e);
+            false
+        }
+    }
+}
+
+/// Convert an RSS item to a NewsItem, or `None` if it has no link.
+fn item_to_news_item(feed_id: uuid::Uuid, item: &Item) -> Option<models::NewsItem> {
     let title = item.title.clone().or(Some("n/a".to_string())).unwrap();
+    let url = item.link.clone()?;
     // set guid to either guid or link
     let guid = match item.guid.clone() {
         Some(guid) => guid.value,
-        None => item.link.clone().expect("Unable to get link for guid")
+        None => url.clone()
     };
-    let url = item.link.clone().expect("Unable to get link");
     let maybe_pub_date = item.pub_date.clone();
     let pub_date: DateTime<Utc> = match maybe_pub_date {
         Some(dt) => {
@@ -324,7 +466,7 @@ fn item_to_news_item(feed_id: uuid::Uuid, item: &Item) -> models::NewsItem {
         }
         None => Utc::now()
     };
-    models::NewsItem::new(feed_id, guid, title, pub_date, url)
+    Some(models::NewsItem::new(feed_id, guid, title, pub_date, url))
 }
 
 // write a test for the following function
@@ -430,22 +572,38 @@ mod tests {
         let expected_date: DateTime<Utc> = "2020-01-01T12:34:56Z".parse().unwrap();
         assert_eq!(parsed_date, expected_date, "Dates do not match.");
     }
+
+    #[test]
+    fn test_items_without_link() {
+        let feed_id = uuid::Uuid::new_v4();
+        let mut item = Item::default();
+        item.set_title("No link".to_string());
+        assert!(item_to_news_item(feed_id, &item).is_none());
+
+        item.set_link("https://example.com/post".to_string());
+        let news_item = item_to_news_item(feed_id, &item).unwrap();
+        assert_eq!(news_item.guid, "https://example.com/post");
+
+        let entry = Entry::default();
+        assert!(entry_to_news_item(feed_id, &entry).is_none());
+    }
+
 }
 
 fn date_parse_error(date: &str) -> String {
     format!("Failed to parse date and time: '{}'", date)
 }
 
-/// Convert an Atom entry to a NewsItem
-fn entry_to_news_item(feed_id: uuid::Uuid, entry: &Entry) -> models::NewsItem {
+/// Convert an Atom entry to a NewsItem, or `None` if it has no link.
+fn entry_to_news_item(feed_id: uuid::Uuid, entry: &Entry) -> Option<models::NewsItem> {
     let title = entry.title.clone().value;
     let guid = entry.id.clone();
-    let url = entry.links[0].href.clone();
+    let url = entry.links.first()?.href.clone();
     let published = match entry.published {
         Some(p) => p,
         None => entry.updated.clone()
     };
-    models::NewsItem::new(feed_id, guid, title, DateTime::from(published), url)
+    Some(models::NewsItem::new(feed_id, guid, title, DateTime::from(published), url))
 }
 
 fn feed_webpage_to_feed(source_id: uuid::Uuid, title: Option<String>, feed_type: Option<String>, webpage: &Webpage) -> models::Feed {
@@ -453,18 +611,12 @@ fn feed_webpage_to_feed(source_id: uuid::Uuid, title: Option<String>, feed_type:
     models::Feed::new(source_id, url, title, feed_type)
 }
 
-async fn handle_atom_feed(dir_path: &str, feed_content: &str, save_content_files: bool) -> Result<Feed, atom_syndication::Error> {
+async fn handle_atom_feed(dir_path: &str, feed_content: &str) -> Result<Feed, atom_syndication::Error> {
     let feed_parsed = Feed::read_from(feed_content.as_bytes());
-    let parsed_file_path = format!("{}/{}", dir_path, "feed-parsed.json");
     match &feed_parsed {
         Ok(feed) => {
-            let feed_parsed_json = serde_json::to_string_pretty(&feed).expect("Unable to serialize Atom feed");
-            let mut feed_parsed_file: fs::File = fs::File::create(&parsed_file_path).expect("Unable to create feed parsed file");
-            feed_parsed_file.write_all(&feed_parsed_json.as_bytes()).expect("Unable to write feed parsed file");
-
-            // for each feed entry save the html content to a file
-            if save_content_files {
-                save_atom_content(dir_path, &feed).await.expect("Unable to save Atom content");
+            if let Err(e) = write_json_file(dir_path, "feed-parsed.json", feed).await {
+                warn!("Unable to save parsed Atom feed: {}", e);
             }
         }
         Err(err) => info!("Not a RSS feed: {}", err),
@@ -473,26 +625,6 @@ async fn handle_atom_feed(dir_path: &str, feed_content: &str, save_content_files
     return feed_parsed;
 }
 
-async fn save_atom_content(dir_path: &str, feed: &Feed) -> Result<(), Error> {
-    // create "content" directory under dir_path
-    let content_dir_path = format!("{}/{}", dir_path, "content");
-    fs::create_dir_all(&content_dir_path).expect("Unable to create content directory");
-
-    let entries: Vec<Entry> = feed.clone().entries;
-    if entries.len() == 0 {
-        error!("No entries found in Atom feed");
-    } else {
-        for entry in entries {
-            let title = entry.title.clone().value;
-            let title_slug = slug::slugify(title);
-            let maybe_content_url = get_atom_content_url(&entry.links).await;
-            download_content(&content_dir_path, &title_slug, maybe_content_url, true).await;
-        }
-    }
-
-    Ok(())
-}
-
 async fn get_atom_content_url(links: &Vec<Link>) -> Option<String> {
     if links.len() == 1 {
         return Some(links.first().unwrap().href.clone());
@@ -510,52 +642,167 @@ async fn get_atom_content_url(links: &Vec<Link>) -> Option<String> {
     }).map(|lnk| lnk.clone().href);
 }
 
-async fn download_content(content_dir: &String,
+/// How to download the content of feed items.
+struct ContentFetch<'a> {
+    client: &'a FetchClient,
+    pool: &'a Pool<Postgres>,
+    profile: Option<&'a FetchProfile>,
+}
+
+impl ContentFetch<'_> {
+    /// Fetch with the feed's profile if it has one for the URL's origin, since the headless
+    /// browser can't carry credentials, otherwise render the page with playwright.
+    async fn fetch(&self, url: &str) -> Option<String> {
+        let applies = |profile: &&FetchProfile| Url::parse(url).map_or(false, |url| profile.applies_to(&url));
+        match self.profile.filter(|profile| !profile.is_empty()).filter(applies) {
+            Some(profile) => match self.client.get_with(url, Some(profile)).await {
+                Ok(response) => Some(response.text()),
+                Err(e) => {
+                    error!("Error fetching content: {}", e);
+                    None
+                }
+            },
+            None => playwright_fetch(url).await,
+        }
+    }
+
+    /// Fetch a document as it is served, since a headless browser would only render a viewer.
+    async fn fetch_response(&self, url: &str) -> Option<FetchResponse> {
+        match self.client.get_with(url, self.profile.filter(|profile| !profile.is_empty())).await {
+            Ok(response) => Some(response),
+            Err(e) => {
+                error!("Error fetching document: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Download an item's content to `content_dir`, returning whether it succeeded.
+///
+/// The metadata and text extracted from the content are saved to the news item `news_id`.
+async fn download_content(fetch: &ContentFetch<'_>,
+                          content_dir: &String,
+                          news_id: Option<uuid::Uuid>,
                           title_slug: &String,
                           maybe_content_url: Option<String>,
-                          extract_text: bool) {
+                          extract_text: bool) -> bool {
     match maybe_content_url {
         None => {
             error!("No content URL found for item: {}", title_slug.clone());
-            return;
+            return false;
         }
         Some(url) => {
             let valid_url = validate_url(&url).await;
             if !valid_url {
                 error!("Attempted download with invalid URL: {}", url);
-                return;
+                return false;
+            }
+
+            if let Some(kind) = DocumentKind::from_path(&url).filter(|kind| *kind != DocumentKind::Html) {
+                return download_document(fetch, content_dir, news_id, title_slug, &url, kind, extract_text).await;
             }
 
-            let maybe_content = playwright_fetch(&url).await;
+            let maybe_content = fetch.fetch(&url).await;
             match maybe_content {
                 None => {
                     error!("Unable to fetch content for item: {}", title_slug.clone());
-                    return;
+                    false
                 }
                 Some(content) => {
                     let safe_title_slug = safe_filename(&title_slug).await;
 
                     let content_file_path = format!("{}/{}.html", content_dir, safe_title_slug);
-                    let mut html_content_file: File = File::create(&content_file_path).expect("Unable to create html content file");
-                    let _ = html_content_file.write_all(content.as_bytes());
+                    if let Err(e) = tokio::fs::write(&content_file_path, &content).await {
+                        error!("Unable to save content of {}: {}", url, e);
+                        return false;
+                    }
+
+                    let base = Url::parse(&url).ok();
+                    let metadata = extract_metadata(&content, base.as_ref());
+                    if let Some(news_id) = &news_id {
+                        enrich::enrich_news(fetch.pool, news_id, &metadata).await;
+                    }
+                    let mut lead_image = metadata.image;
 
                     if extract_text {
-                        let extract_result = extract_text_from_str(content, true).await;
-                        match extract_result {
-                            Ok(c) => {
-                                let content_text_path = format!("{}/{}-content.txt", content_dir, safe_title_slug);
-                                let mut text_content_file: File = File::create(&content_text_path).expect("Unable to create text content file");
-                                let _ = text_content_file.write_all(c.as_bytes());
-                            }
-                            Err(_) => {}
+                        let rules = site_rules(fetch.pool, &url).await;
+                        let article = extract_article_with_rules(&content, base.as_ref(), rules.as_ref());
+                        let content_text_path = format!("{}/{}-content.txt", content_dir, safe_title_slug);
+                        if let Err(e) = tokio::fs::write(&content_text_path, &article.text).await {
+                            error!("Unable to save text of {}: {}", url, e);
                         }
+                        if let Some(news_id) = &news_id {
+                            enrich::save_news_text_stats(fetch.pool, news_id, &article.text).await;
+                            enrich::chunk_news(fetch.pool, news_id, &article.text).await;
+                            enrich::link_news(fetch.pool, news_id, &url, &article.links).await;
+                        }
+                        lead_image = article.lead_image;
+                    }
+                    if let Some(news_id) = &news_id {
+                        enrich::save_news_thumbnail(fetch.client, fetch.pool, news_id, lead_image.as_deref()).await;
                     }
+                    true
                 }
             }
         }
     }
 }
 
+/// The extraction rules for the host of `url`, if there are any.
+pub(crate) async fn site_rules(pool: &Pool<Postgres>, url: &str) -> Option<SiteRules> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.trim_start_matches("www.");
+    match db::extraction_rule_for_host(pool, host).await {
+        Ok(rule) => rule.map(|rule| rule.site_rules()),
+        Err(e) => {
+            error!("Error loading extraction rules for {}: {}", host, e);
+            None
+        }
+    }
+}
+
+/// Download a PDF, DOCX, EPUB, text or Markdown document, saving it as is alongside its text.
+async fn download_document(fetch: &ContentFetch<'_>,
+                           content_dir: &String,
+                           news_id: Option<uuid::Uuid>,
+                           title_slug: &String,
+                           url: &str,
+                           kind: DocumentKind,
+                           extract_text: bool) -> bool {
+    let Some(response) = fetch.fetch_response(url).await else {
+        error!("Unable to fetch document for item: {}", title_slug);
+        return false;
+    };
+    let safe_title_slug = safe_filename(title_slug).await;
+
+    let document_path = format!("{}/{}.{}", content_dir, safe_title_slug, kind.extension());
+    if let Err(e) = tokio::fs::write(&document_path, &response.body).await {
+        error!("Unable to save document {}: {}", url, e);
+        return false;
+    }
+
+    if extract_text {
+        let document = match extract_document_async(response.body.clone(), response.content_type.clone(), Some(url.to_string()), Url::parse(url).ok(), None).await {
+            Ok(document) => document,
+            Err(e) => {
+                error!("Unable to extract text of {}: {}", url, e);
+                return true;
+            }
+        };
+        let content_text_path = format!("{}/{}-content.txt", content_dir, safe_title_slug);
+        if let Err(e) = tokio::fs::write(&content_text_path, &document.text).await {
+            error!("Unable to save text of {}: {}", url, e);
+        }
+        if let Some(news_id) = &news_id {
+            enrich::enrich_news(fetch.pool, news_id, &document.metadata()).await;
+            enrich::save_news_text_stats(fetch.pool, news_id, &document.text).await;
+            enrich::chunk_news(fetch.pool, news_id, &document.text).await;
+        }
+    }
+    true
+}
+
 async fn safe_filename(orig: &String) -> &str {
     if orig.len() <= 100 {
         orig
@@ -564,17 +811,12 @@ async fn safe_filename(orig: &String) -> &str {
     }
 }
 
-async fn handle_rss_feed(dir_path: &str, feed_content: String, save_content_files: bool) -> Result<Channel, rss::Error> {
+async fn handle_rss_feed(dir_path: &str, feed_content: String) -> Result<Channel, rss::Error> {
     let feed_parsed = Channel::read_from(feed_content.as_bytes());
-    let parsed_file_path = format!("{}/{}", dir_path, "feed-parsed.json");
     match &feed_parsed {
         Ok(channel) => {
-            let feed_parsed_json = serde_json::to_string_pretty(&channel).expect("Unable to serialize RSS channel");
-            let mut feed_parsed_file: fs::File = fs::File::create(&parsed_file_path).expect("Unable to create feed parsed file");
-            feed_parsed_file.write_all(&feed_parsed_json.as_bytes()).expect("Unable to write feed parsed file");
-
-            if save_content_files {
-                save_rss_content(dir_path, &channel).await.expect("Unable to save RSS content");
+            if let Err(e) = write_json_file(dir_path, "feed-parsed.json", channel).await {
+                warn!("Unable to save parsed RSS feed: {}", e);
             }
         }
         Err(err) => warn!("Error parsing RSS feed: {}", err),
@@ -583,32 +825,6 @@ async fn handle_rss_feed(dir_path: &str, feed_content: String, save_content_file
     return feed_parsed;
 }
 
-async fn save_rss_content(dir_path: &str, channel: &Channel) -> Result<(), Error> {
-    // create "content" directory under dir_path
-    let content_dir_path = format!("{}/{}", dir_path, "content");
-    fs::create_dir_all(&content_dir_path).expect("Unable to create content directory");
-
-    let items: Vec<Item> = channel.clone().items;
-    if items.len() == 0 {
-        error!("No items found in RSS channel: {:?}", channel);
-    } else {
-        for item in items {
-            match item.title {
-                None => {
-                    error!("No title found for item: {:?}", item);
-                    continue;
-                }
-                Some(title) => {
-                    let title_slug = slug::slugify(title.clone());
-                    let maybe_content_url = item.link.clone();
-                    download_content(&content_dir_path, &title_slug, maybe_content_url, true).await;
-                }
-            }
-        }
-    }
-    Ok(())
-}
-
 /// Using playwright, fetch the content of the URL
 #[allow(dead_code)]
 async fn playwright_fetch(url: &str) -> Option<String> {
@@ -0,0 +1,257 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use chacha20poly1305::aead::{Aead, AeadCore, OsRng};
+use reqwest::header::COOKIE;
+use reqwest::RequestBuilder;
+use url::{Origin, Url};
+
+use crate::models::FetchProfileRecord;
+
+/// How to authenticate against a source or feed.
+#[derive(Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum FetchAuth {
+    Basic { username: String, password: Option<String> },
+    Bearer { token: String },
+}
+
+impl FetchAuth {
+    fn name(&self) -> &'static str {
+        match self {
+            FetchAuth::Basic { .. } => "basic",
+            FetchAuth::Bearer { .. } => "bearer",
+        }
+    }
+}
+
+/// Credentials, cookies and extra headers to send when fetching a source or feed.
+///
+/// This is only ever held in memory in plaintext, it is stored encrypted and is never
+/// returned by the API. Use `FetchProfileSummary` to describe it to clients, and note
+/// that it intentionally does not implement `Debug` so it can't end up in the logs.
+#[derive(Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub(crate) struct FetchProfile {
+    #[serde(default)]
+    pub auth: Option<FetchAuth>,
+    /// Query parameters added to the URL, e.g. `token` for private podcast feeds.
+    #[serde(default)]
+    pub query_params: Vec<(String, String)>,
+    #[serde(default)]
+    pub cookies: Vec<(String, String)>,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    /// The origin of the feed or source the profile was loaded for. It is not stored, and a
+    /// profile without one is never applied.
+    #[serde(skip)]
+    pub origin: Option<Origin>,
+}
+
+impl FetchProfile {
+    /// Only apply the profile to requests for `origin`.
+    pub fn bound_to(self, origin: Origin) -> Self {
+        Self { origin: Some(origin), ..self }
+    }
+
+    /// Whether the profile may be applied to a request for `url`.
+    pub fn applies_to(&self, url: &Url) -> bool {
+        self.origin.as_ref() == Some(&url.origin())
+    }
+
+    /// Add the configured query parameters to `url`.
+    pub fn apply_to_url(&self, url: &mut Url) {
+        if self.query_params.is_empty() {
+            return;
+        }
+        let mut pairs = url.query_pairs_mut();
+        for (name, value) in &self.query_params {
+            pairs.append_pair(name, value);
+        }
+    }
+
+    /// Remove the configured query parameters from `url` again, so that tokens aren't
+    /// persisted or returned along with the fetched URL.
+    pub fn strip_from_url(&self, url: &mut Url) {
+        if self.query_params.is_empty() {
+            return;
+        }
+        let is_secret = |name: &str| self.query_params.iter().any(|(secret, _)| secret == name);
+        let remaining: Vec<(String, String)> = url.query_pairs()
+            .filter(|(name, _)| !is_secret(name))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        if remaining.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(remaining);
+        }
+    }
+
+    /// Add auth, cookies and headers to a request.
+    pub fn apply_to_request(&self, mut request: RequestBuilder) -> RequestBuilder {
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if !self.cookies.is_empty() {
+            let cookie = self.cookies.iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join("; ");
+            request = request.header(COOKIE, cookie);
+        }
+        match &self.auth {
+            Some(FetchAuth::Basic { username, password }) => request.basic_auth(username, password.as_ref()),
+            Some(FetchAuth::Bearer { token }) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Whether the profile adds nothing to a request.
+    pub fn is_empty(&self) -> bool {
+        self.auth.is_none() && self.query_params.is_empty() && self.cookies.is_empty() && self.headers.is_empty()
+    }
+}
+
+/// A redacted view of a `FetchProfile` that is safe to return from the API.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub(crate) struct FetchProfileSummary {
+    pub id: uuid::Uuid,
+    pub source_id: Option<uuid::Uuid>,
+    pub feed_id: Option<uuid::Uuid>,
+    pub auth_type: Option<String>,
+    pub query_param_names: Vec<String>,
+    pub cookie_names: Vec<String>,
+    pub header_names: Vec<String>,
+    pub update_timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl FetchProfileSummary {
+    pub fn new(record: &FetchProfileRecord, profile: &FetchProfile) -> Self {
+        let names = |pairs: &Vec<(String, String)>| pairs.iter().map(|(name, _)| name.clone()).collect();
+        Self {
+            id: record.id,
+            source_id: record.source_id,
+            feed_id: record.feed_id,
+            auth_type: profile.auth.as_ref().map(|auth| auth.name().to_string()),
+            query_param_names: names(&profile.query_params),
+            cookie_names: names(&profile.cookies),
+            header_names: names(&profile.headers),
+            update_timestamp: record.update_timestamp,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum CredentialsError {
+    #[error("credentials key must be 32 bytes encoded as base64")]
+    InvalidKey,
+
+    #[error("no credentials key configured")]
+    MissingKey,
+
+    #[error("unable to encrypt credentials")]
+    Encrypt,
+
+    #[error("unable to decrypt credentials")]
+    Decrypt,
+
+    #[error("invalid credentials payload: {0}")]
+    Payload(#[from] serde_json::Error),
+}
+
+/// The symmetric key used to encrypt fetch profiles at rest.
+#[derive(Clone)]
+pub(crate) struct CredentialsKey {
+    cipher: ChaCha20Poly1305,
+}
+
+impl std::fmt::Debug for CredentialsKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CredentialsKey(..)")
+    }
+}
+
+impl CredentialsKey {
+    pub fn from_base64(encoded: &str) -> Result<Self, CredentialsError> {
+        let bytes = STANDARD.decode(encoded.trim()).map_err(|_| CredentialsError::InvalidKey)?;
+        if bytes.len() != 32 {
+            return Err(CredentialsError::InvalidKey);
+        }
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&bytes));
+        Ok(Self { cipher })
+    }
+
+    /// Encrypt a profile, returning the nonce and the ciphertext.
+    pub fn encrypt(&self, profile: &FetchProfile) -> Result<(Vec<u8>, Vec<u8>), CredentialsError> {
+        let plaintext = serde_json::to_vec(profile)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher.encrypt(&nonce, plaintext.as_ref())
+            .map_err(|_| CredentialsError::Encrypt)?;
+        Ok((nonce.to_vec(), ciphertext))
+    }
+
+    pub fn decrypt(&self, record: &FetchProfileRecord) -> Result<FetchProfile, CredentialsError> {
+        if record.nonce.len() != 12 {
+            return Err(CredentialsError::Decrypt);
+        }
+        let plaintext = self.cipher.decrypt(Nonce::from_slice(&record.nonce), record.credentials.as_ref())
+            .map_err(|_| CredentialsError::Decrypt)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    pub(crate) fn token_profile() -> FetchProfile {
+        FetchProfile {
+            query_params: vec![("token".to_string(), "s3cret".to_string())],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_credentials_roundtrip() {
+        let key = CredentialsKey::from_base64(&STANDARD.encode([7u8; 32])).unwrap();
+        let profile = FetchProfile {
+            auth: Some(FetchAuth::Bearer { token: "abc".to_string() }),
+            cookies: vec![("session".to_string(), "xyz".to_string())],
+            ..token_profile()
+        };
+        let (nonce, credentials) = key.encrypt(&profile).unwrap();
+        assert!(!String::from_utf8_lossy(&credentials).contains("s3cret"));
+
+        let mut record = FetchProfileRecord {
+            id: uuid::Uuid::new_v4(),
+            source_id: None,
+            feed_id: None,
+            nonce,
+            credentials,
+            create_timestamp: Utc::now(),
+            update_timestamp: Utc::now(),
+        };
+        assert!(key.decrypt(&record).unwrap() == profile);
+
+        record.credentials[0] ^= 1;
+        assert!(matches!(key.decrypt(&record), Err(CredentialsError::Decrypt)));
+        assert!(matches!(CredentialsKey::from_base64("c2hvcnQ="), Err(CredentialsError::InvalidKey)));
+    }
+
+    #[test]
+    fn test_profile_strip_from_url() {
+        let profile = token_profile();
+        let mut url = Url::parse("https://example.com/feed.xml?page=2").unwrap();
+        profile.apply_to_url(&mut url);
+        assert_eq!(url.as_str(), "https://example.com/feed.xml?page=2&token=s3cret");
+
+        profile.strip_from_url(&mut url);
+        assert_eq!(url.as_str(), "https://example.com/feed.xml?page=2");
+
+        let mut url = Url::parse("https://example.com/feed.xml?token=s3cret").unwrap();
+        profile.strip_from_url(&mut url);
+        assert_eq!(url.as_str(), "https://example.com/feed.xml");
+    }
+}
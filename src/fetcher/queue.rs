@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio::sync::{Notify, Semaphore, watch};
+use url::Url;
+
+/// A cancellation signal shared by all the tasks of a `WorkQueue`.
+#[derive(Debug, Clone)]
+pub(crate) struct CancelToken {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        Self { sender: Arc::new(sender), receiver }
+    }
+
+    pub fn cancel(&self) {
+        let _ = self.sender.send(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once `cancel` has been called.
+    pub async fn cancelled(&self) {
+        let mut receiver = self.receiver.clone();
+        while !*receiver.borrow() {
+            if receiver.changed().await.is_err() {
+                // the sender is never dropped while a token exists, so this can't happen
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// A bounded-concurrency queue of fetch tasks.
+///
+/// At most `concurrency` tasks run at once, and at most `per_host` of those for any one
+/// host. A task waits for its host's permit before it takes a global permit, so a host with
+/// thousands of queued URLs can't starve the other hosts.
+///
+/// Cheap to clone, so tasks can queue more work, e.g. a feed task queueing its articles.
+#[derive(Debug, Clone)]
+pub(crate) struct WorkQueue {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    global: Semaphore,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+    per_host: usize,
+    pending: AtomicUsize,
+    idle: Notify,
+    cancel: CancelToken,
+}
+
+impl WorkQueue {
+    pub fn new(concurrency: usize, per_host: usize, cancel: CancelToken) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                global: Semaphore::new(concurrency.max(1)),
+                hosts: Mutex::new(HashMap::new()),
+                per_host: per_host.max(1),
+                pending: AtomicUsize::new(0),
+                idle: Notify::new(),
+                cancel,
+            }),
+        }
+    }
+
+    /// Queue `task` to run when a permit for the host of `url` is available.
+    ///
+    /// Tasks that haven't started when the queue is cancelled are dropped, running tasks
+    /// are dropped at their next await point.
+    pub fn spawn<F>(&self, url: &str, task: F)
+        where F: Future<Output=()> + Send + 'static
+    {
+        let host = host_key(url);
+        let host_permits = {
+            let mut hosts = self.inner.hosts.lock().unwrap();
+            hosts.entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.inner.per_host)))
+                .clone()
+        };
+
+        self.inner.pending.fetch_add(1, Ordering::SeqCst);
+        let queue = self.clone();
+        tokio::spawn(async move {
+            let cancel = queue.inner.cancel.clone();
+            // biased, so a task woken by the permits of a cancelled task sees the
+            // cancellation before it starts
+            tokio::select! {
+                biased;
+                _ = cancel.cancelled() => {},
+                _ = async {
+                    let _host_permit = host_permits.acquire().await;
+                    let _permit = queue.inner.global.acquire().await;
+                    if !cancel.is_cancelled() {
+                        task.await;
+                    }
+                } => {},
+            }
+            if queue.inner.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                queue.inner.idle.notify_waiters();
+            }
+        });
+    }
+
+    /// Wait until every queued task, including tasks queued by other tasks, has finished.
+    pub async fn wait(&self) {
+        loop {
+            let idle = self.inner.idle.notified();
+            if self.inner.pending.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+fn host_key(url: &str) -> String {
+    Url::parse(url).ok()
+        .and_then(|u| u.host_str().map(|host| host.trim_start_matches("www.").to_string()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_work_queue_per_host_limit() {
+        let queue = WorkQueue::new(8, 2, CancelToken::new());
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let other_done = Arc::new(AtomicUsize::new(0));
+        for i in 0..6 {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            queue.spawn(&format!("https://www.example.com/{}", i), async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        let done = other_done.clone();
+        queue.spawn("https://other.example.org/", async move {
+            done.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // the other host doesn't wait behind the queued example.com tasks
+        tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+        assert_eq!(other_done.load(Ordering::SeqCst), 1);
+
+        queue.wait().await;
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert_eq!(running.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_work_queue_cancel() {
+        let cancel = CancelToken::new();
+        let queue = WorkQueue::new(1, 1, cancel.clone());
+        let started = Arc::new(AtomicUsize::new(0));
+        let finished = Arc::new(AtomicUsize::new(0));
+        for i in 0..3 {
+            let started = started.clone();
+            let finished = finished.clone();
+            queue.spawn(&format!("https://example.com/{}", i), async move {
+                started.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                finished.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+        cancel.cancel();
+
+        tokio::time::timeout(std::time::Duration::from_secs(1), queue.wait()).await
+            .expect("cancelled queue didn't become idle");
+        assert!(cancel.is_cancelled());
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+    }
+}
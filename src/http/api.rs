@@ -1,12 +1,14 @@
-use std::{env, fmt, str::FromStr};
+use std::{env, fmt, fs, str::FromStr};
 
 use anyhow::Context;
 use axum::{Json, Router};
 use axum::extract::{DefaultBodyLimit, Multipart, Path, Query, State};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::{delete, get, post};
+use axum::http::{HeaderMap, StatusCode};
+use axum::http::header::{ETAG, IF_MATCH};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{delete, get, patch, post};
 use chrono::{Duration, Utc};
+use log::error;
 use newsapi::api::NewsAPIClient;
 use newsapi::constants::{Category, Language, SortMethod};
 use newsapi::payload::article::Articles;
@@ -14,10 +16,12 @@ use serde::{de, Deserialize, Deserializer};
 use serde_json::{json, Value};
 use tower_http::limit::RequestBodyLimitLayer;
 
-use crate::db;
-use crate::db::{garden_by_id, garden_by_slug, page_by_id, page_by_slug, pages_by_garden_slug, pages_by_garden_slug_and_type, source_type_by_id};
+use crate::{db, fetcher, import, newsletter};
+use crate::db::{garden_by_id, garden_by_slug, page_by_id, pages_by_garden_slug, pages_by_garden_slug_and_type, source_type_by_id};
+use crate::extractor::{chunk_text, extract_article_with_rules, extract_document, first_image, hashtags, html_to_markdown, text_stats, wiki_links, ChunkOptions};
 use crate::http::{ApiContext, Error, Result};
-use crate::models::{Company, Feed, Garden, NewsItem, Page, Source, SourceType, SourceTypePatch, Tool};
+use crate::http::render::{garden_page_path, render_content};
+use crate::models::{normalize_tag, Company, Feed, FeedPatch, Garden, NewsItem, Page, PagePatch, Source, SourceType, SourceTypePatch, TextFilter, Tool};
 use crate::tasks::CompanyPayload;
 
 pub(crate) fn router() -> Router<ApiContext> {
@@ -35,14 +39,25 @@ pub(crate) fn router() -> Router<ApiContext> {
         .route("/api/gardens/:slug", get(get_garden)
             .delete(delete_garden))
         .route("/api/gardens/:slug/pages", get(get_pages_by_garden_slug))
+        .route("/api/gardens/:slug/clips", post(post_garden_clip))
+        .route("/api/gardens/:slug/pages/:page", get(get_garden_page)
+            .patch(patch_garden_page)
+            .put(patch_garden_page)
+            .delete(delete_garden_page))
         .route("/api/news_api/:query", get(get_news_api))
         .route("/api/pages", get(get_pages).post(post_page))
 
-        .route("/api/pages/:slug", get(get_page).delete(delete_page))
+        .route("/api/pages/:slug", get(get_page)
+            .patch(patch_page)
+            .put(patch_page)
+            .delete(delete_page))
+        .route("/api/pages/:slug/chunks", get(get_page_chunks))
         .route("/api/feeds", get(get_feeds))
+        .route("/api/feeds/:id", patch(patch_feed))
         .route("/api/sources", get(get_sources))
         .route("/api/tools", get(get_tools))
         .route("/api/news", get(get_news))
+        .route("/api/news/:id/chunks", get(get_news_chunks))
         .route("/api/companies", get(get_companies)
             .post(post_company))
         .route("/api/companies/:id", delete(delete_company))
@@ -53,7 +68,24 @@ pub(crate) fn router() -> Router<ApiContext> {
         ))
 }
 
-async fn handle_upload(mut multipart: Multipart) {
+/// `garden` is the garden zipped vaults are imported into.
+#[derive(Debug, Deserialize)]
+struct UploadParams {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    garden: Option<String>,
+}
+
+async fn handle_upload(ctx: State<ApiContext>,
+                       Query(params): Query<UploadParams>,
+                       mut multipart: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut newsletters_saved = 0;
+    let mut newsletters_failed = 0;
+    let mut documents: Vec<Value> = Vec::new();
+    let mut documents_failed = 0;
+    let mut imports: Vec<Value> = Vec::new();
+    let mut imports_failed = 0;
+
     while let Some(field) = multipart.next_field().await.unwrap() {
         let name = field.name().unwrap().to_string();
         let file_name = field.file_name().unwrap().to_string();
@@ -67,7 +99,97 @@ async fn handle_upload(mut multipart: Multipart) {
             content_type,
             data.len()
         );
+
+        if is_email_upload(&file_name, &content_type) {
+            let (saved, failed) = newsletter::save_messages(&ctx.db, &data).await;
+            newsletters_saved += saved;
+            newsletters_failed += failed;
+        } else if is_vault_upload(&file_name, &content_type) {
+            let garden = params.garden.as_ref().ok_or_else(|| {
+                let error_response = json!({
+                    "status": "error",
+                    "message": "garden query parameter is required to import a zipped vault",
+                });
+                (StatusCode::BAD_REQUEST, Json(error_response))
+            })?;
+            // unzipping a large vault is CPU bound
+            let files = tokio::task::spawn_blocking(move || import::read_vault_zip(&data)).await;
+            let result = match files.map_err(anyhow::Error::from).and_then(|files| files) {
+                Ok(files) => import::import_vault(&ctx.db, garden, files).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(summary) => imports.push(json!(summary)),
+                Err(e) => {
+                    error!("Error importing uploaded vault {}: {:?}", file_name, e);
+                    imports_failed += 1;
+                }
+            }
+        } else {
+            // extracting the text of a large document is CPU bound
+            let upload = (file_name.clone(), content_type.clone());
+            let result = tokio::task::spawn_blocking(move || save_document_upload(&upload.0, &upload.1, &data)).await;
+            match result.map_err(anyhow::Error::from).and_then(|document| document) {
+                Ok(document) => documents.push(document),
+                Err(e) => {
+                    error!("Error saving uploaded document {}: {}", file_name, e);
+                    documents_failed += 1;
+                }
+            }
+        }
     }
+
+    Ok(Json(json!({"status": "success","data": json!({
+        "newsletters_saved": newsletters_saved,
+        "newsletters_failed": newsletters_failed,
+        "documents": documents,
+        "documents_failed": documents_failed,
+        "imports": imports,
+        "imports_failed": imports_failed,
+    })})))
+}
+
+/// Whether an uploaded file is an `.eml` message or an mbox of messages.
+fn is_email_upload(file_name: &str, content_type: &str) -> bool {
+    let file_name = file_name.to_lowercase();
+    file_name.ends_with(".eml")
+        || file_name.ends_with(".mbox")
+        || content_type == "message/rfc822"
+        || content_type == "application/mbox"
+}
+
+/// Whether an uploaded file is a zipped vault of Markdown notes.
+fn is_vault_upload(file_name: &str, content_type: &str) -> bool {
+    file_name.to_lowercase().ends_with(".zip")
+        || content_type == "application/zip"
+        || content_type == "application/x-zip-compressed"
+}
+
+/// Where uploaded documents and their extracted text are written.
+const UPLOAD_DIR: &str = "downloads/uploads";
+
+/// Save an uploaded PDF, DOCX, EPUB, text or Markdown file with its extracted text, and a
+/// `-document.json` of its metadata and text statistics, returning the latter.
+fn save_document_upload(file_name: &str, content_type: &str, data: &[u8]) -> anyhow::Result<Value> {
+    let document = extract_document(data, Some(content_type), Some(file_name), None, None)?;
+    let stem = file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem);
+    let file_slug: String = slug::slugify(stem).chars().take(100).collect();
+    fs::create_dir_all(UPLOAD_DIR)?;
+
+    let path = format!("{}/{}.{}", UPLOAD_DIR, file_slug, document.kind.extension());
+    fs::write(&path, data)?;
+    let text_path = format!("{}/{}-content.txt", UPLOAD_DIR, file_slug);
+    fs::write(&text_path, &document.text)?;
+
+    let summary = json!({
+        "file_name": file_name,
+        "path": path,
+        "text_path": text_path,
+        "document": document,
+        "stats": text_stats(&document.text),
+    });
+    fs::write(format!("{}/{}-document.json", UPLOAD_DIR, file_slug), serde_json::to_string_pretty(&summary)?)?;
+    Ok(summary)
 }
 
 #[derive(serde::Serialize)]
@@ -116,6 +238,27 @@ struct PagesBody {
 struct Params {
     #[serde(default, deserialize_with = "empty_string_as_none")]
     r#type: Option<String>,
+    /// ISO 639-3 code of the language, e.g. `eng`.
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    language: Option<String>,
+    /// Reading time bounds, in minutes.
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    min_reading_time: Option<i32>,
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    max_reading_time: Option<i32>,
+    /// Only pages with this tag.
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    tag: Option<String>,
+}
+
+impl Params {
+    fn text_filter(&self) -> TextFilter {
+        TextFilter {
+            language: self.language.clone(),
+            min_reading_time: self.min_reading_time,
+            max_reading_time: self.max_reading_time,
+        }
+    }
 }
 
 /// Serde deserialization decorator to map empty Strings to None,
@@ -136,10 +279,11 @@ async fn get_pages_by_garden_slug(ctx: State<ApiContext>,
                                   Path(slug): Path<String>,
                                   Query(params): Query<Params>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let filter = params.text_filter();
     let query_result: Result<Vec<Page>, sqlx::Error> = if params.r#type.is_some() {
-        pages_by_garden_slug_and_type(&ctx.db, &slug, &params.r#type.unwrap()).await
+        pages_by_garden_slug_and_type(&ctx.db, &slug, &params.r#type.unwrap(), &filter, params.tag.as_deref()).await
     } else {
-        pages_by_garden_slug(&ctx.db, &slug).await
+        pages_by_garden_slug(&ctx.db, &slug, &filter, params.tag.as_deref()).await
     };
 
     return match query_result {
@@ -190,8 +334,9 @@ async fn get_news_api(ctx: State<ApiContext>,
 
 }
 
-async fn get_pages(ctx: State<ApiContext>) -> Result<Json<PagesBody>> {
-    let pages = db::pages(&ctx.db).await.context("Failed to get pages").unwrap();
+async fn get_pages(ctx: State<ApiContext>,
+                   Query(params): Query<Params>) -> Result<Json<PagesBody>> {
+    let pages = db::pages(&ctx.db, &params.text_filter()).await.context("Failed to get pages").unwrap();
     Ok(Json(PagesBody {
         pages,
     }))
@@ -211,8 +356,9 @@ async fn get_feeds(ctx: State<ApiContext>) -> Result<Json<FeedsBody>> {
     }))
 }
 
-async fn get_news(ctx: State<ApiContext>) -> Result<Json<NewsBody>> {
-    let news = db::news(&ctx.db).await.context("Failed to get news").unwrap();
+async fn get_news(ctx: State<ApiContext>,
+                  Query(params): Query<Params>) -> Result<Json<NewsBody>> {
+    let news = db::news(&ctx.db, &params.text_filter()).await.context("Failed to get news").unwrap();
     Ok(Json(NewsBody {
         news,
     }))
@@ -317,6 +463,10 @@ async fn post_company(
             let payload_bytes = serde_json::to_vec(&json!(payload)).expect("Failed to serialize CompanyPayload");
             ctx.nc.publish(subject.into(), payload_bytes).expect("Failed to publish company_created");
 
+            if company.url.as_deref().map_or(false, |url| !url.is_empty()) {
+                tokio::spawn(fetcher::try_enrich_company(ctx.fetcher.clone(), ctx.db.clone(), company.clone()));
+            }
+
             let company_response = json!({"status": "success","data": json!({
                 "company": company
             })});
@@ -348,30 +498,60 @@ struct CreatePageRequest {
     content: String,
     garden_id: uuid::Uuid,
     published: bool,
-    slug: String,
+    /// Generated from the title when missing, with a `-2`, `-3`, ... suffix if another page of
+    /// the garden has it.
+    #[serde(default)]
+    slug: Option<String>,
     page_type: String,
+    author: Option<String>,
+}
+
+/// Make a slug URL safe, e.g. `My Page` becomes `my-page`.
+fn normalize_slug(slug: &str) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    let normalized = slug::slugify(slug);
+    if normalized.is_empty() {
+        let error_response = json!({
+            "status": "error",
+            "message": format!("invalid slug: {}", slug),
+        });
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    Ok(normalized)
 }
 
 async fn post_page(
     ctx: State<ApiContext>,
     Json(body): Json<CreatePageRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let slug = match body.slug.as_deref().filter(|slug| !slug.trim().is_empty()) {
+        Some(slug) => normalize_slug(slug)?,
+        None => db::unique_page_slug(&ctx.db, &body.garden_id, &normalize_slug(&body.title)?, None).await
+            .map_err(|e| (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"status": "error","message": format!("{:?}", e)})),
+            ))?,
+    };
     let query_result = db::save_page(&ctx.db,
                                      &body.title,
                                      &body.content,
                                      &body.garden_id,
                                      &body.published,
-                                     &body.slug,
-                                     &body.page_type
+                                     &slug,
+                                     &body.page_type,
+                                     &text_stats(&body.content),
+                                     body.author.as_deref(),
     ).await;
 
     return match query_result {
         Ok(page) => {
+            index_page_content(&ctx, &page).await;
+            link_page(&ctx, &page).await;
+
             let page_response = json!({"status": "success","data": json!({
                 "page": page
             })});
 
-            Ok((StatusCode::CREATED, Json(page_response)))
+            Ok((StatusCode::CREATED, [(ETAG, page.etag())], Json(page_response)))
         }
         Err(e) => {
             if e.to_string()
@@ -391,6 +571,259 @@ async fn post_page(
     };
 }
 
+#[derive(Debug, Deserialize)]
+struct ClipRequest {
+    url: String,
+    /// Taken from the webpage when missing.
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    published: bool,
+}
+
+/// Clip the main content of a webpage into a new page of the garden, converted to Markdown.
+async fn post_garden_clip(ctx: State<ApiContext>,
+                          Path(slug): Path<String>,
+                          Json(body): Json<ClipRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let error = |status: StatusCode, message: String| (status, Json(json!({"status": "error","message": message})));
+    let garden = garden_by_slug(&ctx.db, &slug).await
+        .map_err(|_| error(StatusCode::NOT_FOUND, format!("garden with slug: {} not found", slug)))?;
+
+    let profile = fetcher::load_fetch_profile(&ctx.fetcher, &ctx.db, &body.url, None).await;
+    let response = ctx.fetcher.get_with(&body.url, profile.as_ref()).await
+        .map_err(|e| error(StatusCode::BAD_GATEWAY, format!("{}", e)))?;
+    let base = url::Url::parse(&response.url).ok();
+    let rules = fetcher::site_rules(&ctx.db, &response.url).await;
+    let article = extract_article_with_rules(&response.text(), base.as_ref(), rules.as_ref());
+    let markdown = html_to_markdown(&article.content_html, base.as_ref());
+    if markdown.is_empty() {
+        return Err(error(StatusCode::UNPROCESSABLE_ENTITY, format!("no content found at {}", response.url)));
+    }
+
+    let title = body.title.filter(|title| !title.trim().is_empty())
+        .or(article.title)
+        .unwrap_or_else(|| response.url.clone());
+    let content = format!("{}\n\nClipped from <{}>\n", markdown, response.url);
+    let slug = db::unique_page_slug(&ctx.db, &garden.id, &normalize_slug(&title)?, None).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?;
+    let page = db::save_page(&ctx.db,
+                             &title,
+                             &content,
+                             &garden.id,
+                             &body.published,
+                             &slug,
+                             &"clip".to_string(),
+                             &text_stats(&content),
+                             article.byline.as_deref(),
+    ).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?;
+    index_page_content(&ctx, &page).await;
+    link_page(&ctx, &page).await;
+
+    let page_response = json!({"status": "success","data": json!({
+        "page": page
+    })});
+    Ok((StatusCode::CREATED, [(ETAG, page.etag())], Json(page_response)))
+}
+
+/// Chunk the content of a new or edited page, tag it with its hashtags and thumbnail its
+/// first image.
+pub(crate) async fn index_page_content(ctx: &ApiContext, page: &Page) {
+    let chunks = chunk_text(&page.content, &ChunkOptions::default());
+    if let Err(e) = db::save_page_chunks(&ctx.db, &page.id, &chunks).await {
+        error!("Error saving chunks of page {}: {}", page.id, e);
+    }
+    let tags: Vec<String> = hashtags(&page.content).iter()
+        .filter_map(|tag| normalize_tag(tag))
+        .collect();
+    if let Err(e) = db::save_page_hashtags(&ctx.db, &page.id, &tags).await {
+        error!("Error saving hashtags of page {}: {}", page.id, e);
+    }
+    if let Some(image_url) = first_image(&page.content) {
+        tokio::spawn(fetcher::save_page_thumbnail(ctx.fetcher.clone(), ctx.db.clone(), page.id, image_url));
+    }
+}
+
+/// Sync the wiki-links of a new or edited page, and point links that were waiting for a page
+/// with its slug or title at it.
+pub(crate) async fn link_page(ctx: &ApiContext, page: &Page) {
+    if let Err(e) = db::save_page_links(&ctx.db, page, &wiki_links(&page.content)).await {
+        error!("Error saving links of page {}: {}", page.id, e);
+    }
+    if let Err(e) = db::resolve_page_links(&ctx.db, page).await {
+        error!("Error resolving links to page {}: {}", page.id, e);
+    }
+}
+
+/// Whether an `If-Match` header value matches `etag`, either exactly, as one of a list, or `*`.
+pub(crate) fn etag_matches(if_match: &str, etag: &str) -> bool {
+    if_match.split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Names the garden of the page a route's slug refers to, e.g. `/api/pages/about/tags?garden=notes`,
+/// for when pages of several gardens have the slug.
+#[derive(Debug, Deserialize)]
+pub(crate) struct GardenScope {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    garden: Option<String>,
+}
+
+/// The page with a slug, for the routes that don't name the garden in their path. Without a
+/// `garden` in `scope` it fails with `409 Conflict` when pages of several gardens have the slug.
+pub(crate) async fn find_page(ctx: &ApiContext, scope: &GardenScope, slug: &String) -> Result<Page, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(garden) = &scope.garden {
+        return find_garden_page(ctx, garden, slug).await?
+            .ok_or_else(|| garden_page_not_found(garden, slug));
+    }
+    let mut pages = db::pages_by_slug(&ctx.db, slug).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"status": "error","message": format!("{:?}", e)})),
+    ))?;
+    match pages.len() {
+        0 => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"status": "error","message": format!("page with slug: {} not found", slug)})),
+        )),
+        1 => Ok(pages.remove(0)),
+        _ => Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "status": "error",
+                "message": format!("pages of several gardens have slug: {}, name the garden with ?garden= or use /api/gardens/:garden/pages/{}", slug, slug),
+            })),
+        )),
+    }
+}
+
+/// The page with a slug in a garden.
+async fn find_garden_page(ctx: &ApiContext, garden: &String, slug: &String) -> Result<Option<Page>, (StatusCode, Json<serde_json::Value>)> {
+    db::garden_page(&ctx.db, garden, slug).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"status": "error","message": format!("{:?}", e)})),
+    ))
+}
+
+fn garden_page_not_found(garden: &String, slug: &String) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = json!({
+        "status": "error",
+        "message": format!("page with slug: {} not found in garden: {}", slug, garden)
+    });
+    (StatusCode::NOT_FOUND, Json(error_response))
+}
+
+/// Update some or all of the title, content, published flag, slug and type of a page.
+///
+/// Requires the page's current `ETag` in `If-Match`, so that an edit based on an outdated copy
+/// of the page fails with `412 Precondition Failed` instead of overwriting someone else's edit.
+///
+/// Slugs the page had before aren't followed, only `/api/gardens/:garden/pages/:page` redirects
+/// them.
+async fn patch_page(
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+    Query(scope): Query<GardenScope>,
+    headers: HeaderMap,
+    Json(patch): Json<PagePatch>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let page = find_page(&ctx, &scope, &slug).await?;
+    apply_page_patch(&ctx, page, &headers, patch).await
+}
+
+/// Update a page of a garden. A slug the page had before is redirected to its current one with
+/// `308 Permanent Redirect`, so the client repeats the update there.
+async fn patch_garden_page(
+    ctx: State<ApiContext>,
+    Path((garden, slug)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(patch): Json<PagePatch>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    match find_garden_page(&ctx, &garden, &slug).await? {
+        Some(page) => apply_page_patch(&ctx, page, &headers, patch).await.map(IntoResponse::into_response),
+        None => garden_page_redirect(&ctx, &garden, &slug, "").await,
+    }
+}
+
+async fn apply_page_patch(
+    ctx: &ApiContext,
+    page: Page,
+    headers: &HeaderMap,
+    patch: PagePatch,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let slug = page.slug.clone();
+    let etag = page.etag();
+    let precondition_failed = || {
+        let error_response = json!({
+            "status": "error",
+            "message": format!("page with slug: {} has been modified", slug),
+        });
+        (StatusCode::PRECONDITION_FAILED, Json(error_response))
+    };
+    match headers.get(IF_MATCH).and_then(|value| value.to_str().ok()) {
+        Some(if_match) if etag_matches(if_match, &etag) => {}
+        Some(_) => return Err(precondition_failed()),
+        None => {
+            let error_response = json!({
+                "status": "error",
+                "message": "If-Match header with the page's ETag is required",
+            });
+            return Err((StatusCode::PRECONDITION_REQUIRED, Json(error_response)));
+        }
+    }
+
+    let new_slug = match patch.slug.as_deref() {
+        Some(new_slug) => normalize_slug(new_slug)?,
+        None => page.slug,
+    };
+    let content_changed = patch.content.as_ref().map_or(false, |content| *content != page.content);
+    let content = patch.content.unwrap_or(page.content);
+    let query_result = db::update_page(&ctx.db,
+                                       &page.id,
+                                       &page.update_timestamp,
+                                       &patch.title.unwrap_or(page.title),
+                                       &content,
+                                       &patch.published.unwrap_or(page.published),
+                                       &new_slug,
+                                       &patch.page_type.unwrap_or(page.page_type),
+                                       &text_stats(&content),
+                                       patch.author.as_deref(),
+                                       None,
+    ).await;
+
+    return match query_result {
+        Ok(Some(page)) => {
+            if content_changed {
+                index_page_content(ctx, &page).await;
+            }
+            link_page(ctx, &page).await;
+
+            let page_response = json!({"status": "success","data": json!({
+                "page": page
+            })});
+            Ok(([(ETAG, page.etag())], Json(page_response)))
+        }
+        // changed between reading and updating it
+        Ok(None) => Err(precondition_failed()),
+        Err(e) => {
+            if e.to_string()
+                .contains("duplicate key value violates unique constraint")
+            {
+                let error_response = serde_json::json!({
+                    "status": "error",
+                    "message": "page with this title or slug already exists",
+                });
+                return Err((StatusCode::CONFLICT, Json(error_response)));
+            }
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"status": "error","message": format!("{:?}", e)})),
+            ))
+        }
+    };
+}
+
 async fn post_source_types(
     ctx: State<ApiContext>,
     Json(body): Json<CreateSourceTypeRequest>,
@@ -443,24 +876,107 @@ async fn get_garden(ctx: State<ApiContext>,
     };
 }
 
+/// `format=html` adds the content rendered to sanitized HTML, and its table of contents.
+#[derive(Debug, Deserialize)]
+struct PageParams {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    format: Option<String>,
+}
+
 async fn get_page(ctx: State<ApiContext>,
                   Path(slug): Path<String>,
+                  Query(scope): Query<GardenScope>,
+                  Query(params): Query<PageParams>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query_result = page_by_slug(&ctx.db, &slug).await;
+    let page = find_page(&ctx, &scope, &slug).await?;
+    page_response(&ctx, page, &params).await
+}
 
-    return match query_result {
-        Ok(page) => {
-            let response = json!(page);
-            Ok(Json(response))
+/// A page of a garden. A slug the page had before is permanently redirected to its current one.
+async fn get_garden_page(ctx: State<ApiContext>,
+                         Path((garden, slug)): Path<(String, String)>,
+                         Query(params): Query<PageParams>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(page) = find_garden_page(&ctx, &garden, &slug).await? {
+        return page_response(&ctx, page, &params).await.map(IntoResponse::into_response);
+    }
+    let query = match params.format.as_deref() {
+        Some(format @ ("html" | "markdown")) => format!("?format={}", format),
+        _ => String::new(),
+    };
+    garden_page_redirect(&ctx, &garden, &slug, &query).await
+}
+
+/// A permanent redirect from a slug a page of the garden had before to its current slug, or
+/// `404 Not Found` when no page of the garden had it. The redirect is a `308`, which keeps the
+/// method and body of the request.
+async fn garden_page_redirect(ctx: &ApiContext, garden: &String, slug: &String, query: &str) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let current_slug = db::page_slug_alias(&ctx.db, garden, slug).await
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        ))?
+        .ok_or_else(|| garden_page_not_found(garden, slug))?;
+    let location = format!("{}{}", garden_page_path(garden, &current_slug), query);
+    Ok(Redirect::permanent(&location).into_response())
+}
+
+async fn page_response(ctx: &ApiContext,
+                       page: Page,
+                       params: &PageParams,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let mut response = json!(page);
+    match params.format.as_deref() {
+        None | Some("markdown") => {}
+        Some("html") => {
+            let rendered = render_content(&ctx.db, Some(&page.garden_id), &page.content).await
+                .map_err(|e| (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"status": "error","message": format!("{:?}", e)})),
+                ))?;
+            response["html"] = json!(rendered.html);
+            response["toc"] = json!(rendered.toc);
         }
-        Err(_) => {
+        Some(format) => {
             let error_response = json!({
                 "status": "error",
-                "message": format!("page with slug: {} not found", slug)
+                "message": format!("unsupported format: {}, expected html or markdown", format)
             });
-            Err((StatusCode::NOT_FOUND, Json(error_response)))
+            return Err((StatusCode::BAD_REQUEST, Json(error_response)));
         }
-    };
+    }
+    Ok(([(ETAG, page.etag())], Json(response)))
+}
+
+async fn get_page_chunks(ctx: State<ApiContext>,
+                         Path(slug): Path<String>,
+                         Query(scope): Query<GardenScope>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let page = find_page(&ctx, &scope, &slug).await?;
+
+    match db::page_chunks(&ctx.db, &page.id).await {
+        Ok(chunks) => Ok(Json(json!({"status": "success","data": json!({
+            "chunks": chunks
+        })}))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        )),
+    }
+}
+
+async fn get_news_chunks(ctx: State<ApiContext>,
+                         Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match db::news_chunks(&ctx.db, &id).await {
+        Ok(chunks) => Ok(Json(json!({"status": "success","data": json!({
+            "chunks": chunks
+        })}))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        )),
+    }
 }
 
 async fn get_source_type(ctx: State<ApiContext>,
@@ -516,20 +1032,74 @@ async fn patch_source_type(
     };
 }
 
+async fn patch_feed(
+    ctx: State<ApiContext>,
+    Path(id): Path<uuid::Uuid>,
+    Json(patch): Json<FeedPatch>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let query_result = db::feed_by_id(&ctx.db, &id).await;
+
+    if query_result.is_err() {
+        let error_response = json!({
+            "status": "error",
+            "message": format!("feed with id: {} not found", id)
+        });
+        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    }
+
+    let feed = query_result.unwrap();
+    let full_text = patch.full_text.unwrap_or(feed.full_text);
+
+    let query_result = db::update_feed_full_text(&ctx.db, &feed.id, &full_text).await;
+
+    return match query_result {
+        Ok(feed) => {
+            let response = json!({"status": "success","feed": feed});
+            Ok(Json(response))
+        }
+        Err(err) => {
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"status": "error", "message": format!("{:?}", err)})),
+            ))
+        }
+    };
+}
+
+/// Delete a page. Like updates, this doesn't follow slugs the page had before.
 async fn delete_page(
     ctx: State<ApiContext>,
     Path(slug): Path<String>,
+    Query(scope): Query<GardenScope>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let rows_affected = sqlx::query!("DELETE FROM page WHERE slug = $1", slug)
-        .execute(&ctx.db)
-        .await
-        .unwrap()
+    let page = find_page(&ctx, &scope, &slug).await?;
+    remove_page(&ctx, &page).await
+}
+
+/// Delete a page of a garden. A slug the page had before is redirected to its current one, like
+/// updates are.
+async fn delete_garden_page(
+    ctx: State<ApiContext>,
+    Path((garden, slug)): Path<(String, String)>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    match find_garden_page(&ctx, &garden, &slug).await? {
+        Some(page) => remove_page(&ctx, &page).await.map(IntoResponse::into_response),
+        None => garden_page_redirect(&ctx, &garden, &slug, "").await,
+    }
+}
+
+async fn remove_page(ctx: &ApiContext, page: &Page) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let rows_affected = db::delete_page(&ctx.db, &page.id).await
+        .map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        ))?
         .rows_affected();
 
     if rows_affected == 0 {
         let error_response = serde_json::json!({
             "status": "error",
-            "message": format!("page with slug: {} not found", slug)
+            "message": format!("page with slug: {} not found", page.slug)
         });
         return Err((StatusCode::NOT_FOUND, Json(error_response)));
     }
@@ -601,3 +1171,31 @@ async fn delete_company(
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::models::tests::page;
+
+    use super::*;
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = page().etag();
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(etag_matches(&format!("\"other\", {}", etag), &etag));
+        assert!(etag_matches(&format!("\"other\",{} ,\"more\"", etag), &etag));
+        assert!(!etag_matches("\"other\"", &etag));
+        assert!(!etag_matches("", &etag));
+        // If-Match uses the strong comparison, a weak tag never matches
+        assert!(!etag_matches(&format!("W/{}", etag), &etag));
+    }
+
+    #[test]
+    fn test_normalize_slug() {
+        assert_eq!(normalize_slug("My Page").unwrap(), "my-page");
+        assert_eq!(normalize_slug("  Ünïcode & Co.  ").unwrap(), "unicode-co");
+        assert_eq!(normalize_slug("already-a-slug").unwrap(), "already-a-slug");
+        assert!(normalize_slug("").is_err());
+        assert!(normalize_slug("?!").is_err());
+    }
+}
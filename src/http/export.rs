@@ -0,0 +1,66 @@
+use axum::{Json, Router};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::post;
+use chrono::Utc;
+use serde_json::json;
+
+use crate::artifacts::artifact_location;
+use crate::db;
+use crate::export::{export_garden, ExportFormat, ExportOptions};
+use crate::http::ApiContext;
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/gardens/:slug/export", post(post_export))
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct ExportRequest {
+    format: Option<ExportFormat>,
+    base_url: Option<String>,
+}
+
+/// Export the published pages of a garden as a static site into the artifact store, as a zip
+/// file by default, returning where it can be downloaded or browsed.
+async fn post_export(ctx: State<ApiContext>,
+                     Path(slug): Path<String>,
+                     body: Option<Json<ExportRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    if db::garden_by_slug(&ctx.db, &slug).await.is_err() {
+        let error_response = json!({
+            "status": "error",
+            "message": format!("garden with slug: {} not found", slug)
+        });
+        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    }
+
+    let body = body.map(|Json(body)| body).unwrap_or_default();
+    let options = ExportOptions {
+        base_url: body.base_url,
+        format: body.format.unwrap_or(ExportFormat::Zip),
+    };
+    let key = format!("exports/{}/{}", slug::slugify(&slug), Utc::now().format("%Y%m%d%H%M%S"));
+    let (output, url) = match options.format {
+        ExportFormat::Zip => artifact_location(&format!("{}.zip", key)),
+        ExportFormat::Directory => {
+            let (output, url) = artifact_location(&key);
+            (output, format!("{}/index.html", url))
+        }
+    };
+
+    match export_garden(&ctx.db, &slug, &output, &options).await {
+        Ok(summary) => Ok((StatusCode::CREATED, Json(json!({"status": "success","data": json!({
+            "garden": summary.garden,
+            "pages": summary.pages,
+            "files": summary.files,
+            "format": options.format,
+            "url": url,
+        })})))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        )),
+    }
+}
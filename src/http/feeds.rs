@@ -0,0 +1,320 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use axum::{Json, Router};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::http::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use chrono::{DateTime, Utc};
+use rss::{CategoryBuilder as RssCategoryBuilder, ChannelBuilder, GuidBuilder, ItemBuilder};
+use serde_json::json;
+use url::Url;
+
+use crate::db;
+use crate::export::{atom_feed, AtomEntry, AtomFeed};
+use crate::extractor::{absolute_url, render_markdown, resolve_wiki_link, sanitize_html};
+use crate::http::ApiContext;
+use crate::http::render::{garden_page_path, garden_path};
+use crate::models::{normalize_tag, Garden, Page};
+
+/// How many of the most recently updated pages a feed lists.
+const FEED_ENTRIES: i64 = 50;
+
+const ATOM_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+const RSS_CONTENT_TYPE: &str = "application/rss+xml; charset=utf-8";
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/gardens/:slug/feed.xml", get(get_atom_feed))
+        .route("/gardens/:slug/rss.xml", get(get_rss_feed))
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: String) -> ApiError {
+    (status, Json(json!({"status": "error","message": message})))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct FeedParams {
+    tag: Option<String>,
+    r#type: Option<String>,
+}
+
+/// The recently updated published pages of a garden, with their tags and rendered content.
+struct GardenFeed {
+    pub garden: Garden,
+    /// The root of the URLs in the feed, e.g. `https://example.com`, see `Config::public_url`.
+    /// Without it the URLs are relative to the server's root.
+    pub base: Option<Url>,
+    pub entries: Vec<FeedEntry>,
+    pub updated: DateTime<Utc>,
+}
+
+struct FeedEntry {
+    pub page: Page,
+    pub tags: Vec<String>,
+    pub html: String,
+}
+
+enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+/// An Atom feed of the recently published or updated pages of a garden.
+async fn get_atom_feed(ctx: State<ApiContext>,
+                       Path(slug): Path<String>,
+                       Query(params): Query<FeedParams>,
+                       headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    feed_response(&ctx, &slug, params, &headers, FeedFormat::Atom).await
+}
+
+/// An RSS 2.0 feed of the recently published or updated pages of a garden.
+async fn get_rss_feed(ctx: State<ApiContext>,
+                      Path(slug): Path<String>,
+                      Query(params): Query<FeedParams>,
+                      headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    feed_response(&ctx, &slug, params, &headers, FeedFormat::Rss).await
+}
+
+/// The feed, or `304 Not Modified` when the client's copy, identified by `If-None-Match` or
+/// `If-Modified-Since`, is still current. Pages are only rendered when the feed is sent.
+async fn feed_response(ctx: &ApiContext,
+                       slug: &String,
+                       params: FeedParams,
+                       headers: &HeaderMap,
+                       format: FeedFormat,
+) -> Result<Response, ApiError> {
+    let garden = match db::garden_by_slug(&ctx.db, slug).await {
+        Ok(garden) => garden,
+        Err(sqlx::Error::RowNotFound) => return Err(error(StatusCode::NOT_FOUND, format!("garden with slug: {} not found", slug))),
+        Err(e) => return Err(error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))),
+    };
+    let tag = params.tag.as_deref()
+        .map(|tag| normalize_tag(tag).ok_or_else(|| error(StatusCode::BAD_REQUEST, format!("invalid tag name: {}", tag))))
+        .transpose()?;
+    let page_type = params.r#type.as_deref().map(str::trim).filter(|page_type| !page_type.is_empty());
+    let pages = db::recent_published_pages(&ctx.db, &garden.id, page_type, tag.as_deref(), FEED_ENTRIES).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?;
+
+    let mut tags: HashMap<uuid::Uuid, Vec<String>> = HashMap::new();
+    for (page_id, name) in db::published_page_tags(&ctx.db, &garden.id).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))? {
+        tags.entry(page_id).or_default().push(name);
+    }
+
+    let updated = pages.first().map_or(garden.update_timestamp, |page| page.update_timestamp);
+    let etag = feed_etag(&garden, &pages, &tags, tag.as_deref(), page_type);
+    let last_modified = updated.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    if not_modified(headers, &etag, &updated) {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag), (LAST_MODIFIED, last_modified)]).into_response());
+    }
+
+    let base = ctx.config.public_url.as_deref().and_then(|url| Url::parse(url).ok());
+    let titles = db::page_titles(&ctx.db, &garden.id).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?;
+    let entries = pages.into_iter()
+        .map(|page| {
+            let rendered = render_markdown(&page.content, |link| resolve_wiki_link(&titles, link).map(|slug| garden_page_path(&garden.slug, &slug)));
+            FeedEntry {
+                tags: tags.remove(&page.id).unwrap_or_default(),
+                html: sanitize_html(&rendered.html, base.as_ref()),
+                page,
+            }
+        })
+        .collect();
+    let feed = GardenFeed { garden, base, entries, updated };
+
+    let (content_type, body) = match format {
+        FeedFormat::Atom => (ATOM_CONTENT_TYPE, atom(&feed)),
+        FeedFormat::Rss => (RSS_CONTENT_TYPE, rss(&feed)),
+    };
+    Ok(([(CONTENT_TYPE, content_type.to_string()), (ETAG, etag), (LAST_MODIFIED, last_modified)], body).into_response())
+}
+
+/// A weak entity tag of the feed, from the filters and the listed pages with their tags. Tagging
+/// a page doesn't update it, and unpublishing or deleting one can bring an older page into the
+/// feed, so the timestamp of the newest page alone doesn't tell whether the feed changed.
+fn feed_etag(garden: &Garden,
+                        pages: &[Page],
+                        tags: &HashMap<uuid::Uuid, Vec<String>>,
+                        tag: Option<&str>,
+                        page_type: Option<&str>,
+) -> String {
+    let mut hasher = DefaultHasher::new();
+    (&garden.title, garden.update_timestamp.timestamp_micros(), tag, page_type).hash(&mut hasher);
+    for page in pages {
+        (page.id, page.update_timestamp.timestamp_micros(), tags.get(&page.id)).hash(&mut hasher);
+    }
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Whether the client's copy of the feed is current. `If-None-Match` takes precedence over
+/// `If-Modified-Since`, which only has a precision of seconds.
+///
+/// `If-None-Match` uses the weak comparison, so tags match whether or not they are marked weak.
+fn not_modified(headers: &HeaderMap, etag: &str, updated: &DateTime<Utc>) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH).and_then(|value| value.to_str().ok()) {
+        let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        return if_none_match.split(',')
+            .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag));
+    }
+    headers.get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .is_some_and(|since| updated.timestamp() <= since.timestamp())
+}
+
+fn atom(feed: &GardenFeed) -> String {
+    atom_feed(&AtomFeed {
+        garden: &feed.garden,
+        url: absolute_url(feed.base.as_ref(), &format!("/gardens/{}/feed.xml", feed.garden.slug)),
+        home_url: absolute_url(feed.base.as_ref(), &garden_path(&feed.garden.slug)),
+        updated: feed.updated,
+        entries: feed.entries.iter()
+            .map(|entry| AtomEntry {
+                page: &entry.page,
+                url: absolute_url(feed.base.as_ref(), &garden_page_path(&feed.garden.slug, &entry.page.slug)),
+                tags: &entry.tags,
+                html: &entry.html,
+            })
+            .collect(),
+    })
+}
+
+fn rss(feed: &GardenFeed) -> String {
+    let items = feed.entries.iter()
+        .map(|entry| ItemBuilder::default()
+            .title(Some(entry.page.title.clone()))
+            .link(Some(absolute_url(feed.base.as_ref(), &garden_page_path(&feed.garden.slug, &entry.page.slug))))
+            .guid(Some(GuidBuilder::default()
+                .value(format!("urn:uuid:{}", entry.page.id))
+                .permalink(false)
+                .build()))
+            .pub_date(Some(entry.page.update_timestamp.to_rfc2822()))
+            .categories(entry.tags.iter()
+                .map(|tag| RssCategoryBuilder::default().name(tag.clone()).build())
+                .collect::<Vec<_>>())
+            .description(Some(entry.html.clone()))
+            .build())
+        .collect::<Vec<_>>();
+    ChannelBuilder::default()
+        .title(feed.garden.title.clone())
+        .link(absolute_url(feed.base.as_ref(), &garden_path(&feed.garden.slug)))
+        .description(feed.garden.title.clone())
+        .last_build_date(Some(feed.updated.to_rfc2822()))
+        .items(items)
+        .build()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::models::tests::{garden, page};
+
+    use super::*;
+
+    fn headers(name: axum::http::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_feed_etag() {
+        let garden = garden();
+        let pages = vec![page(), page()];
+        let mut tags = HashMap::new();
+        let etag = feed_etag(&garden, &pages, &tags, None, None);
+        assert!(etag.starts_with("W/\""));
+        assert_eq!(etag, feed_etag(&garden, &pages, &tags, None, None));
+
+        // a tagged page, a dropped page and other filters all change the feed
+        tags.insert(pages[0].id, vec!["cycling".to_string()]);
+        assert_ne!(etag, feed_etag(&garden, &pages, &tags, None, None));
+        tags.clear();
+        assert_ne!(etag, feed_etag(&garden, &pages[..1], &tags, None, None));
+        assert_ne!(etag, feed_etag(&garden, &pages, &tags, Some("cycling"), None));
+        assert_ne!(etag, feed_etag(&garden, &pages, &tags, None, Some("note")));
+    }
+
+    #[test]
+    fn test_feed_not_modified() {
+        let etag = "W/\"0123456789abcdef\"";
+        let updated = Utc.with_ymd_and_hms(2023, 6, 1, 12, 0, 0).unwrap();
+
+        assert!(!not_modified(&HeaderMap::new(), etag, &updated));
+        assert!(not_modified(&headers(IF_NONE_MATCH, etag), etag, &updated));
+        assert!(not_modified(&headers(IF_NONE_MATCH, "\"0123456789abcdef\""), etag, &updated));
+        assert!(not_modified(&headers(IF_NONE_MATCH, &format!("\"other\", {}", etag)), etag, &updated));
+        assert!(not_modified(&headers(IF_NONE_MATCH, "*"), etag, &updated));
+        assert!(!not_modified(&headers(IF_NONE_MATCH, "W/\"other\""), etag, &updated));
+
+        assert!(not_modified(&headers(IF_MODIFIED_SINCE, "Thu, 01 Jun 2023 12:00:00 GMT"), etag, &updated));
+        assert!(not_modified(&headers(IF_MODIFIED_SINCE, "Fri, 02 Jun 2023 12:00:00 GMT"), etag, &updated));
+        assert!(!not_modified(&headers(IF_MODIFIED_SINCE, "Thu, 01 Jun 2023 11:59:59 GMT"), etag, &updated));
+        assert!(!not_modified(&headers(IF_MODIFIED_SINCE, "yesterday"), etag, &updated));
+
+        // If-None-Match takes precedence
+        let mut both = headers(IF_NONE_MATCH, "W/\"other\"");
+        both.insert(IF_MODIFIED_SINCE, "Fri, 02 Jun 2023 12:00:00 GMT".parse().unwrap());
+        assert!(!not_modified(&both, etag, &updated));
+    }
+
+    fn garden_feed(base: Option<&str>) -> GardenFeed {
+        let page = page();
+        GardenFeed {
+            updated: page.update_timestamp,
+            entries: vec![FeedEntry {
+                page,
+                tags: vec!["cycling".to_string(), "politics".to_string()],
+                html: "<p>Bike <em>lanes</em></p>".to_string(),
+            }],
+            garden: garden(),
+            base: base.map(|base| Url::parse(base).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_atom_feed() {
+        let feed = garden_feed(Some("https://garden.example.com"));
+        let atom: atom_syndication::Feed = atom(&feed).parse().unwrap();
+
+        assert_eq!(atom.title().as_str(), "Streets");
+        assert_eq!(atom.id(), format!("urn:uuid:{}", feed.garden.id));
+        assert_eq!(atom.links()[0].href(), "https://garden.example.com/gardens/streets/feed.xml");
+        assert_eq!(atom.links()[0].rel(), "self");
+        assert_eq!(atom.entries().len(), 1);
+        let entry = &atom.entries()[0];
+        assert_eq!(entry.title().as_str(), "Title");
+        assert_eq!(entry.links()[0].href(), "https://garden.example.com/api/gardens/streets/pages/title");
+        assert_eq!(entry.id(), format!("urn:uuid:{}", feed.entries[0].page.id));
+        assert_eq!(entry.categories().iter().map(|category| category.term()).collect::<Vec<_>>(), vec!["cycling", "politics"]);
+        assert_eq!(entry.content().and_then(|content| content.value()), Some("<p>Bike <em>lanes</em></p>"));
+        assert_eq!(entry.updated(), &feed.entries[0].page.update_timestamp);
+    }
+
+    #[test]
+    fn test_rss_feed() {
+        let feed = garden_feed(None);
+        let channel: rss::Channel = rss(&feed).parse().unwrap();
+
+        assert_eq!(channel.title(), "Streets");
+        assert_eq!(channel.link(), "/api/gardens/streets");
+        assert_eq!(channel.items().len(), 1);
+        let item = &channel.items()[0];
+        assert_eq!(item.title(), Some("Title"));
+        assert_eq!(item.link(), Some("/api/gardens/streets/pages/title"));
+        assert_eq!(item.guid().map(|guid| guid.value().to_string()), Some(format!("urn:uuid:{}", feed.entries[0].page.id)));
+        assert_eq!(item.categories().len(), 2);
+        assert_eq!(item.description(), Some("<p>Bike <em>lanes</em></p>"));
+    }
+}
@@ -1,20 +1,27 @@
-use std::io::Error;
-
 use axum::{Json, Router};
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::get;
 use serde_json::json;
 use webpage::Webpage;
 
-use crate::fetcher::fetch_url;
+use crate::db;
+use crate::fetcher::{fetch_url, CredentialsError, FetchError, FetchProfile, FetchProfileSummary};
 use crate::http::{ApiContext, Result};
-use crate::models::{WebpageRequest, WebpageResponse};
+use crate::models::{FetchProfileRecord, WebpageRequest, WebpageResponse};
 
 pub(crate) fn router() -> Router<ApiContext> {
     Router::new()
         .route("/api/webpages", get(get_webpages).post(post_webpage))
+        .route("/api/sources/:id/fetch_profile",
+               get(get_source_fetch_profile)
+                   .put(put_source_fetch_profile)
+                   .delete(delete_source_fetch_profile))
+        .route("/api/feeds/:id/fetch_profile",
+               get(get_feed_fetch_profile)
+                   .put(put_feed_fetch_profile)
+                   .delete(delete_feed_fetch_profile))
 }
 
 #[derive(serde::Serialize)]
@@ -35,7 +42,7 @@ async fn get_webpages(ctx: State<ApiContext>) -> Result<Json<FetchBody>> {
 async fn post_webpage(ctx: State<ApiContext>,
                       Json(body): Json<WebpageRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let fetch_result: Result<Webpage, Error> = fetch_url(&body).await;
+    let fetch_result: Result<Webpage, FetchError> = fetch_url(&ctx.fetcher, &ctx.db, &body).await;
     return match fetch_result {
         Ok(webpage) => {
             let response = WebpageResponse::new(body, webpage);
@@ -49,4 +56,144 @@ async fn post_webpage(ctx: State<ApiContext>,
             ))
         }
     };
-}
\ No newline at end of file
+}
+
+/// Which kind of row a fetch profile belongs to.
+#[derive(Debug, Clone, Copy)]
+enum ProfileOwner {
+    Source,
+    Feed,
+}
+
+impl ProfileOwner {
+    fn name(&self) -> &'static str {
+        match self {
+            ProfileOwner::Source => "source",
+            ProfileOwner::Feed => "feed",
+        }
+    }
+}
+
+fn profile_not_found(owner: ProfileOwner, id: &uuid::Uuid) -> (StatusCode, Json<serde_json::Value>) {
+    let error_response = json!({
+        "status": "error",
+        "message": format!("fetch_profile for {} with id: {} not found", owner.name(), id)
+    });
+    (StatusCode::NOT_FOUND, Json(error_response))
+}
+
+fn credentials_error(e: CredentialsError) -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"status": "error","message": e.to_string()})),
+    )
+}
+
+async fn get_fetch_profile(ctx: &ApiContext,
+                           owner: ProfileOwner,
+                           id: uuid::Uuid,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let query_result = match owner {
+        ProfileOwner::Source => db::fetch_profile_by_source_id(&ctx.db, &id).await,
+        ProfileOwner::Feed => db::fetch_profile_by_feed_id(&ctx.db, &id).await,
+    };
+
+    let record: FetchProfileRecord = match query_result {
+        Ok(Some(record)) => record,
+        _ => return Err(profile_not_found(owner, &id)),
+    };
+
+    let profile = ctx.fetcher.credentials_key()
+        .and_then(|key| key.decrypt(&record))
+        .map_err(credentials_error)?;
+
+    Ok(Json(json!({"status": "success","fetch_profile": FetchProfileSummary::new(&record, &profile)})))
+}
+
+async fn put_fetch_profile(ctx: &ApiContext,
+                           owner: ProfileOwner,
+                           id: uuid::Uuid,
+                           profile: FetchProfile,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let (nonce, credentials) = ctx.fetcher.credentials_key()
+        .and_then(|key| key.encrypt(&profile))
+        .map_err(credentials_error)?;
+
+    let query_result = match owner {
+        ProfileOwner::Source => db::save_source_fetch_profile(&ctx.db, &id, &nonce, &credentials).await,
+        ProfileOwner::Feed => db::save_feed_fetch_profile(&ctx.db, &id, &nonce, &credentials).await,
+    };
+
+    return match query_result {
+        Ok(record) => {
+            let response = json!({"status": "success","fetch_profile": FetchProfileSummary::new(&record, &profile)});
+            Ok(Json(response))
+        }
+        Err(e) => {
+            if e.to_string().contains("violates foreign key constraint") {
+                let error_response = json!({
+                    "status": "error",
+                    "message": format!("{} with id: {} not found", owner.name(), id)
+                });
+                return Err((StatusCode::NOT_FOUND, Json(error_response)));
+            }
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"status": "error","message": format!("{:?}", e)})),
+            ))
+        }
+    };
+}
+
+async fn delete_fetch_profile(ctx: &ApiContext,
+                              owner: ProfileOwner,
+                              id: uuid::Uuid,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let query_result = match owner {
+        ProfileOwner::Source => db::delete_source_fetch_profile(&ctx.db, &id).await,
+        ProfileOwner::Feed => db::delete_feed_fetch_profile(&ctx.db, &id).await,
+    };
+
+    match query_result {
+        Ok(result) if result.rows_affected() > 0 => Ok(StatusCode::NO_CONTENT),
+        _ => Err(profile_not_found(owner, &id)),
+    }
+}
+
+async fn get_source_fetch_profile(ctx: State<ApiContext>,
+                                  Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    get_fetch_profile(&ctx, ProfileOwner::Source, id).await
+}
+
+async fn put_source_fetch_profile(ctx: State<ApiContext>,
+                                  Path(id): Path<uuid::Uuid>,
+                                  Json(profile): Json<FetchProfile>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    put_fetch_profile(&ctx, ProfileOwner::Source, id, profile).await
+}
+
+async fn delete_source_fetch_profile(ctx: State<ApiContext>,
+                                     Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    delete_fetch_profile(&ctx, ProfileOwner::Source, id).await
+}
+
+async fn get_feed_fetch_profile(ctx: State<ApiContext>,
+                                Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    get_fetch_profile(&ctx, ProfileOwner::Feed, id).await
+}
+
+async fn put_feed_fetch_profile(ctx: State<ApiContext>,
+                                Path(id): Path<uuid::Uuid>,
+                                Json(profile): Json<FetchProfile>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    put_fetch_profile(&ctx, ProfileOwner::Feed, id, profile).await
+}
+
+async fn delete_feed_fetch_profile(ctx: State<ApiContext>,
+                                   Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    delete_fetch_profile(&ctx, ProfileOwner::Feed, id).await
+}
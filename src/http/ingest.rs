@@ -0,0 +1,106 @@
+use axum::{Json, Router};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use serde_json::json;
+
+use crate::fetcher::{IngestSettings, spawn_opml_ingest};
+use crate::http::ApiContext;
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/ingest", get(list_ingest_jobs))
+        .route("/api/ingest/opml", post(post_opml_ingest))
+        .route("/api/ingest/:id", get(get_ingest_job).delete(cancel_ingest_job))
+}
+
+/// Either the URL of an OPML document or the document itself.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct OpmlIngestRequest {
+    url: Option<String>,
+    opml: Option<String>,
+}
+
+async fn post_opml_ingest(
+    ctx: State<ApiContext>,
+    Json(body): Json<OpmlIngestRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let (name, opml) = match (body.url, body.opml) {
+        (_, Some(opml)) => ("opml-upload".to_string(), opml),
+        (Some(url), None) => {
+            let fetch_result = ctx.fetcher.get(&url).await;
+            match fetch_result {
+                Ok(response) => (url, response.text()),
+                Err(e) => {
+                    return Err((
+                        StatusCode::BAD_GATEWAY,
+                        Json(json!({"status": "error","message": e.to_string()})),
+                    ));
+                }
+            }
+        }
+        (None, None) => {
+            let error_response = json!({
+                "status": "error",
+                "message": "either url or opml is required",
+            });
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(error_response)));
+        }
+    };
+
+    let job = spawn_opml_ingest(ctx.fetcher.clone(),
+                                ctx.db.clone(),
+                                Some(ctx.nc.clone()),
+                                name,
+                                opml,
+                                IngestSettings::from(ctx.config.as_ref()),
+    ).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"status": "error","message": format!("Unable to create ingest directory: {}", e)})),
+    ))?;
+    ctx.ingest_jobs.insert(job.clone());
+
+    let ingest_response = json!({"status": "success","data": json!({
+        "ingest": job.progress()
+    })});
+
+    Ok((StatusCode::ACCEPTED, Json(ingest_response)))
+}
+
+async fn list_ingest_jobs(ctx: State<ApiContext>) -> impl IntoResponse {
+    Json(json!({"ingests": ctx.ingest_jobs.list()}))
+}
+
+async fn get_ingest_job(ctx: State<ApiContext>,
+                        Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    return match ctx.ingest_jobs.get(&id) {
+        Some(job) => Ok(Json(json!(job.progress()))),
+        None => {
+            let error_response = json!({
+                "status": "error",
+                "message": format!("ingest with id: {} not found", id)
+            });
+            Err((StatusCode::NOT_FOUND, Json(error_response)))
+        }
+    };
+}
+
+async fn cancel_ingest_job(ctx: State<ApiContext>,
+                           Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    return match ctx.ingest_jobs.get(&id) {
+        Some(job) => {
+            job.cancel();
+            Ok(StatusCode::ACCEPTED)
+        }
+        None => {
+            let error_response = json!({
+                "status": "error",
+                "message": format!("ingest with id: {} not found", id)
+            });
+            Err((StatusCode::NOT_FOUND, Json(error_response)))
+        }
+    };
+}
@@ -0,0 +1,98 @@
+use axum::{Json, Router};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use serde_json::json;
+
+use crate::db;
+use crate::http::ApiContext;
+use crate::http::api::{find_page, GardenScope};
+use crate::models::{Citation, PageLink};
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/news/:id/cites", get(get_news_cites))
+        .route("/api/news/:id/cited_by", get(get_news_cited_by))
+        .route("/api/sources/:id/cites", get(get_source_cites))
+        .route("/api/sources/:id/cited_by", get(get_source_cited_by))
+        .route("/api/pages/:slug/backlinks", get(get_page_backlinks))
+        .route("/api/pages/:slug/outlinks", get(get_page_outlinks))
+        .route("/api/gardens/:slug/dangling_links", get(get_dangling_links))
+}
+
+fn citations_response(result: Result<Vec<Citation>, sqlx::Error>)
+                      -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match result {
+        Ok(citations) => Ok(Json(json!({"status": "success","data": json!({
+            "citations": citations
+        })}))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        )),
+    }
+}
+
+async fn get_news_cites(ctx: State<ApiContext>,
+                        Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    citations_response(db::news_cites(&ctx.db, &id).await)
+}
+
+async fn get_news_cited_by(ctx: State<ApiContext>,
+                           Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    citations_response(db::news_cited_by(&ctx.db, &id).await)
+}
+
+async fn get_source_cites(ctx: State<ApiContext>,
+                          Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    citations_response(db::source_cites(&ctx.db, &id).await)
+}
+
+async fn get_source_cited_by(ctx: State<ApiContext>,
+                             Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    citations_response(db::source_cited_by(&ctx.db, &id).await)
+}
+
+fn page_links_response(result: Result<Vec<PageLink>, sqlx::Error>)
+                       -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match result {
+        Ok(links) => Ok(Json(json!({"status": "success","data": json!({
+            "links": links
+        })}))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        )),
+    }
+}
+
+async fn page_id(ctx: &ApiContext, scope: &GardenScope, slug: &String) -> Result<uuid::Uuid, (StatusCode, Json<serde_json::Value>)> {
+    find_page(ctx, scope, slug).await.map(|page| page.id)
+}
+
+async fn get_page_backlinks(ctx: State<ApiContext>,
+                            Path(slug): Path<String>,
+                            Query(scope): Query<GardenScope>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let id = page_id(&ctx, &scope, &slug).await?;
+    page_links_response(db::page_backlinks(&ctx.db, &id).await)
+}
+
+async fn get_page_outlinks(ctx: State<ApiContext>,
+                           Path(slug): Path<String>,
+                           Query(scope): Query<GardenScope>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let id = page_id(&ctx, &scope, &slug).await?;
+    page_links_response(db::page_outlinks(&ctx.db, &id).await)
+}
+
+async fn get_dangling_links(ctx: State<ApiContext>,
+                            Path(slug): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    page_links_response(db::dangling_links(&ctx.db, &slug).await)
+}
@@ -1,4 +1,6 @@
+use crate::artifacts;
 use crate::config::Config;
+use crate::fetcher::{FetchClient, FetchOptions, IngestJobs};
 use anyhow::Context;
 use axum::Router;
 use sqlx::PgPool;
@@ -6,7 +8,7 @@ use std::{
     net::{Ipv4Addr, SocketAddr},
     sync::Arc,
 };
-use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::header::{ACCEPT, CONTENT_TYPE, ETAG, IF_MATCH};
 use axum::http::Method;
 use reqwest::header::AUTHORIZATION;
 use tower_http::cors::{Any, CorsLayer};
@@ -16,7 +18,17 @@ use tracing::Level;
 
 mod api;
 mod error;
+mod export;
+mod feeds;
 mod fetcher;
+mod ingest;
+mod links;
+mod reader;
+mod render;
+mod revisions;
+mod rules;
+mod search;
+mod tags;
 
 pub use error::{Error, ResultExt};
 
@@ -28,15 +40,22 @@ pub(crate) struct ApiContext {
     config: Arc<Config>,
     db: PgPool,
     nc: nats::Connection,
+    fetcher: FetchClient,
+    ingest_jobs: IngestJobs,
 }
 
 pub async fn serve(config: Config,
                    db: PgPool,
                    nc: nats::Connection) -> anyhow::Result<()> {
+    let fetcher = FetchClient::new(FetchOptions::from(&config))
+        .context("could not build fetch client")?;
+
     let api_context = ApiContext {
         config: Arc::new(config.clone()),
         db,
         nc,
+        fetcher,
+        ingest_jobs: IngestJobs::default(),
     };
 
     let app = api_router(api_context);
@@ -53,9 +72,10 @@ fn api_router(api_context: ApiContext) -> Router {
 
     // TODO for dev only
     let cors_layer = CorsLayer::new()
-        .allow_headers([ACCEPT, AUTHORIZATION, CONTENT_TYPE])
+        .allow_headers([ACCEPT, AUTHORIZATION, CONTENT_TYPE, IF_MATCH])
+        .expose_headers([ETAG])
         // .allow_credentials(true)
-        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
         .allow_origin(Any);
 
     let tracing_layer = TraceLayer::new_for_http()
@@ -66,6 +86,17 @@ fn api_router(api_context: ApiContext) -> Router {
 
     Router::new()
         .merge(fetcher::router())
+        .merge(export::router())
+        .merge(feeds::router())
+        .merge(ingest::router())
+        .merge(links::router())
+        .merge(reader::router())
+        .merge(render::router())
+        .merge(revisions::router())
+        .merge(rules::router())
+        .merge(search::router())
+        .merge(tags::router())
+        .merge(artifacts::router())
         .merge(api::router())
         .layer(tracing_layer)
         .layer(cors_layer)
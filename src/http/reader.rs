@@ -0,0 +1,73 @@
+use axum::{Json, Router};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use serde_json::json;
+use url::Url;
+
+use crate::db;
+use crate::extractor::sanitize_html;
+use crate::fetcher::extract_news;
+use crate::http::ApiContext;
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/news/:id/reader", get(get_reader_view))
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: String) -> ApiError {
+    (status, Json(json!({"status": "error","message": message})))
+}
+
+/// The article of a news item, sanitized so it can be shown inline.
+///
+/// Items that haven't been extracted yet are fetched and extracted first. Documents only
+/// have text, which is shown as plain paragraphs.
+async fn get_reader_view(ctx: State<ApiContext>,
+                         Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let not_found = || error(StatusCode::NOT_FOUND, format!("news item with id: {} not found", id));
+    let mut news_item = db::news_by_id(&ctx.db, &id).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?
+        .ok_or_else(not_found)?;
+
+    if news_item.content_html.is_none() && news_item.content_text.is_none() {
+        extract_news(&ctx.fetcher, &ctx.db, &news_item).await
+            .map_err(|e| error(StatusCode::BAD_GATEWAY, format!("unable to extract {}: {}", news_item.url, e)))?;
+        news_item = db::news_by_id(&ctx.db, &id).await
+            .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?
+            .ok_or_else(not_found)?;
+    }
+
+    let base = Url::parse(&news_item.url).ok();
+    let content_html = match (&news_item.content_html, &news_item.content_text) {
+        (Some(html), _) => sanitize_html(html, base.as_ref()),
+        (None, Some(text)) => text_to_html(text),
+        (None, None) => String::new(),
+    };
+
+    Ok(Json(json!({"status": "success","data": json!({
+        "id": news_item.id,
+        "url": news_item.url,
+        "title": news_item.title,
+        "byline": news_item.author,
+        "published": news_item.published_timestamp,
+        "language": news_item.language,
+        "word_count": news_item.word_count,
+        "reading_time": news_item.reading_time,
+        "thumbnail_url": news_item.thumbnail_url,
+        "content_html": content_html,
+    })})))
+}
+
+/// Escape plain text and wrap each paragraph in `<p>`.
+fn text_to_html(text: &str) -> String {
+    text.split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| format!("<p>{}</p>", ammonia::clean_text(paragraph)))
+        .collect()
+}
@@ -0,0 +1,83 @@
+use axum::{Json, Router};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::post;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+
+use crate::db;
+use crate::extractor::{render_markdown, resolve_wiki_link, Rendered};
+use crate::http::ApiContext;
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/render", post(post_render))
+}
+
+/// Characters left as they are in a path segment, the unreserved ones of RFC 3986.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/// Path of a garden, which its feeds point at.
+pub(crate) fn garden_path(garden: &str) -> String {
+    format!("/api/gardens/{}", utf8_percent_encode(garden, PATH_SEGMENT))
+}
+
+/// Path of a page of a garden, which wiki-links and feed entries point at.
+pub(crate) fn garden_page_path(garden: &str, slug: &str) -> String {
+    format!("{}/pages/{}", garden_path(garden), utf8_percent_encode(slug, PATH_SEGMENT))
+}
+
+/// Render page content, resolving wiki-links against the pages of `garden_id`. Without a garden,
+/// or with one that doesn't exist, every wiki-link is rendered as missing.
+pub(crate) async fn render_content(pool: &Pool<Postgres>, garden_id: Option<&uuid::Uuid>, content: &str) -> Result<Rendered, sqlx::Error> {
+    let garden = match garden_id {
+        Some(garden_id) => match db::garden_by_id(pool, garden_id).await {
+            Ok(garden) => Some(garden),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(e) => return Err(e),
+        },
+        None => None,
+    };
+    let pages = match &garden {
+        Some(garden) => db::page_titles(pool, &garden.id).await?,
+        None => vec![],
+    };
+    Ok(render_markdown(content, |link| resolve_wiki_link(&pages, link)
+        .zip(garden.as_ref())
+        .map(|(slug, garden)| garden_page_path(&garden.slug, &slug))))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RenderRequest {
+    content: String,
+    garden_id: Option<uuid::Uuid>,
+}
+
+/// Render Markdown the way page content is rendered, e.g. for previews while editing.
+async fn post_render(ctx: State<ApiContext>,
+                     Json(body): Json<RenderRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    match render_content(&ctx.db, body.garden_id.as_ref(), &body.content).await {
+        Ok(rendered) => Ok(Json(json!({"status": "success","data": json!({
+            "html": rendered.html,
+            "toc": rendered.toc,
+        })}))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"status": "error","message": format!("{:?}", e)})),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_garden_page_path() {
+        assert_eq!(garden_page_path("streets", "bike-lanes"), "/api/gardens/streets/pages/bike-lanes");
+        assert_eq!(garden_page_path("my garden", "a/b?c"), "/api/gardens/my%20garden/pages/a%2Fb%3Fc");
+    }
+}
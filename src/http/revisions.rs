@@ -0,0 +1,230 @@
+use axum::{Json, Router};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::http::header::{ETAG, IF_MATCH};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use serde_json::json;
+use similar::{ChangeTag, TextDiff};
+
+use crate::db;
+use crate::extractor::text_stats;
+use crate::http::ApiContext;
+use crate::http::api::{etag_matches, find_page, index_page_content, link_page, GardenScope};
+use crate::models::{Page, PageRevision};
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/pages/:slug/revisions", get(get_page_revisions))
+        .route("/api/pages/:slug/revisions/:number", get(get_page_revision))
+        .route("/api/pages/:slug/revisions/:number/revert", post(revert_page))
+        .route("/api/pages/:slug/diff", get(get_page_diff))
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: String) -> ApiError {
+    (status, Json(json!({"status": "error","message": message})))
+}
+
+async fn revision(ctx: &ApiContext, page: &Page, number: i32) -> Result<PageRevision, ApiError> {
+    db::page_revision(&ctx.db, &page.id, number).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, format!("revision {} of page with slug: {} not found", number, page.slug)))
+}
+
+async fn get_page_revisions(ctx: State<ApiContext>,
+                            Path(slug): Path<String>,
+                            Query(scope): Query<GardenScope>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = find_page(&ctx, &scope, &slug).await?;
+    let revisions = db::page_revisions(&ctx.db, &page.id).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?;
+    Ok(Json(json!({"status": "success","data": json!({
+        "revisions": revisions
+    })})))
+}
+
+async fn get_page_revision(ctx: State<ApiContext>,
+                           Path((slug, number)): Path<(String, i32)>,
+                           Query(scope): Query<GardenScope>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = find_page(&ctx, &scope, &slug).await?;
+    let revision = revision(&ctx, &page, number).await?;
+    Ok(Json(json!({"status": "success","data": json!({
+        "revision": revision
+    })})))
+}
+
+/// The revisions to compare, `to` defaults to the latest one.
+#[derive(Debug, serde::Deserialize)]
+struct DiffParams {
+    from: i32,
+    to: Option<i32>,
+}
+
+/// A line diff of the content of two revisions of a page, as a unified diff and line by line.
+async fn get_page_diff(ctx: State<ApiContext>,
+                       Path(slug): Path<String>,
+                       Query(scope): Query<GardenScope>,
+                       Query(params): Query<DiffParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = find_page(&ctx, &scope, &slug).await?;
+    let from = revision(&ctx, &page, params.from).await?;
+    let to = match params.to {
+        Some(number) => revision(&ctx, &page, number).await?,
+        None => db::page_revisions(&ctx.db, &page.id).await
+            .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| error(StatusCode::NOT_FOUND, format!("page with slug: {} has no revisions", slug)))?,
+    };
+
+    Ok(Json(json!({"status": "success","data": revision_diff(&from, &to)})))
+}
+
+/// The line diff of the content of two revisions.
+fn revision_diff(from: &PageRevision, to: &PageRevision) -> serde_json::Value {
+    let diff = TextDiff::from_lines(&from.content, &to.content);
+    let lines: Vec<serde_json::Value> = diff.iter_all_changes()
+        .map(|change| json!({
+            "tag": match change.tag() {
+                ChangeTag::Equal => "equal",
+                ChangeTag::Delete => "delete",
+                ChangeTag::Insert => "insert",
+            },
+            "old_line": change.old_index().map(|i| i + 1),
+            "new_line": change.new_index().map(|i| i + 1),
+            "value": change.value(),
+        }))
+        .collect();
+    let count = |tag| diff.iter_all_changes().filter(|change| change.tag() == tag).count();
+    let unified = diff.unified_diff()
+        .context_radius(3)
+        .header(&format!("revision {}", from.revision_number), &format!("revision {}", to.revision_number))
+        .to_string();
+
+    json!({
+        "from": from.revision_number,
+        "to": to.revision_number,
+        "title_changed": from.title != to.title,
+        "insertions": count(ChangeTag::Insert),
+        "deletions": count(ChangeTag::Delete),
+        "unified": unified,
+        "lines": lines,
+    })
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct RevertRequest {
+    author: Option<String>,
+}
+
+/// Restore the title and content of an old revision, saving them as a new revision so that
+/// the history in between is kept. An `If-Match` header is checked against the page's `ETag`.
+async fn revert_page(ctx: State<ApiContext>,
+                     Path((slug, number)): Path<(String, i32)>,
+                     Query(scope): Query<GardenScope>,
+                     headers: HeaderMap,
+                     body: Option<Json<RevertRequest>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = find_page(&ctx, &scope, &slug).await?;
+    let revision = revision(&ctx, &page, number).await?;
+    let precondition_failed = || error(StatusCode::PRECONDITION_FAILED, format!("page with slug: {} has been modified", slug));
+    if let Some(if_match) = headers.get(IF_MATCH).and_then(|value| value.to_str().ok()) {
+        if !etag_matches(if_match, &page.etag()) {
+            return Err(precondition_failed());
+        }
+    }
+
+    let author = body.and_then(|Json(body)| body.author);
+    let updated = db::update_page(&ctx.db,
+                                  &page.id,
+                                  &page.update_timestamp,
+                                  &revision.title,
+                                  &revision.content,
+                                  &page.published,
+                                  &page.slug,
+                                  &page.page_type,
+                                  &text_stats(&revision.content),
+                                  author.as_deref(),
+                                  Some(revision.revision_number),
+    ).await;
+
+    match updated {
+        Ok(Some(updated)) => {
+            if updated.content != page.content {
+                index_page_content(&ctx, &updated).await;
+            }
+            link_page(&ctx, &updated).await;
+            Ok(([(ETAG, updated.etag())], Json(json!({"status": "success","data": json!({
+                "page": updated
+            })}))))
+        }
+        Ok(None) => Err(precondition_failed()),
+        Err(e) if e.to_string().contains("duplicate key value violates unique constraint") =>
+            Err(error(StatusCode::CONFLICT, "page with this title already exists".to_string())),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn revision(number: i32, title: &str, content: &str) -> PageRevision {
+        PageRevision {
+            id: uuid::Uuid::new_v4(),
+            page_id: uuid::Uuid::nil(),
+            revision_number: number,
+            title: title.to_string(),
+            content: content.to_string(),
+            author: None,
+            reverted_from: None,
+            create_timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_revision_diff() {
+        let from = revision(1, "Title", "one\ntwo\nthree\n");
+        let to = revision(2, "New title", "one\n2\nthree\nfour\n");
+        let diff = revision_diff(&from, &to);
+
+        assert_eq!(diff["from"], 1);
+        assert_eq!(diff["to"], 2);
+        assert_eq!(diff["title_changed"], true);
+        assert_eq!(diff["insertions"], 2);
+        assert_eq!(diff["deletions"], 1);
+
+        let lines = diff["lines"].as_array().unwrap();
+        let tags: Vec<&str> = lines.iter().map(|line| line["tag"].as_str().unwrap()).collect();
+        assert_eq!(tags, vec!["equal", "delete", "insert", "equal", "insert"]);
+        assert_eq!(lines[1]["old_line"], 2);
+        assert!(lines[1]["new_line"].is_null());
+        assert_eq!(lines[2]["new_line"], 2);
+        assert!(lines[2]["old_line"].is_null());
+        assert_eq!(lines[4]["value"], "four\n");
+        assert_eq!(lines[4]["new_line"], 4);
+
+        let unified = diff["unified"].as_str().unwrap();
+        assert!(unified.starts_with("--- revision 1\n+++ revision 2\n"));
+        assert!(unified.contains("-two\n"));
+        assert!(unified.contains("+2\n"));
+        assert!(unified.contains("+four\n"));
+    }
+
+    #[test]
+    fn test_revision_diff_unchanged() {
+        let from = revision(3, "Title", "same\n");
+        let to = revision(4, "Title", "same\n");
+        let diff = revision_diff(&from, &to);
+
+        assert_eq!(diff["title_changed"], false);
+        assert_eq!(diff["insertions"], 0);
+        assert_eq!(diff["deletions"], 0);
+        assert_eq!(diff["unified"], "");
+    }
+}
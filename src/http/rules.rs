@@ -0,0 +1,171 @@
+use std::fs;
+
+use axum::{Json, Router};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use serde_json::json;
+use url::Url;
+
+use crate::db;
+use crate::extractor::{extract_article, extract_article_with_rules, SiteRules};
+use crate::fetcher::site_rules;
+use crate::http::ApiContext;
+use crate::models::ExtractionRulePayload;
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/extraction_rules", get(get_extraction_rules).post(post_extraction_rule))
+        .route("/api/extraction_rules/test", post(test_extraction_rules))
+        .route("/api/extraction_rules/:id",
+               get(get_extraction_rule)
+                   .put(put_extraction_rule)
+                   .delete(delete_extraction_rule))
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: String) -> ApiError {
+    (status, Json(json!({"status": "error","message": message})))
+}
+
+fn rule_not_found(id: &uuid::Uuid) -> ApiError {
+    error(StatusCode::NOT_FOUND, format!("extraction_rule with id: {} not found", id))
+}
+
+/// Lower case the domain and drop any scheme, path and `www.`, so that both `example.com` and
+/// `https://www.example.com/` are accepted.
+fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().to_lowercase();
+    let host = match Url::parse(&domain) {
+        Ok(url) if url.has_host() => url.host_str().unwrap_or_default().to_string(),
+        _ => domain.split('/').next().unwrap_or_default().to_string(),
+    };
+    let host = host.trim_start_matches("www.").to_string();
+    Some(host).filter(|host| !host.is_empty() && !host.contains(char::is_whitespace))
+}
+
+fn validate(payload: &ExtractionRulePayload) -> Result<String, ApiError> {
+    let domain = normalize_domain(&payload.domain)
+        .ok_or_else(|| error(StatusCode::BAD_REQUEST, format!("invalid domain: {}", payload.domain)))?;
+    payload.rules.validate()
+        .map_err(|selector| error(StatusCode::BAD_REQUEST, format!("invalid selector: {}", selector)))?;
+    Ok(domain)
+}
+
+fn save_error(e: sqlx::Error, domain: &str) -> ApiError {
+    if e.to_string().contains("unique_extraction_rule_domain") {
+        return error(StatusCode::CONFLICT, format!("extraction_rule for domain: {} already exists", domain));
+    }
+    error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))
+}
+
+async fn get_extraction_rules(ctx: State<ApiContext>) -> Result<impl IntoResponse, ApiError> {
+    let rules = db::extraction_rules(&ctx.db).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?;
+    Ok(Json(json!({"status": "success","data": json!({
+        "extraction_rules": rules
+    })})))
+}
+
+async fn post_extraction_rule(ctx: State<ApiContext>,
+                              Json(payload): Json<ExtractionRulePayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    let domain = validate(&payload)?;
+    let rule = db::save_extraction_rule(&ctx.db, &domain, &payload.rules).await
+        .map_err(|e| save_error(e, &domain))?;
+    Ok((StatusCode::CREATED, Json(json!({"status": "success","data": json!({
+        "extraction_rule": rule
+    })}))))
+}
+
+async fn get_extraction_rule(ctx: State<ApiContext>,
+                             Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    match db::extraction_rule_by_id(&ctx.db, &id).await {
+        Ok(Some(rule)) => Ok(Json(json!({"status": "success","data": json!({
+            "extraction_rule": rule
+        })}))),
+        Ok(None) => Err(rule_not_found(&id)),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))),
+    }
+}
+
+async fn put_extraction_rule(ctx: State<ApiContext>,
+                             Path(id): Path<uuid::Uuid>,
+                             Json(payload): Json<ExtractionRulePayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    let domain = validate(&payload)?;
+    match db::update_extraction_rule(&ctx.db, &id, &domain, &payload.rules).await {
+        Ok(Some(rule)) => Ok(Json(json!({"status": "success","data": json!({
+            "extraction_rule": rule
+        })}))),
+        Ok(None) => Err(rule_not_found(&id)),
+        Err(e) => Err(save_error(e, &domain)),
+    }
+}
+
+async fn delete_extraction_rule(ctx: State<ApiContext>,
+                                Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    match db::delete_extraction_rule(&ctx.db, &id).await {
+        Ok(result) if result.rows_affected() > 0 => Ok(StatusCode::NO_CONTENT),
+        Ok(_) => Err(rule_not_found(&id)),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))),
+    }
+}
+
+/// The page to test rules against: HTML given inline, the stored HTML of a news item, or a URL
+/// to fetch. `rules` tries out unsaved rules instead of the stored ones for the URL's domain.
+#[derive(Debug, serde::Deserialize)]
+struct TestRequest {
+    url: Option<String>,
+    html: Option<String>,
+    news_id: Option<uuid::Uuid>,
+    rules: Option<SiteRules>,
+}
+
+async fn test_extraction_rules(ctx: State<ApiContext>,
+                               Json(body): Json<TestRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut url = body.url.clone();
+    let html = match (body.html, body.news_id, &body.url) {
+        (Some(html), _, _) => html,
+        (None, Some(news_id), _) => {
+            let news_item = db::news_by_id(&ctx.db, &news_id).await
+                .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?
+                .ok_or_else(|| error(StatusCode::NOT_FOUND, format!("news item with id: {} not found", news_id)))?;
+            let path = news_item.raw_content_path
+                .ok_or_else(|| error(StatusCode::NOT_FOUND, format!("news item with id: {} has no stored HTML", news_id)))?;
+            url = url.or(Some(news_item.url));
+            fs::read_to_string(&path)
+                .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("unable to read {}: {}", path, e)))?
+        }
+        (None, None, Some(url)) => ctx.fetcher.get(url).await
+            .map_err(|e| error(StatusCode::BAD_GATEWAY, e.to_string()))?
+            .text(),
+        (None, None, None) => return Err(error(StatusCode::BAD_REQUEST, "one of url, html or news_id is required".to_string())),
+    };
+
+    let rules = match body.rules {
+        Some(rules) => {
+            rules.validate()
+                .map_err(|selector| error(StatusCode::BAD_REQUEST, format!("invalid selector: {}", selector)))?;
+            Some(rules)
+        }
+        None => match &url {
+            Some(url) => site_rules(&ctx.db, url).await,
+            None => None,
+        },
+    };
+
+    let base = url.as_deref().and_then(|url| Url::parse(url).ok());
+    let article = extract_article_with_rules(&html, base.as_ref(), rules.as_ref());
+    let heuristic = extract_article(&html, base.as_ref());
+    Ok(Json(json!({"status": "success","data": json!({
+        "rules": rules,
+        "article": article,
+        "heuristic": heuristic,
+    })})))
+}
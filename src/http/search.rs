@@ -0,0 +1,112 @@
+use axum::{Json, Router};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use serde_json::json;
+
+use crate::db;
+use crate::http::ApiContext;
+use crate::models::{SearchFilter, SEARCH_MARK_END, SEARCH_MARK_START};
+
+/// Kinds of search results, and the `type` filter values that select them.
+const SEARCH_KINDS: [&str; 3] = ["page", "news", "source"];
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/search", get(get_search))
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: String) -> ApiError {
+    (status, Json(json!({"status": "error","message": message})))
+}
+
+/// `type` is a comma separated list of kinds, `from` and `to` are dates (`2023-08-01`) or
+/// RFC 3339 timestamps, `to` being exclusive.
+#[derive(Debug, serde::Deserialize)]
+struct SearchParams {
+    #[serde(default)]
+    q: String,
+    r#type: Option<String>,
+    garden: Option<String>,
+    tag: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+fn parse_timestamp(name: &str, value: &str) -> Result<DateTime<Utc>, ApiError> {
+    DateTime::parse_from_rfc3339(value).map(|timestamp| timestamp.with_timezone(&Utc))
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(|date| Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())))
+        .map_err(|_| error(StatusCode::BAD_REQUEST, format!("invalid {}: {}, expected a date or RFC 3339 timestamp", name, value)))
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+impl SearchParams {
+    fn filter(self) -> Result<SearchFilter, ApiError> {
+        let kinds: Vec<String> = non_empty(self.r#type).iter()
+            .flat_map(|kinds| kinds.split(','))
+            .map(|kind| kind.trim().to_lowercase())
+            .filter(|kind| !kind.is_empty())
+            .collect();
+        if let Some(kind) = kinds.iter().find(|kind| !SEARCH_KINDS.contains(&kind.as_str())) {
+            return Err(error(StatusCode::BAD_REQUEST, format!("invalid type: {}, expected one of {}", kind, SEARCH_KINDS.join(", "))));
+        }
+        Ok(SearchFilter {
+            kinds,
+            garden: non_empty(self.garden),
+            tag: non_empty(self.tag).map(|tag| tag.trim_start_matches('#').to_string()),
+            from: non_empty(self.from).map(|from| parse_timestamp("from", &from)).transpose()?,
+            to: non_empty(self.to).map(|to| parse_timestamp("to", &to)).transpose()?,
+            limit: self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            offset: self.offset.unwrap_or(0).max(0),
+        })
+    }
+}
+
+/// Escape a snippet for HTML and turn the match markers into `<mark>` elements.
+fn highlight(snippet: &str) -> String {
+    snippet.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace(SEARCH_MARK_START, "<mark>")
+        .replace(SEARCH_MARK_END, "</mark>")
+}
+
+async fn get_search(ctx: State<ApiContext>,
+                    Query(params): Query<SearchParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let query = params.q.trim().to_string();
+    if query.is_empty() {
+        return Err(error(StatusCode::BAD_REQUEST, "q is required".to_string()));
+    }
+    let filter = params.filter()?;
+
+    let hits = db::search(&ctx.db, &query, &filter).await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e)))?;
+    let results: Vec<serde_json::Value> = hits.into_iter()
+        .map(|hit| {
+            let snippet = highlight(&hit.snippet);
+            let mut result = json!(hit);
+            result["snippet"] = json!(snippet);
+            result
+        })
+        .collect();
+    Ok(Json(json!({"status": "success","data": json!({
+        "query": query,
+        "limit": filter.limit,
+        "offset": filter.offset,
+        "results": results,
+    })})))
+}
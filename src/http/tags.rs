@@ -0,0 +1,150 @@
+use axum::{Json, Router};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{delete, get};
+use serde_json::json;
+
+use crate::db;
+use crate::http::ApiContext;
+use crate::http::api::{find_page, GardenScope};
+use crate::models::{normalize_tag, TagPayload};
+
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/tags", get(get_tags).post(post_tag))
+        .route("/api/tags/:id", get(get_tag).put(put_tag).delete(delete_tag))
+        .route("/api/pages/:slug/tags", get(get_page_tags).post(post_page_tag))
+        .route("/api/pages/:slug/tags/:name", delete(delete_page_tag))
+        .route("/api/gardens/:slug/tags", get(get_garden_tags))
+}
+
+type ApiError = (StatusCode, Json<serde_json::Value>);
+
+fn error(status: StatusCode, message: String) -> ApiError {
+    (status, Json(json!({"status": "error","message": message})))
+}
+
+fn internal_error(e: sqlx::Error) -> ApiError {
+    error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))
+}
+
+fn tag_not_found(id: &uuid::Uuid) -> ApiError {
+    error(StatusCode::NOT_FOUND, format!("tag with id: {} not found", id))
+}
+
+fn valid_name(name: &str) -> Result<String, ApiError> {
+    normalize_tag(name)
+        .ok_or_else(|| error(StatusCode::BAD_REQUEST, format!("invalid tag name: {}", name)))
+}
+
+fn save_error(e: sqlx::Error, name: &str) -> ApiError {
+    if e.to_string().contains("unique_name_key") {
+        return error(StatusCode::CONFLICT, format!("tag: {} already exists", name));
+    }
+    internal_error(e)
+}
+
+async fn get_tags(ctx: State<ApiContext>) -> Result<impl IntoResponse, ApiError> {
+    let tags = db::tags(&ctx.db).await.map_err(internal_error)?;
+    Ok(Json(json!({"status": "success","data": json!({
+        "tags": tags
+    })})))
+}
+
+async fn post_tag(ctx: State<ApiContext>,
+                  Json(payload): Json<TagPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = valid_name(&payload.name)?;
+    let tag = db::save_tag(&ctx.db, &name).await
+        .map_err(|e| save_error(e, &name))?;
+    Ok((StatusCode::CREATED, Json(json!({"status": "success","data": json!({
+        "tag": tag
+    })}))))
+}
+
+async fn get_tag(ctx: State<ApiContext>,
+                 Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    match db::tag_by_id(&ctx.db, &id).await {
+        Ok(Some(tag)) => Ok(Json(json!({"status": "success","data": json!({
+            "tag": tag
+        })}))),
+        Ok(None) => Err(tag_not_found(&id)),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+/// Rename a tag, on every page that has it.
+async fn put_tag(ctx: State<ApiContext>,
+                 Path(id): Path<uuid::Uuid>,
+                 Json(payload): Json<TagPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = valid_name(&payload.name)?;
+    match db::update_tag(&ctx.db, &id, &name).await {
+        Ok(Some(tag)) => Ok(Json(json!({"status": "success","data": json!({
+            "tag": tag
+        })}))),
+        Ok(None) => Err(tag_not_found(&id)),
+        Err(e) => Err(save_error(e, &name)),
+    }
+}
+
+async fn delete_tag(ctx: State<ApiContext>,
+                    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    match db::delete_tag(&ctx.db, &id).await {
+        Ok(result) if result.rows_affected() > 0 => Ok(StatusCode::NO_CONTENT),
+        Ok(_) => Err(tag_not_found(&id)),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+async fn get_page_tags(ctx: State<ApiContext>,
+                       Path(slug): Path<String>,
+                       Query(scope): Query<GardenScope>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = find_page(&ctx, &scope, &slug).await?;
+    let tags = db::page_tags(&ctx.db, &page.id).await.map_err(internal_error)?;
+    Ok(Json(json!({"status": "success","data": json!({
+        "tags": tags
+    })})))
+}
+
+async fn post_page_tag(ctx: State<ApiContext>,
+                       Path(slug): Path<String>,
+                       Query(scope): Query<GardenScope>,
+                       Json(payload): Json<TagPayload>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = valid_name(&payload.name)?;
+    let page = find_page(&ctx, &scope, &slug).await?;
+    let tag = db::add_page_tag(&ctx.db, &page.id, &name).await.map_err(internal_error)?;
+    Ok((StatusCode::CREATED, Json(json!({"status": "success","data": json!({
+        "tag": tag
+    })}))))
+}
+
+async fn delete_page_tag(ctx: State<ApiContext>,
+                         Path((slug, name)): Path<(String, String)>,
+                         Query(scope): Query<GardenScope>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = find_page(&ctx, &scope, &slug).await?;
+    let normalized = valid_name(&name)?;
+    match db::remove_page_tag(&ctx.db, &page.id, &normalized).await {
+        Ok(result) if result.rows_affected() > 0 => Ok(StatusCode::NO_CONTENT),
+        Ok(_) => Err(error(StatusCode::NOT_FOUND, format!("page with slug: {} has no tag: {}", slug, normalized))),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+/// The tag cloud of a garden: every tag of its pages, with how many pages have it.
+async fn get_garden_tags(ctx: State<ApiContext>,
+                         Path(slug): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    db::garden_by_slug(&ctx.db, &slug).await
+        .map_err(|_| error(StatusCode::NOT_FOUND, format!("garden with slug: {} not found", slug)))?;
+    let tags = db::garden_tag_counts(&ctx.db, &slug).await.map_err(internal_error)?;
+    Ok(Json(json!({"status": "success","data": json!({
+        "tags": tags
+    })})))
+}
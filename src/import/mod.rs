@@ -0,0 +1,741 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use log::warn;
+use percent_encoding::percent_decode_str;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use sqlx::{Pool, Postgres};
+
+use crate::artifacts::{artifact_location, save_artifact};
+use crate::db;
+use crate::extractor::{chunk_text, hashtags, replace_wiki_links, text_stats, wiki_links, ChunkOptions, WikiLink};
+use crate::models::{normalize_tag, Page};
+
+/// The author of the revisions an import writes. Pages whose latest revision is by someone else
+/// have been edited since they were imported, and aren't overwritten.
+const IMPORT_AUTHOR: &str = "import";
+
+/// The page type of notes without a `type` in their frontmatter.
+const DEFAULT_PAGE_TYPE: &str = "note";
+
+/// Where the attachments of imported notes are stored, under the slug of the garden.
+const ATTACHMENT_KEY: &str = "imports";
+
+const NOTE_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+const IMAGE_EXTENSIONS: [&str; 8] = ["apng", "avif", "bmp", "gif", "jpeg", "jpg", "png", "webp"];
+
+/// The largest file of a zipped vault that is read, uncompressed.
+const MAX_ZIP_FILE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// The most the files of a zipped vault may hold together, uncompressed, so that a small archive
+/// can't expand to fill the memory.
+const MAX_ZIP_SIZE: u64 = 512 * 1024 * 1024;
+
+/// A file of a vault, with its path relative to the root of the vault, `/` separated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VaultFile {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct ImportSummary {
+    pub garden: String,
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub attachments: usize,
+    /// Notes that weren't imported, and why.
+    pub conflicts: Vec<ImportConflict>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct ImportConflict {
+    /// The path of the note or attachment in the vault.
+    pub path: String,
+    /// The slug of the note, or the artifact key of the attachment.
+    pub slug: String,
+    pub reason: String,
+}
+
+/// A note converted to the fields of a page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Note {
+    path: String,
+    title: String,
+    slug: String,
+    content: String,
+    published: bool,
+    page_type: String,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct ConvertedVault {
+    notes: Vec<Note>,
+    /// The artifact keys of the files the notes refer to, by their path in the vault.
+    attachments: BTreeMap<String, String>,
+    conflicts: Vec<ImportConflict>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct Frontmatter {
+    title: Option<String>,
+    slug: Option<String>,
+    published: Option<bool>,
+    /// The flag Obsidian Publish uses.
+    publish: Option<bool>,
+    #[serde(rename = "type")]
+    page_type: Option<String>,
+    tags: Option<Tags>,
+    tag: Option<Tags>,
+}
+
+/// Tags as a list, or as one string of comma or space separated tags.
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum Tags {
+    List(Vec<String>),
+    Text(String),
+}
+
+impl Tags {
+    fn names(&self) -> Vec<String> {
+        let names: Vec<&str> = match self {
+            Tags::List(names) => names.iter().map(String::as_str).collect(),
+            Tags::Text(text) => text.split([',', ' ']).collect(),
+        };
+        names.into_iter().filter_map(normalize_tag).collect()
+    }
+}
+
+/// The files of a vault directory, leaving out hidden files and directories like `.obsidian`.
+pub(crate) fn read_vault_directory(dir: &Path) -> anyhow::Result<Vec<VaultFile>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in fs::read_dir(&current).with_context(|| format!("failed to read directory: {}", current.display()))? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_file() {
+                let relative: Vec<String> = path.strip_prefix(dir)?.components()
+                    .map(|component| component.as_os_str().to_string_lossy().to_string())
+                    .collect();
+                let data = fs::read(&path).with_context(|| format!("failed to read file: {}", path.display()))?;
+                files.push(VaultFile { path: relative.join("/"), data });
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The files of a zipped vault. When everything is in one top level directory, as when zipping
+/// the vault's folder, paths are relative to that directory.
+///
+/// Fails for archives with a file over `MAX_ZIP_FILE_SIZE`, or files over `MAX_ZIP_SIZE` in all.
+pub(crate) fn read_vault_zip(data: &[u8]) -> anyhow::Result<Vec<VaultFile>> {
+    read_zip(data, MAX_ZIP_FILE_SIZE, MAX_ZIP_SIZE)
+}
+
+fn read_zip(data: &[u8], max_file_size: u64, max_size: u64) -> anyhow::Result<Vec<VaultFile>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).context("failed to read zip file")?;
+    let mut files = Vec::new();
+    let mut total: u64 = 0;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let parts: Vec<String> = match file.enclosed_name() {
+            Some(path) => path.components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect(),
+            None => continue,
+        };
+        if parts.iter().any(|part| part.starts_with('.') || part == "__MACOSX") {
+            continue;
+        }
+        let path = parts.join("/");
+        if file.size() > max_file_size {
+            bail!("file {} of the zip file is larger than {} bytes", path, max_file_size);
+        }
+        // the size in the archive can be wrong, so the reads are limited too
+        let mut data = Vec::new();
+        file.take(max_file_size + 1).read_to_end(&mut data)?;
+        if data.len() as u64 > max_file_size {
+            bail!("file {} of the zip file is larger than {} bytes", path, max_file_size);
+        }
+        total += data.len() as u64;
+        if total > max_size {
+            bail!("the files of the zip file are larger than {} bytes", max_size);
+        }
+        files.push(VaultFile { path, data });
+    }
+
+    let root = files.first().and_then(|file| file.path.split_once('/')).map(|(root, _)| format!("{}/", root));
+    if let Some(root) = root {
+        if files.iter().all(|file| file.path.starts_with(&root)) {
+            for file in &mut files {
+                file.path = file.path[root.len()..].to_string();
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Import the Markdown notes of a vault into a garden, and the files they embed or link to as
+/// attachments.
+///
+/// Notes are matched to pages on their slug, so importing a vault again only updates the pages
+/// of notes that changed. Notes whose slug or title another note already has, and pages edited
+/// since they were imported or that weren't imported at all, are reported as conflicts and left
+/// alone.
+pub(crate) async fn import_vault(pool: &Pool<Postgres>, garden_slug: &String, files: Vec<VaultFile>) -> anyhow::Result<ImportSummary> {
+    let garden = db::garden_by_slug(pool, garden_slug).await
+        .with_context(|| format!("garden with slug: {} not found", garden_slug))?;
+    let vault = convert_vault(&garden.slug, &files);
+    let mut summary = ImportSummary {
+        garden: garden.slug.clone(),
+        created: 0,
+        updated: 0,
+        unchanged: 0,
+        attachments: 0,
+        conflicts: vault.conflicts,
+    };
+
+    let attachments: Vec<(String, String, Vec<u8>)> = files.into_iter()
+        .filter_map(|file| vault.attachments.get(&file.path).map(|key| (file.path, key.clone(), file.data)))
+        .collect();
+    let (saved, failed) = tokio::task::spawn_blocking(move || save_attachments(attachments)).await?;
+    summary.attachments = saved;
+    summary.conflicts.extend(failed);
+
+    for note in vault.notes {
+        let conflict = |reason: &str| ImportConflict {
+            path: note.path.clone(),
+            slug: note.slug.clone(),
+            reason: reason.to_string(),
+        };
+        let existing = db::garden_page(pool, &garden.slug, &note.slug).await?;
+        let page = match existing {
+            Some(page) if page.title == note.title && page.content == note.content
+                && page.published == note.published && page.page_type == note.page_type => {
+                summary.unchanged += 1;
+                save_tags(pool, &page, &note.tags).await;
+                continue;
+            }
+            Some(page) => {
+                let imported = db::page_revisions(pool, &page.id).await?.first()
+                    .map_or(false, |revision| revision.author.as_deref() == Some(IMPORT_AUTHOR));
+                if !imported {
+                    summary.conflicts.push(conflict("page has been edited since it was imported, or wasn't imported"));
+                    continue;
+                }
+                match db::update_page(pool, &page.id, &page.update_timestamp, &note.title, &note.content,
+                                      &note.published, &note.slug, &note.page_type, &text_stats(&note.content),
+                                      Some(IMPORT_AUTHOR), None).await {
+                    Ok(Some(page)) => {
+                        summary.updated += 1;
+                        page
+                    }
+                    Ok(None) => {
+                        summary.conflicts.push(conflict("page changed during the import"));
+                        continue;
+                    }
+                    Err(e) if is_duplicate(&e) => {
+                        summary.conflicts.push(conflict("another page of the garden has this title"));
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            None => match db::save_page(pool, &note.title, &note.content, &garden.id, &note.published,
+                                        &note.slug, &note.page_type, &text_stats(&note.content),
+                                        Some(IMPORT_AUTHOR)).await {
+                Ok(page) => {
+                    summary.created += 1;
+                    page
+                }
+                Err(e) if is_duplicate(&e) => {
+                    summary.conflicts.push(conflict("another page of the garden has this title"));
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            },
+        };
+        index_page(pool, &page).await;
+        save_tags(pool, &page, &note.tags).await;
+    }
+    Ok(summary)
+}
+
+/// Store the attachments of a vault, given by path, artifact key and content. Returns how many
+/// were saved, and the failures as conflicts.
+fn save_attachments(attachments: Vec<(String, String, Vec<u8>)>) -> (usize, Vec<ImportConflict>) {
+    let mut saved = 0;
+    let mut failed = Vec::new();
+    for (path, key, data) in attachments {
+        match save_artifact(&key, &data) {
+            Ok(_) => saved += 1,
+            Err(e) => {
+                warn!("Failed to save attachment {}: {}", key, e);
+                failed.push(ImportConflict { path, slug: key, reason: format!("failed to save attachment: {}", e) });
+            }
+        }
+    }
+    (saved, failed)
+}
+
+fn is_duplicate(e: &sqlx::Error) -> bool {
+    e.to_string().contains("duplicate key value violates unique constraint")
+}
+
+/// Chunk a created or updated page, and sync its hashtags and wiki-links.
+async fn index_page(pool: &Pool<Postgres>, page: &Page) {
+    let chunks = chunk_text(&page.content, &ChunkOptions::default());
+    if let Err(e) = db::save_page_chunks(pool, &page.id, &chunks).await {
+        warn!("Failed to save chunks of page {}: {}", page.id, e);
+    }
+    let tags: Vec<String> = hashtags(&page.content).iter()
+        .filter_map(|tag| normalize_tag(tag))
+        .collect();
+    if let Err(e) = db::save_page_hashtags(pool, &page.id, &tags).await {
+        warn!("Failed to save hashtags of page {}: {}", page.id, e);
+    }
+    if let Err(e) = db::save_page_links(pool, page, &wiki_links(&page.content)).await {
+        warn!("Failed to save links of page {}: {}", page.id, e);
+    }
+    if let Err(e) = db::resolve_page_links(pool, page).await {
+        warn!("Failed to resolve links to page {}: {}", page.id, e);
+    }
+}
+
+/// Add the frontmatter tags of a note to its page. Tags are only ever added, so tags added to the
+/// page by hand stay.
+async fn save_tags(pool: &Pool<Postgres>, page: &Page, tags: &[String]) {
+    for tag in tags {
+        if let Err(e) = db::add_page_tag(pool, &page.id, tag).await {
+            warn!("Failed to add tag {} to page {}: {}", tag, page.id, e);
+        }
+    }
+}
+
+/// The notes of a vault as pages of a garden, with their links pointing at the slugs of the
+/// notes and the artifact URLs of the attachments.
+fn convert_vault(garden_slug: &str, files: &[VaultFile]) -> ConvertedVault {
+    let mut converted = ConvertedVault::default();
+    let mut files: Vec<&VaultFile> = files.iter().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut notes: Vec<(Note, String)> = Vec::new();
+    let mut attachments: Vec<&str> = Vec::new();
+    for file in files {
+        if !is_note(&file.path) {
+            attachments.push(&file.path);
+            continue;
+        }
+        match parse_note(&file.path, &String::from_utf8_lossy(&file.data)) {
+            Ok(note) => {
+                let reason = notes.iter().find_map(|(other, _)| {
+                    if other.slug == note.slug {
+                        Some(format!("slug is also the slug of {}", other.path))
+                    } else if other.title == note.title {
+                        Some(format!("title is also the title of {}", other.path))
+                    } else {
+                        None
+                    }
+                });
+                match reason {
+                    Some(reason) => converted.conflicts.push(ImportConflict { path: note.path, slug: note.slug, reason }),
+                    None => {
+                        let body = note.content.clone();
+                        notes.push((note, body));
+                    }
+                }
+            }
+            Err(reason) => converted.conflicts.push(ImportConflict {
+                path: file.path.clone(),
+                slug: note_slug(&file.path),
+                reason,
+            }),
+        }
+    }
+
+    let index = VaultIndex::new(garden_slug, &notes.iter().map(|(note, _)| note).collect::<Vec<_>>(), &attachments);
+    for (mut note, body) in notes {
+        let dir = note.path.rsplit_once('/').map_or("", |(dir, _)| dir).to_string();
+        note.content = convert_links(&body, &dir, &index, &mut converted.attachments);
+        converted.notes.push(note);
+    }
+    converted
+}
+
+fn is_note(path: &str) -> bool {
+    extension(path).map_or(false, |extension| NOTE_EXTENSIONS.contains(&extension.as_str()))
+}
+
+fn extension(path: &str) -> Option<String> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.rsplit_once('.').map(|(_, extension)| extension.to_lowercase())
+}
+
+/// A path without the extension of its file.
+fn without_extension(path: &str) -> &str {
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
+    match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => &path[..name_start + dot],
+        _ => path,
+    }
+}
+
+/// The slug of a note from its path, `Projects/Bike Lanes.md` becoming `projects-bike-lanes`.
+fn note_slug(path: &str) -> String {
+    without_extension(path).split('/')
+        .map(slug::slugify)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn parse_note(path: &str, text: &str) -> Result<Note, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let (frontmatter, body) = split_frontmatter(text);
+    let frontmatter: Frontmatter = match frontmatter {
+        Some(yaml) if !yaml.trim().is_empty() => serde_yaml::from_str(yaml)
+            .map_err(|e| format!("invalid frontmatter: {}", e))?,
+        _ => Frontmatter::default(),
+    };
+
+    let name = without_extension(path).rsplit('/').next().unwrap_or_default();
+    let title = frontmatter.title.as_deref().map(str::trim).filter(|title| !title.is_empty())
+        .unwrap_or(name).to_string();
+    let slug = match frontmatter.slug.as_deref().map(slug::slugify).filter(|slug| !slug.is_empty()) {
+        Some(slug) => slug,
+        None => note_slug(path),
+    };
+    if slug.is_empty() {
+        return Err("no slug can be made from the path".to_string());
+    }
+    let mut tags: Vec<String> = Vec::new();
+    for tag in frontmatter.tags.iter().chain(frontmatter.tag.iter()).flat_map(Tags::names) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    Ok(Note {
+        path: path.to_string(),
+        title,
+        slug,
+        content: body.trim_start_matches(['\r', '\n']).to_string(),
+        published: frontmatter.published.or(frontmatter.publish).unwrap_or(false),
+        page_type: frontmatter.page_type.filter(|page_type| !page_type.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_PAGE_TYPE.to_string()),
+        tags,
+    })
+}
+
+/// The YAML between a `---` line at the very start and the next `---` or `...` line, and the
+/// rest of the note.
+fn split_frontmatter(text: &str) -> (Option<&str>, &str) {
+    let rest = match text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) {
+        Some(rest) => rest,
+        None => return (None, text),
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+/// Finds the notes and files links point at the way Obsidian does: by path relative to the
+/// note, by path from the root of the vault, or by name alone.
+struct VaultIndex<'a> {
+    garden_slug: &'a str,
+    /// Slugs of notes by lower case path without extension, and by name.
+    notes: HashMap<String, String>,
+    /// Paths of other files by lower case path, and by name.
+    files: HashMap<String, &'a str>,
+}
+
+impl<'a> VaultIndex<'a> {
+    fn new(garden_slug: &'a str, notes: &[&Note], files: &[&'a str]) -> Self {
+        let mut index = VaultIndex { garden_slug, notes: HashMap::new(), files: HashMap::new() };
+        for note in notes {
+            let path = without_extension(&note.path).to_lowercase();
+            let name = path.rsplit('/').next().unwrap_or_default().to_string();
+            index.notes.insert(path, note.slug.clone());
+            index.notes.entry(name).or_insert_with(|| note.slug.clone());
+            index.notes.entry(note.title.to_lowercase()).or_insert_with(|| note.slug.clone());
+        }
+        for file in files {
+            let path = file.to_lowercase();
+            let name = path.rsplit('/').next().unwrap_or_default().to_string();
+            index.files.insert(path, file);
+            index.files.entry(name).or_insert(file);
+        }
+        index
+    }
+
+    fn candidates(target: &str, dir: &str) -> Vec<String> {
+        let target = target.trim().trim_start_matches("./").to_lowercase();
+        let mut candidates = Vec::new();
+        if let Some(relative) = join_path(&dir.to_lowercase(), &target) {
+            candidates.push(relative);
+        }
+        candidates.push(target.trim_start_matches('/').to_string());
+        candidates
+    }
+
+    fn note(&self, target: &str, dir: &str) -> Option<&String> {
+        let target = if is_note(target) { without_extension(target) } else { target };
+        Self::candidates(target, dir).iter().find_map(|candidate| self.notes.get(candidate))
+    }
+
+    fn file(&self, target: &str, dir: &str) -> Option<&'a str> {
+        Self::candidates(target, dir).iter().find_map(|candidate| self.files.get(candidate).copied())
+    }
+
+    /// The artifact key of a file of the vault.
+    fn attachment_key(&self, path: &str) -> String {
+        let mut parts: Vec<String> = without_extension(path).split('/').map(slug::slugify).collect();
+        if let Some(extension) = extension(path) {
+            if let Some(name) = parts.last_mut() {
+                name.push('.');
+                name.push_str(&extension);
+            }
+        }
+        format!("{}/{}/{}", ATTACHMENT_KEY, slug::slugify(self.garden_slug), parts.join("/"))
+    }
+}
+
+/// `target` relative to `dir`, resolving `.` and `..`, or `None` if it leaves the vault.
+fn join_path(dir: &str, target: &str) -> Option<String> {
+    let mut parts: Vec<&str> = dir.split('/').filter(|part| !part.is_empty()).collect();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn is_image(path: &str) -> bool {
+    extension(path).map_or(false, |extension| IMAGE_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// Point the wiki-links, embeds, links and images of a note's Markdown at the slugs of the notes
+/// and the artifact URLs of the files they refer to, recording the files in `attachments`.
+///
+/// Embedded notes become links, as pages aren't embedded, and links that refer to nothing in the
+/// vault are left as they are.
+fn convert_links(markdown: &str, dir: &str, index: &VaultIndex, attachments: &mut BTreeMap<String, String>) -> String {
+    let mut attachment_url = |path: &str| {
+        let key = attachments.entry(path.to_string()).or_insert_with(|| index.attachment_key(path));
+        artifact_location(key).1
+    };
+
+    let markdown = replace_wiki_links(markdown, |link, label, embed| {
+        if let Some(slug) = index.note(&link.target, dir) {
+            return format!("[[{}|{}]]", slug, label);
+        }
+        match index.file(&link.target, dir) {
+            Some(path) if embed && is_image(path) => {
+                // the label of an embedded image is its size, like `300` or `300x200`
+                let alt = link.label.as_deref()
+                    .filter(|label| !label.chars().all(|c| c.is_ascii_digit() || c == 'x'))
+                    .unwrap_or_else(|| without_extension(path).rsplit('/').next().unwrap_or_default());
+                format!("![{}]({})", alt, attachment_url(path))
+            }
+            Some(path) => format!("[{}]({})", label, attachment_url(path)),
+            None => wiki_link_text(link, embed),
+        }
+    });
+
+    replace_markdown_links(&markdown, |destination, image, text| {
+        if destination.contains("://") || destination.starts_with(['#', '/']) || destination.starts_with("mailto:") {
+            return None;
+        }
+        let target = percent_decode_str(destination.split(['#', '?']).next().unwrap_or_default())
+            .decode_utf8_lossy().to_string();
+        if !image && is_note(&target) {
+            if let Some(slug) = index.note(&target, dir) {
+                return Some(format!("[[{}|{}]]", slug, text));
+            }
+        }
+        index.file(&target, dir).map(|path| {
+            let url = attachment_url(path);
+            if image { format!("![{}]({})", text, url) } else { format!("[{}]({})", text, url) }
+        })
+    })
+}
+
+fn wiki_link_text(link: &WikiLink, embed: bool) -> String {
+    let prefix = if embed { "!" } else { "" };
+    match &link.label {
+        Some(label) => format!("{}[[{}|{}]]", prefix, link.target, label),
+        None => format!("{}[[{}]]", prefix, link.target),
+    }
+}
+
+/// Replace inline links and images outside code with `replace(destination, image, text)`, keeping
+/// them as they are when it returns `None`. `text` is the link text or the image's alt text, as
+/// written.
+fn replace_markdown_links(markdown: &str, mut replace: impl FnMut(&str, bool, &str) -> Option<String>) -> String {
+    let mut replaced = String::with_capacity(markdown.len());
+    let mut copied = 0;
+    let options = Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH;
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        let (destination, image) = match &event {
+            Event::Start(Tag::Link(_, destination, _)) => (&**destination, false),
+            Event::Start(Tag::Image(_, destination, _)) => (&**destination, true),
+            _ => continue,
+        };
+        if range.start < copied {
+            continue;
+        }
+        // only inline links, `[text](destination)`, end with their destination
+        let source = &markdown[range.clone()];
+        let text_start = if image { 2 } else { 1 };
+        let text_end = match source.rfind("](") {
+            Some(text_end) if text_end >= text_start && source.ends_with(')') => text_end,
+            _ => continue,
+        };
+        if !source[text_end..].contains(destination) {
+            continue;
+        }
+        if let Some(replacement) = replace(destination, image, &source[text_start..text_end]) {
+            replaced.push_str(&markdown[copied..range.start]);
+            replaced.push_str(&replacement);
+            copied = range.end;
+        }
+    }
+    replaced.push_str(&markdown[copied..]);
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, data: &str) -> VaultFile {
+        VaultFile { path: path.to_string(), data: data.as_bytes().to_vec() }
+    }
+
+    #[test]
+    fn test_convert_vault() {
+        let files = vec![
+            file("Bike Lanes.md", "---\ntitle: Bike lanes\ntags: [Cycling, \"#streets\"]\npublish: true\n---\n\
+# Plan\n\nSee [[City Council|the council]], [[Projects/Budget#2023]] and [[Missing]].\n\n\
+![[lanes.png|300]] ![[City Council]] ![map](assets/Lane%20Map.jpg) [notes](Projects/Budget.md)\n\n\
+`[[Code]]` and [site](https://example.com)\n"),
+            file("City Council.md", "Meets on Mondays. #politics\n"),
+            file("Projects/Budget.md", "---\ntype: project\nslug: budget-2023\n---\nNumbers.\n"),
+            file("Archive/City Council.md", "Old notes.\n"),
+            file("Broken.md", "---\npublished: [\n---\nText\n"),
+            file("assets/lanes.png", "png"),
+            file("assets/Lane Map.jpg", "jpg"),
+            file("assets/unused.png", "png"),
+        ];
+        let vault = convert_vault("Notes", &files);
+
+        let slugs: Vec<&str> = vault.notes.iter().map(|note| note.slug.as_str()).collect();
+        assert_eq!(slugs, vec!["archive-city-council", "bike-lanes", "budget-2023"]);
+        assert_eq!(vault.conflicts.len(), 2);
+        assert_eq!(vault.conflicts[0].path, "Broken.md");
+        assert!(vault.conflicts[0].reason.starts_with("invalid frontmatter"));
+        assert_eq!(vault.conflicts[1].path, "City Council.md");
+        assert_eq!(vault.conflicts[1].reason, "title is also the title of Archive/City Council.md");
+
+        let note = &vault.notes[1];
+        assert_eq!(note.title, "Bike lanes");
+        assert!(note.published);
+        assert_eq!(note.page_type, "note");
+        assert_eq!(note.tags, vec!["cycling", "streets"]);
+        assert_eq!(note.content, "# Plan\n\n\
+See [[archive-city-council|the council]], [[budget-2023|Projects/Budget]] and [[Missing]].\n\n\
+![lanes](/artifacts/imports/notes/assets/lanes.png) [[archive-city-council|City Council]] \
+![map](/artifacts/imports/notes/assets/lane-map.jpg) [[budget-2023|notes]]\n\n\
+`[[Code]]` and [site](https://example.com)\n");
+        assert_eq!(vault.notes[2].page_type, "project");
+        assert!(!vault.notes[2].published);
+
+        let attachments: Vec<(&str, &str)> = vault.attachments.iter()
+            .map(|(path, key)| (path.as_str(), key.as_str()))
+            .collect();
+        assert_eq!(attachments, vec![
+            ("assets/Lane Map.jpg", "imports/notes/assets/lane-map.jpg"),
+            ("assets/lanes.png", "imports/notes/assets/lanes.png"),
+        ]);
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (path, data) in files {
+            if path.ends_with('/') {
+                zip.add_directory(*path, options).unwrap();
+            } else {
+                zip.start_file(*path, options).unwrap();
+                zip.write_all(data).unwrap();
+            }
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn paths(files: &[VaultFile]) -> Vec<&str> {
+        files.iter().map(|file| file.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_read_zip_strips_top_level_directory() {
+        let data = zip(&[
+            ("Vault/", b""),
+            ("Vault/Note.md", b"text"),
+            ("Vault/assets/map.png", b"png"),
+            ("Vault/.obsidian/app.json", b"{}"),
+            ("__MACOSX/Vault/._Note.md", b""),
+        ]);
+        let files = read_vault_zip(&data).unwrap();
+        assert_eq!(paths(&files), vec!["Note.md", "assets/map.png"]);
+        assert_eq!(files[0].data, b"text");
+
+        // files outside the directory keep their paths
+        let data = zip(&[("Vault/Note.md", b"text"), ("Other.md", b"text")]);
+        assert_eq!(paths(&read_vault_zip(&data).unwrap()), vec!["Vault/Note.md", "Other.md"]);
+    }
+
+    #[test]
+    fn test_read_zip_size_limits() {
+        let data = zip(&[("a.md", &[b'a'; 10]), ("b.md", &[b'b'; 10])]);
+        assert_eq!(read_zip(&data, 10, 20).unwrap().len(), 2);
+
+        let error = read_zip(&data, 9, 20).unwrap_err().to_string();
+        assert_eq!(error, "file a.md of the zip file is larger than 9 bytes");
+        let error = read_zip(&data, 10, 19).unwrap_err().to_string();
+        assert_eq!(error, "the files of the zip file are larger than 19 bytes");
+    }
+}
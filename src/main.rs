@@ -14,7 +14,8 @@ use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use static_support::using_serve_dir;
-use crate::config::Config;
+use crate::config::{Command, Config};
+use crate::export::{ExportFormat, ExportOptions};
 use crate::models::{Source, SourceType};
 
 use clap::Parser;
@@ -31,6 +32,10 @@ mod fetcher;
 mod extractor;
 mod tasks;
 mod rating;
+mod newsletter;
+mod artifacts;
+mod export;
+mod import;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -54,6 +59,10 @@ async fn main() -> anyhow::Result<()> {
         .context("could not connect to database_url")?;
     tracing::info!("connected to database");
 
+    if let Some(command) = &config.command {
+        return run_command(command, &db).await;
+    }
+
     // using sync NATS client for now
     let nats_client = nats::connect(&config.nats_url)
         .context("could not connect to nats_url")?;
@@ -84,11 +93,48 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    if let Some(smtp_addr) = config.smtp_addr {
+        let pool = db.clone();
+        tokio::task::spawn(async move {
+            if let Err(err) = newsletter::smtp::serve(smtp_addr, pool).await {
+                tracing::error!("Error running smtp listener: {err:?}");
+            }
+        });
+    }
+
     http::serve(config, db, nats_client).await?;
 
     Ok(())
 }
 
+async fn run_command(command: &Command, db: &PgPool) -> anyhow::Result<()> {
+    match command {
+        Command::Export { garden, output, zip, base_url } => {
+            let options = ExportOptions {
+                base_url: base_url.clone(),
+                format: if *zip { ExportFormat::Zip } else { ExportFormat::Directory },
+            };
+            let summary = export::export_garden(db, garden, output, &options).await?;
+            tracing::info!("exported {} pages of {} to {}", summary.pages, summary.garden, summary.output.display());
+        }
+        Command::Import { garden, vault } => {
+            let vault = vault.clone();
+            let files = tokio::task::spawn_blocking(move || if vault.is_dir() {
+                import::read_vault_directory(&vault)
+            } else {
+                import::read_vault_zip(&std::fs::read(&vault).with_context(|| format!("failed to read {}", vault.display()))?)
+            }).await??;
+            let summary = import::import_vault(db, garden, files).await?;
+            tracing::info!("imported into {}: {} created, {} updated, {} unchanged, {} attachments",
+                summary.garden, summary.created, summary.updated, summary.unchanged, summary.attachments);
+            for conflict in &summary.conflicts {
+                tracing::warn!("skipped {} ({}): {}", conflict.path, conflict.slug, conflict.reason);
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn handle_new_company(company_name: &String) {
     tracing::info!("Handling new company {company_name}");
 }
@@ -1,7 +1,10 @@
+use std::collections::HashSet;
 use chrono::Utc;
 use sqlx::{Error, Pool, Postgres};
+use url::Url;
 use webpage::Webpage;
 use crate::db;
+use crate::extractor::{extract_metadata, Metadata, SiteRules, TextStats};
 
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Deserialize, serde::Serialize)]
 pub struct Page {
@@ -14,6 +17,13 @@ pub struct Page {
     pub create_timestamp: chrono::DateTime<Utc>,
     pub update_timestamp: chrono::DateTime<Utc>,
     pub page_type: String,
+    pub language: Option<String>,
+    pub word_count: Option<i32>,
+    pub reading_time: Option<i32>,
+    pub flesch_reading_ease: Option<f64>,
+    pub flesch_kincaid_grade: Option<f64>,
+    /// URL of a thumbnail of the first image in the content, in the artifact store.
+    pub thumbnail_url: Option<String>,
 }
 
 impl Page {
@@ -36,8 +46,53 @@ impl Page {
             create_timestamp,
             update_timestamp,
             page_type,
+            language: None,
+            word_count: None,
+            reading_time: None,
+            flesch_reading_ease: None,
+            flesch_kincaid_grade: None,
+            thumbnail_url: None,
         }
     }
+
+    /// The entity tag of the page's current version, quoted as in an `ETag` header.
+    pub fn etag(&self) -> String {
+        format!("\"{}-{}\"", self.id.simple(), self.update_timestamp.timestamp_micros())
+    }
+}
+
+/// `slug`, or the first of `slug-2`, `slug-3`, ... that isn't `taken`.
+pub(crate) fn free_slug(slug: &str, taken: &HashSet<String>) -> String {
+    std::iter::once(slug.to_string())
+        .chain((2..).map(|n| format!("{}-{}", slug, n)))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
+
+/// A partial update of a page, fields left out are kept as they are.
+#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize)]
+pub(crate) struct PagePatch {
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub published: Option<bool>,
+    pub slug: Option<String>,
+    pub page_type: Option<String>,
+    /// Who made the edit, recorded on the revision it creates.
+    pub author: Option<String>,
+}
+
+/// A saved version of a page's title and content, see `db::save_page_revision`.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub(crate) struct PageRevision {
+    pub id: uuid::Uuid,
+    pub page_id: uuid::Uuid,
+    pub revision_number: i32,
+    pub title: String,
+    pub content: String,
+    pub author: Option<String>,
+    /// The revision this one restored, if it was created by a revert.
+    pub reverted_from: Option<i32>,
+    pub create_timestamp: chrono::DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Deserialize, serde::Serialize)]
@@ -76,7 +131,9 @@ pub struct Company {
     pub address: Option<String>,
     pub exchange: Option<String>,
     pub num_employees_min: Option<i32>,
-    pub num_employees_max: Option<i32>
+    pub num_employees_max: Option<i32>,
+    pub description: Option<String>,
+    pub logo_url: Option<String>,
 }
 
 impl Company {
@@ -103,6 +160,8 @@ impl Company {
             exchange: None,
             num_employees_min: None,
             num_employees_max: None,
+            description: None,
+            logo_url: None,
         }
     }
 }
@@ -127,14 +186,17 @@ impl WebpageRequest {
 pub(crate) struct WebpageResponse {
     pub request: WebpageRequest,
     pub webpage: Webpage,
+    pub metadata: Metadata,
     pub create_timestamp: chrono::DateTime<Utc>,
 }
 
 impl WebpageResponse {
     pub fn new(request: WebpageRequest, webpage: Webpage) -> Self {
+        let metadata = extract_metadata(&webpage.http.body, Url::parse(&webpage.http.url).ok().as_ref());
         Self {
             request,
             webpage,
+            metadata,
             create_timestamp: Utc::now(),
         }
     }
@@ -198,6 +260,8 @@ pub(crate) struct Source {
     pub state: Option<String>,
     pub city: Option<String>,
     pub create_timestamp: chrono::DateTime<Utc>,
+    /// URL of a thumbnail of the site's preview image, in the artifact store.
+    pub thumbnail_url: Option<String>,
 }
 
 impl Source {
@@ -214,6 +278,7 @@ impl Source {
             state: None,
             city: None,
             create_timestamp: Utc::now().into(),
+            thumbnail_url: None,
         }
     }
 
@@ -231,6 +296,8 @@ pub(crate) struct Feed {
     pub create_timestamp: chrono::DateTime<Utc>,
     pub feed_type: Option<String>,
     pub ttl: Option<i32>,
+    /// Fetch the full article of each new item, for feeds that only carry a teaser.
+    pub full_text: bool,
 }
 
 impl Feed {
@@ -243,6 +310,7 @@ impl Feed {
             create_timestamp: Utc::now().into(),
             feed_type,
             ttl: None,
+            full_text: false,
         }
     }
 
@@ -251,6 +319,33 @@ impl Feed {
     }
 }
 
+/// Filters on the text statistics of news items and pages.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct TextFilter {
+    pub language: Option<String>,
+    pub min_reading_time: Option<i32>,
+    pub max_reading_time: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub(crate) struct FeedPatch {
+    pub full_text: Option<bool>,
+}
+
+/// Encrypted fetch credentials for a source or a feed.
+///
+/// Deliberately not `Serialize`, see `fetcher::FetchProfileSummary` for the API representation.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub(crate) struct FetchProfileRecord {
+    pub id: uuid::Uuid,
+    pub source_id: Option<uuid::Uuid>,
+    pub feed_id: Option<uuid::Uuid>,
+    pub nonce: Vec<u8>,
+    pub credentials: Vec<u8>,
+    pub create_timestamp: chrono::DateTime<Utc>,
+    pub update_timestamp: chrono::DateTime<Utc>,
+}
+
 
 #[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
 pub(crate) struct NewsItem {
@@ -263,6 +358,18 @@ pub(crate) struct NewsItem {
     pub create_timestamp: chrono::DateTime<Utc>,
     pub raw_content_path: Option<String>,
     pub text_content_path: Option<String>,
+    /// The full article, for items of full-text feeds.
+    pub content_html: Option<String>,
+    pub content_text: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub language: Option<String>,
+    pub word_count: Option<i32>,
+    pub reading_time: Option<i32>,
+    pub flesch_reading_ease: Option<f64>,
+    pub flesch_kincaid_grade: Option<f64>,
+    /// URL of a thumbnail of the article's lead image, in the artifact store.
+    pub thumbnail_url: Option<String>,
 }
 
 impl NewsItem {
@@ -282,10 +389,209 @@ impl NewsItem {
             create_timestamp: Utc::now().into(),
             raw_content_path: None,
             text_content_path: None,
+            content_html: None,
+            content_text: None,
+            author: None,
+            description: None,
+            language: None,
+            word_count: None,
+            reading_time: None,
+            flesch_reading_ease: None,
+            flesch_kincaid_grade: None,
+            thumbnail_url: None,
         }
     }
 
+    pub fn set_text_stats(&mut self, stats: &TextStats) {
+        self.language = stats.language.clone();
+        self.word_count = Some(stats.word_count);
+        self.reading_time = Some(stats.reading_time);
+        self.flesch_reading_ease = stats.flesch_reading_ease;
+        self.flesch_kincaid_grade = stats.flesch_kincaid_grade;
+    }
+
     pub async fn save(&self, pool: &Pool<Postgres>) -> anyhow::Result<uuid::Uuid> {
         db::save_news_item(self, pool).await
     }
-}
\ No newline at end of file
+}
+
+/// The other end of a link between news articles, or from an article to a source.
+///
+/// `news_id` and `source_id` are unset for links to pages that aren't known.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub(crate) struct Citation {
+    pub url: String,
+    pub news_id: Option<uuid::Uuid>,
+    pub title: Option<String>,
+    pub source_id: Option<uuid::Uuid>,
+    pub source_name: Option<String>,
+    pub create_timestamp: chrono::DateTime<Utc>,
+}
+
+/// A wiki-link from or to a page. `page_id`, `slug` and `title` are those of the page at the
+/// other end, which is missing for links to pages that don't exist yet.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub(crate) struct PageLink {
+    pub target: String,
+    pub label: Option<String>,
+    pub page_id: Option<uuid::Uuid>,
+    pub slug: Option<String>,
+    pub title: Option<String>,
+    pub create_timestamp: chrono::DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub(crate) struct Tag {
+    pub id: uuid::Uuid,
+    pub name: String,
+}
+
+/// A tag and the number of pages of a garden with it, for tag clouds.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, serde::Serialize)]
+pub(crate) struct TagCount {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub(crate) struct TagPayload {
+    pub name: String,
+}
+
+/// Longest tag name the `tag` table holds.
+const MAX_TAG_LENGTH: usize = 100;
+
+/// Tag names are stored lower case and without a leading `#`, so `#Cycling` and `cycling` are
+/// the same tag.
+pub(crate) fn normalize_tag(name: &str) -> Option<String> {
+    let name = name.trim().trim_start_matches('#').trim().to_lowercase();
+    Some(name).filter(|name| !name.is_empty() && name.chars().count() <= MAX_TAG_LENGTH)
+}
+
+/// A page, news item or source matching a search, see `db::search`.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub(crate) struct SearchHit {
+    /// `page`, `news` or `source`.
+    pub kind: String,
+    pub id: uuid::Uuid,
+    pub title: String,
+    /// The slug of a page.
+    pub slug: Option<String>,
+    /// The URL of a news item or source.
+    pub url: Option<String>,
+    /// Passages around the matching words, which are marked with `SEARCH_MARK_START` and
+    /// `SEARCH_MARK_END`.
+    pub snippet: String,
+    /// When a page was last updated, a news item published or a source added.
+    pub timestamp: chrono::DateTime<Utc>,
+    pub rank: f32,
+}
+
+/// Markers around matching words in `SearchHit::snippet`, which can't occur in ordinary text.
+pub(crate) const SEARCH_MARK_START: &str = "\u{2}";
+pub(crate) const SEARCH_MARK_END: &str = "\u{3}";
+
+/// Filters of a search. `garden` and `tag` only apply to pages, so they leave out news and sources.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct SearchFilter {
+    /// Kinds of results to include, all of them if empty.
+    pub kinds: Vec<String>,
+    pub garden: Option<String>,
+    pub tag: Option<String>,
+    pub from: Option<chrono::DateTime<Utc>>,
+    pub to: Option<chrono::DateTime<Utc>>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Site specific extraction selectors for a domain and its subdomains.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub(crate) struct ExtractionRule {
+    pub id: uuid::Uuid,
+    pub domain: String,
+    pub content_selector: Option<String>,
+    pub remove_selectors: Vec<String>,
+    pub date_selector: Option<String>,
+    pub author_selector: Option<String>,
+    pub create_timestamp: chrono::DateTime<Utc>,
+    pub update_timestamp: chrono::DateTime<Utc>,
+}
+
+impl ExtractionRule {
+    pub fn site_rules(&self) -> SiteRules {
+        SiteRules {
+            content_selector: self.content_selector.clone(),
+            remove_selectors: self.remove_selectors.clone(),
+            date_selector: self.date_selector.clone(),
+            author_selector: self.author_selector.clone(),
+        }
+    }
+}
+
+/// The body of a request creating or replacing an `ExtractionRule`.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub(crate) struct ExtractionRulePayload {
+    pub domain: String,
+    #[serde(flatten)]
+    pub rules: SiteRules,
+}
+
+/// A stored passage of a news item's text or a page's content, see `extractor::chunk_text`.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, serde::Serialize)]
+pub(crate) struct ChunkRecord {
+    pub id: uuid::Uuid,
+    pub news_id: Option<uuid::Uuid>,
+    pub page_id: Option<uuid::Uuid>,
+    pub chunk_index: i32,
+    pub start_offset: i32,
+    pub end_offset: i32,
+    pub token_count: i32,
+    pub heading: Option<String>,
+    pub content: String,
+    pub create_timestamp: chrono::DateTime<Utc>,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    /// A page with fixed timestamps, for tests of code handling pages.
+    pub(crate) fn page() -> Page {
+        let timestamp = Utc.with_ymd_and_hms(2023, 6, 1, 12, 0, 0).unwrap();
+        Page::new("Title".to_string(), "title".to_string(), "content".to_string(), uuid::Uuid::new_v4(),
+                  true, timestamp, timestamp, "page".to_string())
+    }
+
+    /// A garden with fixed timestamps, for tests of code handling gardens.
+    pub(crate) fn garden() -> Garden {
+        let timestamp = Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap();
+        Garden::new("Streets".to_string(), "streets".to_string(), timestamp, timestamp)
+    }
+
+    #[test]
+    fn test_page_etag() {
+        let mut page = page();
+        let etag = page.etag();
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert!(etag.contains(&page.id.simple().to_string()));
+        assert_eq!(etag, page.etag());
+
+        page.update_timestamp += Duration::microseconds(1);
+        assert_ne!(etag, page.etag());
+    }
+
+    #[test]
+    fn test_free_slug() {
+        let taken = |slugs: &[&str]| slugs.iter().map(|slug| slug.to_string()).collect::<HashSet<_>>();
+        assert_eq!(free_slug("about", &taken(&[])), "about");
+        assert_eq!(free_slug("about", &taken(&["about-2"])), "about");
+        assert_eq!(free_slug("about", &taken(&["about"])), "about-2");
+        assert_eq!(free_slug("about", &taken(&["about", "about-2", "about-3"])), "about-4");
+        // the first gap is used
+        assert_eq!(free_slug("about", &taken(&["about", "about-3"])), "about-2");
+        assert_eq!(free_slug("about", &taken(&["about", "about-us", "about-2"])), "about-3");
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs::{self, File};
+use std::io::Write;
+
+use chrono::{DateTime, TimeZone, Utc};
+use kuchiki::NodeRef;
+use kuchiki::traits::*;
+use log::{error, info};
+use mail_parser::MessageParser;
+use sqlx::{Pool, Postgres};
+
+use crate::extractor::{extract_text_from_str, is_tracking_pixel, text_stats};
+use crate::models::{Feed, NewsItem, Source};
+
+pub(crate) mod smtp;
+
+/// The `source_type` id of email newsletters.
+pub(crate) const NEWSLETTER_SOURCE_TYPE: i32 = 14;
+
+/// Where newsletter bodies are written, one directory per sender.
+const NEWSLETTER_DIR: &str = "downloads/newsletters";
+
+/// Link text and URLs that identify unsubscribe and preference center links.
+const UNSUBSCRIBE_MARKERS: [&str; 5] = ["unsubscribe", "opt-out", "optout", "manage preferences", "email preferences"];
+
+/// A single email, reduced to what's needed to store it as a `NewsItem`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NewsletterMessage {
+    pub sender_name: Option<String>,
+    pub sender_address: String,
+    pub subject: String,
+    pub message_id: String,
+    pub date: DateTime<Utc>,
+    pub html: Option<String>,
+    pub text: Option<String>,
+}
+
+/// Parse a raw RFC 5322 message, returning `None` if it has no usable sender.
+pub(crate) fn parse_message(raw: &[u8]) -> Option<NewsletterMessage> {
+    let message = MessageParser::default().parse(raw)?;
+    let from = message.from()?.first()?;
+    let sender_address = from.address()?.to_lowercase();
+    let sender_name = from.name().map(|name| name.to_string());
+    let subject = message.subject().unwrap_or("n/a").to_string();
+    let date = message.date()
+        .and_then(|date| Utc.timestamp_opt(date.to_timestamp(), 0).single())
+        .unwrap_or_else(Utc::now);
+    // fall back to something stable so that re-importing the same message is a no-op
+    let message_id = message.message_id()
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| format!("{}-{}-{}", sender_address, date.timestamp(), slug::slugify(&subject)));
+
+    Some(NewsletterMessage {
+        sender_name,
+        sender_address,
+        subject,
+        message_id,
+        date,
+        html: message.body_html(0).map(|html| html.into_owned()),
+        text: message.body_text(0).map(|text| text.into_owned()),
+    })
+}
+
+/// Split an mbox file into its raw messages.
+pub(crate) fn split_mbox(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+
+    for line in data.split_inclusive(|b| *b == b'\n') {
+        if line.starts_with(b"From ") {
+            if let Some(message) = current.take() {
+                messages.push(message);
+            }
+            current = Some(Vec::new());
+            continue;
+        }
+        let Some(message) = current.as_mut() else {
+            continue;
+        };
+        // undo mboxrd quoting of lines that start with "From "
+        let unquoted = line.iter().position(|b| *b != b'>')
+            .filter(|i| *i > 0 && line[*i..].starts_with(b"From "))
+            .map_or(line, |_| &line[1..]);
+        message.extend_from_slice(unquoted);
+    }
+
+    if let Some(message) = current {
+        messages.push(message);
+    }
+    messages
+}
+
+/// Remove unsubscribe links and tracking pixels from a newsletter body.
+pub(crate) fn clean_html(html: &str) -> String {
+    let document = kuchiki::parse_html().one(html);
+
+    let mut to_remove: Vec<NodeRef> = Vec::new();
+    if let Ok(links) = document.select("a") {
+        for link in links {
+            let href = link.attributes.borrow().get("href").unwrap_or_default().to_lowercase();
+            let text = link.text_contents().to_lowercase();
+            if UNSUBSCRIBE_MARKERS.iter().any(|m| href.contains(m) || text.contains(m)) {
+                to_remove.push(link.as_node().clone());
+            }
+        }
+    }
+    if let Ok(images) = document.select("img") {
+        for image in images {
+            if is_tracking_pixel(&image.attributes.borrow()) {
+                to_remove.push(image.as_node().clone());
+            }
+        }
+    }
+
+    for node in to_remove {
+        node.detach();
+    }
+
+    document.to_string()
+}
+
+/// Save a message as a `NewsItem` of the sender's newsletter `Source`.
+///
+/// The sender is mapped onto a source and a feed with a `mailto:` URL, so repeated messages
+/// from the same sender end up on the same source. Re-saving a message is a no-op.
+pub(crate) async fn save_message(pool: &Pool<Postgres>, message: &NewsletterMessage) -> anyhow::Result<uuid::Uuid> {
+    let mailto = format!("mailto:{}", message.sender_address);
+    let name = truncate(message.sender_name.clone().unwrap_or(message.sender_address.clone()), 100);
+
+    let source = Source::new(name.clone(), mailto.clone(), NEWSLETTER_SOURCE_TYPE);
+    let source_id = source.save(pool).await?;
+
+    let feed = Feed::new(source_id, mailto.clone(), Some(name), Some("Email".to_string()));
+    let feed_id = feed.save(pool).await?;
+
+    let mut news_item = NewsItem::new(
+        feed_id,
+        truncate(message.message_id.clone(), 255),
+        message.subject.clone(),
+        message.date,
+        format!("mid:{}", message.message_id.trim_matches(|c| c == '<' || c == '>')),
+    );
+
+    if let Err(e) = write_content(message, &mut news_item) {
+        error!("Error writing newsletter content for {}: {}", message.message_id, e);
+    }
+
+    let id = news_item.save(pool).await?;
+    info!("Newsletter message saved: {} from {}", id, message.sender_address);
+    Ok(id)
+}
+
+/// Parse and save every message in `data`, which is either a single message or an mbox.
+pub(crate) async fn save_messages(pool: &Pool<Postgres>, data: &[u8]) -> (usize, usize) {
+    let raw_messages = if data.starts_with(b"From ") { split_mbox(data) } else { vec![data.to_vec()] };
+
+    let (mut saved, mut failed) = (0, 0);
+    for raw in raw_messages {
+        let result = match parse_message(&raw) {
+            Some(message) => save_message(pool, &message).await,
+            None => Err(anyhow::anyhow!("unable to parse message")),
+        };
+        match result {
+            Ok(_) => saved += 1,
+            Err(e) => {
+                error!("Error saving newsletter message: {}", e);
+                failed += 1;
+            }
+        }
+    }
+    (saved, failed)
+}
+
+/// Write the cleaned HTML body and its extracted text, setting their paths and the text's
+/// statistics on `news_item`.
+fn write_content(message: &NewsletterMessage, news_item: &mut NewsItem) -> std::io::Result<()> {
+    let dir = format!("{}/{}", NEWSLETTER_DIR, slug::slugify(&message.sender_address));
+    fs::create_dir_all(&dir)?;
+    let file_slug = truncate(slug::slugify(format!("{}-{}", message.date.format("%Y%m%d"), message.subject)), 100);
+
+    let (raw_content_path, text) = match &message.html {
+        Some(html) => {
+            let cleaned = clean_html(html);
+            let path = format!("{}/{}.html", dir, file_slug);
+            File::create(&path)?.write_all(cleaned.as_bytes())?;
+            let text = extract_text_from_str(cleaned, true).ok().or(message.text.clone());
+            (Some(path), text)
+        }
+        None => (None, message.text.clone()),
+    };
+
+    news_item.raw_content_path = raw_content_path;
+    if let Some(text) = text {
+        let path = format!("{}/{}-content.txt", dir, file_slug);
+        File::create(&path)?.write_all(text.as_bytes())?;
+        news_item.text_content_path = Some(path);
+        news_item.set_text_stats(&text_stats(&text));
+    }
+
+    Ok(())
+}
+
+fn truncate(s: String, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((i, _)) => s[..i].to_string(),
+        None => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_html_removes_unsubscribe_and_pixels() {
+        let html = r#"<html><body>
+<p>Hello <a href="https://example.com/post">read more</a></p>
+<a href="https://example.com/u?id=1">Unsubscribe</a>
+<img src="https://example.com/logo.png" width="200">
+<img src="https://example.com/x.gif" width="1" height="1">
+<img src="https://mail.example.com/track/open?id=1">
+</body></html>"#;
+        let cleaned = clean_html(html);
+        assert!(cleaned.contains("read more"));
+        assert!(cleaned.contains("logo.png"));
+        assert!(!cleaned.contains("Unsubscribe"));
+        assert!(!cleaned.contains("x.gif"));
+        assert!(!cleaned.contains("track/open"));
+    }
+
+    #[test]
+    fn test_split_mbox() {
+        let mbox = b"From a@example.com Mon Jul  3 10:00:00 2023\nSubject: one\n\n>From here\n\nFrom b@example.com Mon Jul  3 11:00:00 2023\nSubject: two\n\nbody\n";
+        let messages = split_mbox(mbox);
+        assert_eq!(messages.len(), 2);
+        assert!(String::from_utf8_lossy(&messages[0]).contains("\nFrom here"));
+        assert!(String::from_utf8_lossy(&messages[1]).starts_with("Subject: two"));
+    }
+}
@@ -0,0 +1,122 @@
+use std::net::SocketAddr;
+
+use anyhow::Context;
+use sqlx::{Pool, Postgres};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::newsletter::save_messages;
+
+/// Messages larger than this are rejected.
+const MAX_MESSAGE_SIZE: usize = 25 * 1024 * 1024; /* 25mb */
+
+/// Run a minimal SMTP server that accepts every message and saves it as a newsletter.
+///
+/// This is meant to sit behind a mail forwarder on a trusted network, it does no
+/// authentication, TLS or relaying.
+pub(crate) async fn serve(addr: SocketAddr, pool: Pool<Postgres>) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await
+        .context("could not bind smtp listener")?;
+    tracing::info!("smtp listening on {}", addr);
+
+    loop {
+        let (stream, peer) = listener.accept().await.context("error accepting smtp connection")?;
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &pool).await {
+                tracing::error!("smtp session with {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut has_sender = false;
+    let mut has_recipient = false;
+
+    writer.write_all(b"220 langolier ESMTP ready\r\n").await?;
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let command = line.trim_end().to_uppercase();
+
+        let reply: &[u8] = if command.starts_with("EHLO") {
+            b"250-langolier\r\n250-8BITMIME\r\n250 SIZE 26214400\r\n"
+        } else if command.starts_with("HELO") {
+            b"250 langolier\r\n"
+        } else if command.starts_with("MAIL FROM:") {
+            has_sender = true;
+            b"250 OK\r\n"
+        } else if command.starts_with("RCPT TO:") {
+            if has_sender {
+                has_recipient = true;
+                b"250 OK\r\n"
+            } else {
+                b"503 MAIL first\r\n"
+            }
+        } else if command == "DATA" {
+            if !has_recipient {
+                b"503 RCPT first\r\n"
+            } else {
+                writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await?;
+                let reply: &[u8] = match read_data(&mut reader).await? {
+                    Some(data) => {
+                        let (_, failed) = save_messages(pool, &data).await;
+                        if failed == 0 { b"250 OK\r\n" } else { b"451 Unable to save message\r\n" }
+                    }
+                    None => b"552 Message too large\r\n",
+                };
+                has_sender = false;
+                has_recipient = false;
+                reply
+            }
+        } else if command == "RSET" {
+            has_sender = false;
+            has_recipient = false;
+            b"250 OK\r\n"
+        } else if command == "NOOP" {
+            b"250 OK\r\n"
+        } else if command == "QUIT" {
+            writer.write_all(b"221 Bye\r\n").await?;
+            return Ok(());
+        } else {
+            b"502 Command not implemented\r\n"
+        };
+
+        writer.write_all(reply).await?;
+    }
+}
+
+/// Read a message body up to the terminating `.` line, undoing dot-stuffing.
+///
+/// Returns `None` if the message exceeds `MAX_MESSAGE_SIZE`, after consuming it.
+async fn read_data<R>(reader: &mut R) -> anyhow::Result<Option<Vec<u8>>>
+    where R: AsyncBufReadExt + Unpin
+{
+    let mut data = Vec::new();
+    let mut too_large = false;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            anyhow::bail!("connection closed during DATA");
+        }
+        if line == b".\r\n" || line == b".\n" {
+            break;
+        }
+        if too_large {
+            continue;
+        }
+        let unstuffed = if line.starts_with(b"..") { &line[1..] } else { &line[..] };
+        data.extend_from_slice(unstuffed);
+        too_large = data.len() > MAX_MESSAGE_SIZE;
+    }
+
+    Ok(if too_large { None } else { Some(data) })
+}